@@ -0,0 +1,250 @@
+use std::f64::consts::PI;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::{Error, Result}, heightmap::HeightMap, unity::Color32};
+
+/// Attributes `TerrainAnalysis` derives from a height field.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TerrainAttribute {
+    /// degrees from horizontal
+    #[default]
+    Slope,
+    /// compass direction the slope faces in degrees, clockwise from +z towards +x, 0 on flat ground
+    Aspect,
+    /// curvature of the contour lines in 1 / world units, positive on ridges where flow diverges
+    PlanCurvature,
+    /// curvature along the slope in 1 / world units, positive where the slope gets gentler downhill
+    ProfileCurvature,
+    /// fraction of the sky not hidden by the terrain around a sample, 1 is fully open
+    Occlusion,
+}
+
+impl From<u32> for TerrainAttribute {
+    fn from(attribute: u32) -> Self {
+        match attribute {
+            1 => TerrainAttribute::Aspect,
+            2 => TerrainAttribute::PlanCurvature,
+            3 => TerrainAttribute::ProfileCurvature,
+            4 => TerrainAttribute::Occlusion,
+            _ => TerrainAttribute::Slope,
+        }
+    }
+}
+
+/// Settings for deriving terrain attributes from normalized heights.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerrainAnalysis {
+    /// world units of a normalized height of 1
+    pub height_scale: f64,
+    /// samples the horizon search walks out in every direction
+    pub occlusion_radius: usize,
+    pub occlusion_directions: u32,
+    /// curvatures are encoded in a color channel as `0.5 + curvature * curvature_scale`
+    pub curvature_scale: f64,
+}
+
+impl TerrainAnalysis {
+    pub fn build(height_scale: f64) -> Self {
+        Self {
+            height_scale,
+            occlusion_radius: 16,
+            occlusion_directions: 8,
+            curvature_scale: 10.0,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !self.height_scale.is_finite() {
+            return Err(Error::invalid_parameter(format!("analysis height_scale must be finite, got {}", self.height_scale)));
+        }
+        if self.occlusion_directions < 1 {
+            return Err(Error::invalid_parameter("occlusion_directions must be at least 1"));
+        }
+        if !self.curvature_scale.is_finite() {
+            return Err(Error::invalid_parameter(format!("curvature_scale must be finite, got {}", self.curvature_scale)));
+        }
+        Ok(())
+    }
+
+    /// Samples needed around a map so that `attribute` doesn't depend on where the map ends.
+    pub fn get_reach(&self, attribute: TerrainAttribute) -> usize {
+        match attribute {
+            TerrainAttribute::Occlusion => self.occlusion_radius.max(1),
+            _ => 1,
+        }
+    }
+
+    /// `attribute` at sample (x, y) of `map`.
+    pub fn get(&self, map: &HeightMap, attribute: TerrainAttribute, x: usize, y: usize) -> f64 {
+        match attribute {
+            TerrainAttribute::Slope => map.slope_at(x, y, self.height_scale),
+            TerrainAttribute::Aspect => map.aspect_at(x, y, self.height_scale),
+            TerrainAttribute::PlanCurvature => map.curvature_at(x, y, self.height_scale).0,
+            TerrainAttribute::ProfileCurvature => map.curvature_at(x, y, self.height_scale).1,
+            TerrainAttribute::Occlusion => map.occlusion_at(x, y, self.height_scale, self.occlusion_radius, self.occlusion_directions),
+        }
+    }
+
+    /// `attribute` of the map without its outer `border` samples, row major.
+    pub fn fill(&self, map: &HeightMap, attribute: TerrainAttribute, border: usize, buffer: &mut [f32]) {
+        let width = map.width - 2 * border;
+        buffer.par_iter_mut().enumerate().for_each(|(i, value)| {
+            *value = self.get(map, attribute, i % width + border, i / width + border) as f32;
+        });
+    }
+
+    /// Grayscale color of a value of `attribute`, slope over 90 degrees, aspect over 360 degrees,
+    /// curvatures around 0.5 scaled by `curvature_scale` and occlusion as it is.
+    pub fn encode(&self, attribute: TerrainAttribute, value: f32) -> Color32 {
+        let t = match attribute {
+            TerrainAttribute::Slope => value as f64 / 90.0,
+            TerrainAttribute::Aspect => value as f64 / 360.0,
+            TerrainAttribute::PlanCurvature | TerrainAttribute::ProfileCurvature => 0.5 + value as f64 * self.curvature_scale,
+            TerrainAttribute::Occlusion => value as f64,
+        };
+        let v = (t * 255.0).round().clamp(0.0, 255.0) as u8;
+        Color32{ r: v, g: v, b: v, a: 255 }
+    }
+}
+
+impl Default for TerrainAnalysis {
+    fn default() -> Self {
+        TerrainAnalysis::build(50.0)
+    }
+}
+
+/// First and second derivatives of the heights in world units, Zevenbergen and Thorne
+/// differences over the 3x3 neighbourhood, clamped at the border of the map.
+struct Derivatives {
+    p: f64,
+    q: f64,
+    r: f64,
+    s: f64,
+    t: f64,
+}
+
+impl HeightMap {
+    fn derivatives(&self, x: usize, y: usize, height_scale: f64) -> Derivatives {
+        let (x0, x1) = (x.saturating_sub(1), (x + 1).min(self.width - 1));
+        let (y0, y1) = (y.saturating_sub(1), (y + 1).min(self.height - 1));
+        let z = |x: usize, y: usize| self.get(x, y) as f64 * height_scale;
+        let l = self.spacing as f64;
+        let (dx, dy) = ((x1 - x0).max(1) as f64 * l, (y1 - y0).max(1) as f64 * l);
+        Derivatives {
+            p: (z(x1, y) - z(x0, y)) / dx,
+            q: (z(x, y1) - z(x, y0)) / dy,
+            r: (z(x1, y) - 2.0 * z(x, y) + z(x0, y)) / (l * l),
+            s: (z(x1, y1) - z(x0, y1) - z(x1, y0) + z(x0, y0)) / (dx * dy),
+            t: (z(x, y1) - 2.0 * z(x, y) + z(x, y0)) / (l * l),
+        }
+    }
+
+    /// Slope in degrees from horizontal, `height_scale` turns normalized heights into world units.
+    pub fn slope_at(&self, x: usize, y: usize, height_scale: f64) -> f64 {
+        let d = self.derivatives(x, y, height_scale);
+        (d.p * d.p + d.q * d.q).sqrt().atan().to_degrees()
+    }
+
+    /// Direction the slope faces in degrees, clockwise from +z towards +x, 0 on flat ground.
+    pub fn aspect_at(&self, x: usize, y: usize, height_scale: f64) -> f64 {
+        let d = self.derivatives(x, y, height_scale);
+        if d.p == 0.0 && d.q == 0.0 {
+            return 0.0;
+        }
+        (-d.p).atan2(-d.q).to_degrees().rem_euclid(360.0)
+    }
+
+    /// Plan and profile curvature in 1 / world units, both positive where the surface is convex and 0 on flat ground.
+    pub fn curvature_at(&self, x: usize, y: usize, height_scale: f64) -> (f64, f64) {
+        let Derivatives { p, q, r, s, t } = self.derivatives(x, y, height_scale);
+        let gradient_sq = p * p + q * q;
+        if gradient_sq < 1e-12 {
+            return (0.0, 0.0);
+        }
+        let plan = -(q * q * r - 2.0 * p * q * s + p * p * t) / gradient_sq.powf(1.5);
+        let profile = -(p * p * r + 2.0 * p * q * s + q * q * t) / (gradient_sq * (1.0 + gradient_sq).powf(1.5));
+        (plan, profile)
+    }
+
+    /// Horizon based ambient occlusion, the share of the sky above the highest horizon found within
+    /// `radius` samples, averaged over `directions`. 1 is fully open.
+    pub fn occlusion_at(&self, x: usize, y: usize, height_scale: f64, radius: usize, directions: u32) -> f64 {
+        let h = self.get(x, y) as f64 * height_scale;
+        let l = self.spacing as f64;
+        let blocked: f64 = (0..directions.max(1)).map(|k| {
+            let angle = 2.0 * PI * k as f64 / directions.max(1) as f64;
+            let (dx, dy) = (angle.cos(), angle.sin());
+            let mut horizon = 0.0_f64;
+            for step in 1..=radius {
+                let sx = (x as f64 + dx * step as f64).round();
+                let sy = (y as f64 + dy * step as f64).round();
+                if sx < 0.0 || sy < 0.0 || sx >= self.width as f64 || sy >= self.height as f64 {
+                    break;
+                }
+                let rise = self.get(sx as usize, sy as usize) as f64 * height_scale - h;
+                let dist = (sx - x as f64).hypot(sy - y as f64) * l;
+                horizon = horizon.max(rise / (rise * rise + dist * dist).sqrt());
+            }
+            horizon
+        }).sum();
+        1.0 - blocked / directions.max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod analysis_tests {
+    use crate::{heightmap::HeightMap, unity::{Color32, Position2D64}};
+    use super::{TerrainAnalysis, TerrainAttribute};
+
+    #[test]
+    fn test_plane_slope_and_aspect() {
+        // rises one unit per unit towards +x, so it faces -x
+        let map = HeightMap::sample(5, 5, Position2D64::default(), 1.0, |pos| pos.x / 10.0);
+        assert!((map.slope_at(2, 2, 10.0) - 45.0).abs() < 1e-4);
+        assert!((map.aspect_at(2, 2, 10.0) - 270.0).abs() < 1e-4);
+        assert!((map.slope_at(0, 0, 10.0) - 45.0).abs() < 1e-4);
+        let (plan, profile) = map.curvature_at(2, 2, 10.0);
+        assert!(plan.abs() < 1e-6 && profile.abs() < 1e-6);
+        // the low edge only sees the plane rise to the east, at 45 degrees straight east
+        let open = map.occlusion_at(0, 2, 10.0, 4, 8);
+        assert!(open < 1.0 - 0.5_f64.sqrt() / 8.0 + 1e-9 && open > 0.5);
+        assert_eq!(map.occlusion_at(4, 2, 10.0, 4, 8), 1.0);
+
+        let facing_north = HeightMap::sample(5, 5, Position2D64::default(), 1.0, |pos| -pos.y);
+        assert!(facing_north.aspect_at(2, 2, 1.0).abs() < 1e-4);
+        let flat = HeightMap::build(5, 5, Position2D64::default(), 1.0);
+        assert_eq!((flat.slope_at(2, 2, 1.0), flat.aspect_at(2, 2, 1.0)), (0.0, 0.0));
+        assert_eq!(flat.occlusion_at(2, 2, 1.0, 4, 8), 1.0);
+    }
+
+    #[test]
+    fn test_curvature_signs() {
+        let dome = HeightMap::sample(9, 9, Position2D64{x: -4.0, y: -4.0}, 1.0, |pos| 1.0 - (pos.x * pos.x + pos.y * pos.y) / 100.0);
+        let (plan, profile) = dome.curvature_at(6, 4, 10.0);
+        assert!(plan > 0.0 && profile > 0.0);
+        let bowl = HeightMap::sample(9, 9, Position2D64{x: -4.0, y: -4.0}, 1.0, |pos| (pos.x * pos.x + pos.y * pos.y) / 100.0);
+        let (plan, profile) = bowl.curvature_at(6, 4, 10.0);
+        assert!(plan < 0.0 && profile < 0.0);
+        // the bottom of the bowl is occluded, the top of the dome isn't
+        assert!(bowl.occlusion_at(4, 4, 10.0, 4, 8) < 1.0);
+        assert_eq!(dome.occlusion_at(4, 4, 10.0, 4, 8), 1.0);
+    }
+
+    #[test]
+    fn test_fill_and_encode() {
+        let analysis = TerrainAnalysis::build(10.0);
+        analysis.validate().unwrap();
+        let map = HeightMap::sample(6, 6, Position2D64::default(), 1.0, |pos| pos.x / 10.0);
+        let mut slopes = vec![0.0; 16];
+        analysis.fill(&map, TerrainAttribute::Slope, 1, &mut slopes);
+        assert!(slopes.iter().all(|slope| (slope - 45.0).abs() < 1e-4));
+        assert_eq!(analysis.encode(TerrainAttribute::Slope, 45.0), Color32{r: 128, g: 128, b: 128, a: 255});
+        assert_eq!(analysis.encode(TerrainAttribute::PlanCurvature, 0.0).r, 128);
+        assert_eq!(analysis.encode(TerrainAttribute::Occlusion, 1.0).r, 255);
+        assert_eq!(analysis.get_reach(TerrainAttribute::Occlusion), 16);
+        assert_eq!(TerrainAttribute::from(4), TerrainAttribute::Occlusion);
+    }
+}
@@ -0,0 +1,489 @@
+//! Headless front end of the generators, writes textures, heightmaps and meshes for a world rectangle.
+//!
+//! Every subcommand takes `--preset <file>`, a `.json`, `.ron` or `.toml` generator preset as `preset::save` writes it:
+//! a texturegen for `texture` and `heightmap`, a chunkgen for `mesh`. Flags given on the command line override
+//! the fields of the preset, fields neither sets keep the generator defaults.
+
+use std::{fs::File, io::BufWriter, path::{Path, PathBuf}};
+use structopt::{clap::AppSettings, StructOpt};
+
+use meshgen::{
+    gltf::GltfScene, heightmap::HeightMap, obj::save_chunk_obj, ply::save_chunk_ply, preset, write_png,
+    BaseNoiseType, BlendType, Color32, ColorKey, ColorKeyGradient, FractalType, MountainousTerrainChunkGen,
+    MountainousTerrainNoise, MountainousTerrainTextureGen, Position2D32, Position2D64, Position3D64, TextureGen2D,
+    WorleyDistance, WorleyReturn,
+};
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "meshgen", about = "Generate terrain textures, heightmaps and meshes", global_settings = &[AppSettings::AllArgsOverrideSelf])]
+enum Command {
+    /// Color texture, one pixel per world unit
+    Texture {
+        #[structopt(flatten)]
+        common: CommonArgs,
+        #[structopt(flatten)]
+        gradient: GradientArgs,
+    },
+    /// 16 bit grayscale image of the normalized noise heights
+    Heightmap {
+        #[structopt(flatten)]
+        common: CommonArgs,
+        /// world units between samples
+        #[structopt(long, default_value = "1.0")]
+        spacing: f32,
+        /// png, raw (little endian 16 bit, for unity), pgm (16 bit) or pfm (32 bit float)
+        #[structopt(long, default_value = "png", parse(try_from_str = parse_heightmap_format))]
+        format: HeightmapFormat,
+    },
+    /// Chunk meshes covering the rectangle
+    Mesh {
+        #[structopt(flatten)]
+        common: CommonArgs,
+        #[structopt(flatten)]
+        gradient: GradientArgs,
+        #[structopt(flatten)]
+        chunk: ChunkArgs,
+        /// glb, gltf, obj or ply; obj and ply write one file per chunk
+        #[structopt(long, default_value = "glb", parse(try_from_str = parse_mesh_format))]
+        format: MeshFormat,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+struct CommonArgs {
+    /// output file, obj and ply meshes get the chunk coordinates appended to the file stem
+    #[structopt(short, long, parse(from_os_str))]
+    output: PathBuf,
+    /// generator preset (.json, .ron or .toml), command line flags override its fields
+    #[structopt(long, parse(from_os_str))]
+    preset: Option<PathBuf>,
+    #[structopt(flatten)]
+    rect: RectArgs,
+    #[structopt(flatten)]
+    noise: NoiseArgs,
+}
+
+/// world rectangle `x..x + width`, `z..z + depth`
+#[derive(StructOpt, Debug)]
+struct RectArgs {
+    #[structopt(long, default_value = "0.0", allow_hyphen_values = true)]
+    x: f64,
+    #[structopt(long, default_value = "0.0", allow_hyphen_values = true)]
+    z: f64,
+    #[structopt(long, default_value = "256")]
+    width: usize,
+    #[structopt(long, default_value = "256")]
+    depth: usize,
+}
+
+/// parameters of `MountainousTerrainNoise::build`, each one overrides the preset or `MountainousTerrainNoise::default`
+#[derive(StructOpt, Debug, Default)]
+struct NoiseArgs {
+    #[structopt(long)]
+    seed: Option<u32>,
+    #[structopt(long)]
+    scale: Option<f64>,
+    #[structopt(long)]
+    persistance: Option<f64>,
+    #[structopt(long)]
+    lacunarity: Option<f64>,
+    #[structopt(long)]
+    octaves: Option<u32>,
+    /// domain displacement, disabled when not positive
+    #[structopt(long, allow_hyphen_values = true)]
+    displacement: Option<f64>,
+    #[structopt(long)]
+    bias_gain: Option<f64>,
+    #[structopt(long, parse(try_from_str = parse_position))]
+    bezier_from: Option<Position2D32>,
+    #[structopt(long, parse(try_from_str = parse_position))]
+    bezier_to: Option<Position2D32>,
+    #[structopt(long)]
+    bezier_curvature: Option<f64>,
+    /// fbm, ridged, billow or hybrid-multi
+    #[structopt(long, parse(try_from_str = parse_fractal_type))]
+    fractal: Option<FractalType>,
+    /// perlin, open-simplex, super-simplex, value or worley
+    #[structopt(long)]
+    base_noise: Option<String>,
+    /// euclidean, euclidean-squared, manhattan, chebyshev or quadratic
+    #[structopt(long, parse(try_from_str = parse_worley_distance))]
+    worley_distance: Option<WorleyDistance>,
+    /// cell-value or distance
+    #[structopt(long, parse(try_from_str = parse_worley_return))]
+    worley_return: Option<WorleyReturn>,
+}
+
+#[derive(StructOpt, Debug, Default)]
+struct GradientArgs {
+    /// color keys as `t:rrggbb` separated by commas, e.g. `0.3:1e50c8,0.5:d2c88c,1:ffffff`; black to white if empty
+    #[structopt(long, parse(try_from_str = parse_color_keys))]
+    gradient: Option<ColorKeys>,
+    /// blend linearly between keys instead of taking the next key
+    #[structopt(long)]
+    linear: bool,
+}
+
+/// chunk settings, each one overrides the preset or the defaults given here
+#[derive(StructOpt, Debug, Default)]
+struct ChunkArgs {
+    /// world units along the side of a chunk [default: 64]
+    #[structopt(long)]
+    side_len: Option<usize>,
+    /// height of a noise value of 1 in world units [default: 50]
+    #[structopt(long)]
+    height: Option<f64>,
+    /// [default: 0]
+    #[structopt(long)]
+    lod: Option<u32>,
+    /// skirts are added below the chunk borders when positive [default: 0]
+    #[structopt(long)]
+    skirt_depth: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct ColorKeys(Vec<ColorKey>);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum MeshFormat {
+    Glb,
+    Gltf,
+    Obj,
+    Ply,
+}
+
+fn parse_mesh_format(s: &str) -> Result<MeshFormat, String> {
+    match s {
+        "glb" => Ok(MeshFormat::Glb),
+        "gltf" => Ok(MeshFormat::Gltf),
+        "obj" => Ok(MeshFormat::Obj),
+        "ply" => Ok(MeshFormat::Ply),
+        _ => Err(format!("unknown mesh format {}", s)),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum HeightmapFormat {
+    Png,
+    Raw,
+    Pgm,
+    Pfm,
+}
+
+fn parse_heightmap_format(s: &str) -> Result<HeightmapFormat, String> {
+    match s {
+        "png" => Ok(HeightmapFormat::Png),
+        "raw" | "r16" => Ok(HeightmapFormat::Raw),
+        "pgm" => Ok(HeightmapFormat::Pgm),
+        "pfm" => Ok(HeightmapFormat::Pfm),
+        _ => Err(format!("unknown heightmap format {}", s)),
+    }
+}
+
+fn parse_position(s: &str) -> Result<Position2D32, String> {
+    let coords: Vec<f32> = s.split(',').map(|c| c.trim().parse::<f32>()).collect::<Result<_, _>>().map_err(|e| e.to_string())?;
+    match coords.as_slice() {
+        [x, y] => Ok(Position2D32{x: *x, y: *y}),
+        _ => Err(format!("expected x,y but got {}", s)),
+    }
+}
+
+fn parse_fractal_type(s: &str) -> Result<FractalType, String> {
+    match s {
+        "fbm" => Ok(FractalType::Fbm),
+        "ridged" => Ok(FractalType::Ridged),
+        "billow" => Ok(FractalType::Billow),
+        "hybrid-multi" => Ok(FractalType::HybridMulti),
+        _ => Err(format!("unknown fractal type {}", s)),
+    }
+}
+
+fn parse_worley_distance(s: &str) -> Result<WorleyDistance, String> {
+    match s {
+        "euclidean" => Ok(WorleyDistance::Euclidean),
+        "euclidean-squared" => Ok(WorleyDistance::EuclideanSquared),
+        "manhattan" => Ok(WorleyDistance::Manhattan),
+        "chebyshev" => Ok(WorleyDistance::Chebyshev),
+        "quadratic" => Ok(WorleyDistance::Quadratic),
+        _ => Err(format!("unknown worley distance {}", s)),
+    }
+}
+
+fn parse_worley_return(s: &str) -> Result<WorleyReturn, String> {
+    match s {
+        "cell-value" => Ok(WorleyReturn::CellValue),
+        "distance" => Ok(WorleyReturn::Distance),
+        _ => Err(format!("unknown worley return type {}", s)),
+    }
+}
+
+fn parse_base_noise(base_noise: &str, distance: WorleyDistance, return_type: WorleyReturn) -> Result<BaseNoiseType, String> {
+    match base_noise {
+        "perlin" => Ok(BaseNoiseType::Perlin),
+        "open-simplex" => Ok(BaseNoiseType::OpenSimplex),
+        "super-simplex" => Ok(BaseNoiseType::SuperSimplex),
+        "value" => Ok(BaseNoiseType::Value),
+        "worley" => Ok(BaseNoiseType::Worley(distance, return_type)),
+        _ => Err(format!("unknown base noise {}", base_noise)),
+    }
+}
+
+fn parse_color_keys(s: &str) -> Result<ColorKeys, String> {
+    s.split(',').filter(|key| !key.trim().is_empty()).map(|key| {
+        let (t, hex) = key.trim().split_once(':').ok_or_else(|| format!("expected t:rrggbb but got {}", key))?;
+        let t = t.parse::<f32>().map_err(|e| e.to_string())?;
+        let hex = hex.trim_start_matches('#');
+        let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok()).ok_or_else(|| format!("invalid color {}", hex));
+        let a = if hex.len() == 8 { channel(6)? } else { 255 };
+        if hex.len() != 6 && hex.len() != 8 {
+            return Err(format!("invalid color {}", hex));
+        }
+        Ok(ColorKey{ color: Color32{r: channel(0)?, g: channel(2)?, b: channel(4)?, a}, t })
+    }).collect::<Result<Vec<_>, _>>().map(ColorKeys)
+}
+
+impl NoiseArgs {
+    /// Overrides the parameters of `noise` with the flags that were given.
+    fn apply(&self, noise: &MountainousTerrainNoise) -> Result<MountainousTerrainNoise, String> {
+        let mut params = noise.params();
+        let set = |field: &mut f64, flag: Option<f64>| if let Some(value) = flag { *field = value };
+        params.seed = self.seed.unwrap_or(params.seed);
+        set(&mut params.scale, self.scale);
+        set(&mut params.persistance, self.persistance);
+        set(&mut params.lacunarity, self.lacunarity);
+        params.octaves = self.octaves.unwrap_or(params.octaves);
+        set(&mut params.displacement, self.displacement);
+        set(&mut params.bias_gain, self.bias_gain);
+        params.bezier_bias_from = self.bezier_from.unwrap_or(params.bezier_bias_from);
+        params.bezier_bias_to = self.bezier_to.unwrap_or(params.bezier_bias_to);
+        set(&mut params.bezier_bias_corner_curvature, self.bezier_curvature);
+        params.fractal_type = self.fractal.unwrap_or(params.fractal_type);
+
+        let (distance, return_type) = match params.base_noise_type {
+            BaseNoiseType::Worley(distance, return_type) => (distance, return_type),
+            _ => (WorleyDistance::default(), WorleyReturn::default()),
+        };
+        let (distance, return_type) = (self.worley_distance.unwrap_or(distance), self.worley_return.unwrap_or(return_type));
+        params.base_noise_type = match (&self.base_noise, params.base_noise_type) {
+            (Some(base_noise), _) => parse_base_noise(base_noise, distance, return_type)?,
+            (None, BaseNoiseType::Worley(..)) => BaseNoiseType::Worley(distance, return_type),
+            (None, base_noise_type) => base_noise_type,
+        };
+        params.validate().map_err(|e| e.to_string())?;
+        Ok(params.build())
+    }
+}
+
+impl GradientArgs {
+    /// Overrides `color_gradient` with the keys and blending that were given.
+    fn apply(&self, color_gradient: &mut ColorKeyGradient) {
+        if let Some(keys) = &self.gradient {
+            let mut keys = keys.0.clone();
+            keys.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(std::cmp::Ordering::Equal));
+            color_gradient.keys = keys;
+        }
+        if self.linear {
+            color_gradient.blend_type = BlendType::Linear;
+        }
+    }
+}
+
+fn load_preset<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, String> {
+    preset::load(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// The texturegen of the preset, sized to the rectangle, with the flags applied.
+fn texturegen(common: &CommonArgs, gradient: &GradientArgs) -> Result<MountainousTerrainTextureGen, String> {
+    let mut texturegen = match &common.preset {
+        Some(path) => load_preset(path)?,
+        None => MountainousTerrainTextureGen::default(),
+    };
+    texturegen.width = common.rect.width;
+    texturegen.height = common.rect.depth;
+    texturegen.noise = common.noise.apply(&texturegen.noise)?;
+    gradient.apply(&mut texturegen.color_gradient);
+    texturegen.validate().map_err(|e| e.to_string())?;
+    Ok(texturegen)
+}
+
+/// The chunkgen of the preset with the flags applied.
+fn chunkgen(common: &CommonArgs, gradient: &GradientArgs, chunk: &ChunkArgs) -> Result<MountainousTerrainChunkGen, String> {
+    let mut chunkgen = match &common.preset {
+        Some(path) => load_preset(path)?,
+        None => MountainousTerrainChunkGen::build(64, 50.0),
+    };
+    chunkgen.side_len = chunk.side_len.unwrap_or(chunkgen.side_len);
+    chunkgen.height = chunk.height.unwrap_or(chunkgen.height);
+    chunkgen.lod = chunk.lod.unwrap_or(chunkgen.lod);
+    chunkgen.skirt_depth = chunk.skirt_depth.unwrap_or(chunkgen.skirt_depth);
+    chunkgen.noise = common.noise.apply(&chunkgen.noise)?;
+    gradient.apply(&mut chunkgen.color_gradient);
+    chunkgen.validate().map_err(|e| e.to_string())?;
+    Ok(chunkgen)
+}
+
+/// Centers of the chunks of side `side_len` covering the rectangle, with their integer chunk coordinates.
+fn chunk_centers(rect: &RectArgs, side_len: usize) -> Vec<((usize, usize), Position3D64)> {
+    let side = side_len.max(1);
+    let (nx, nz) = (rect.width.div_ceil(side).max(1), rect.depth.div_ceil(side).max(1));
+    (0..nz).flat_map(|j| (0..nx).map(move |i| {
+        let center = Position3D64{
+            x: rect.x + (i as f64 + 0.5) * side as f64,
+            y: 0.0,
+            z: rect.z + (j as f64 + 0.5) * side as f64,
+        };
+        ((i, j), center)
+    })).collect()
+}
+
+fn chunk_path(output: &Path, (i, j): (usize, usize)) -> PathBuf {
+    let stem = output.file_stem().and_then(|s| s.to_str()).unwrap_or("chunk");
+    let extension = output.extension().and_then(|s| s.to_str()).unwrap_or_default();
+    output.with_file_name(format!("{}_{}_{}.{}", stem, i, j, extension))
+}
+
+fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Texture { common, gradient } => {
+            let texturegen = texturegen(&common, &gradient)?;
+            let center = Position2D64{
+                x: common.rect.x + common.rect.width as f64 / 2.0,
+                y: common.rect.z + common.rect.depth as f64 / 2.0,
+            };
+            let mut tex_buffer = vec![Color32::default(); common.rect.width * common.rect.depth];
+            texturegen.fill_texture_2d(&mut tex_buffer, center);
+            let out = BufWriter::new(File::create(&common.output).map_err(|e| e.to_string())?);
+            write_png(out, common.rect.width, common.rect.depth, &tex_buffer).map_err(|e| e.to_string())
+        },
+        Command::Heightmap { common, spacing, format } => {
+            if spacing <= 0.0 {
+                return Err("spacing must be positive".to_string());
+            }
+            let noise = texturegen(&common, &GradientArgs::default())?.noise;
+            let width = ((common.rect.width as f32 / spacing).ceil() as usize).max(1);
+            let depth = ((common.rect.depth as f32 / spacing).ceil() as usize).max(1);
+            let map = HeightMap::from_noise(&noise, width, depth, Position2D64{x: common.rect.x, y: common.rect.z}, spacing);
+            let out = BufWriter::new(File::create(&common.output).map_err(|e| e.to_string())?);
+            let res = match format {
+                HeightmapFormat::Png => map.write_png16(out),
+                HeightmapFormat::Raw => map.write_raw16(out),
+                HeightmapFormat::Pgm => map.write_pgm16(out),
+                HeightmapFormat::Pfm => map.write_pfm(out),
+            };
+            res.map_err(|e| e.to_string())
+        },
+        Command::Mesh { common, gradient, chunk, format } => {
+            let chunkgen = chunkgen(&common, &gradient, &chunk)?;
+            let chunks = chunk_centers(&common.rect, chunkgen.side_len);
+            let res = match format {
+                MeshFormat::Glb | MeshFormat::Gltf => {
+                    let mut scene = GltfScene::build();
+                    for (_, center) in chunks.iter() {
+                        scene.add_chunkgen(&chunkgen, *center);
+                    }
+                    if format == MeshFormat::Glb { scene.save_glb(&common.output) } else { scene.save_gltf(&common.output) }
+                },
+                MeshFormat::Obj => chunks.iter().try_for_each(|(coords, center)| save_chunk_obj(&chunkgen, *center, chunk_path(&common.output, *coords))),
+                MeshFormat::Ply => chunks.iter().try_for_each(|(coords, center)| save_chunk_ply(&chunkgen, *center, chunk_path(&common.output, *coords))),
+            };
+            res.map_err(|e| e.to_string())
+        },
+    }
+}
+
+fn main() {
+    if let Err(err) = run(Command::from_args()) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod meshgen_cli_tests {
+    use meshgen::{preset, BaseNoiseType, BlendType, FractalType, MountainousTerrainChunkGen, MountainousTerrainTextureGen, WorleyDistance, WorleyReturn};
+    use structopt::StructOpt;
+    use super::{chunk_centers, chunkgen, parse_color_keys, parse_heightmap_format, parse_position, texturegen, Command, HeightmapFormat, RectArgs};
+
+    #[test]
+    fn test_parse_args() {
+        let keys = parse_color_keys("0.3:1e50c8, 1:ffffff80").unwrap().0;
+        assert_eq!(keys.len(), 2);
+        assert_eq!((keys[0].color.r, keys[0].color.g, keys[0].color.b, keys[0].color.a), (0x1e, 0x50, 0xc8, 255));
+        assert_eq!(keys[1].color.a, 0x80);
+        assert!(parse_color_keys("0.3:1e50").is_err());
+        assert_eq!(parse_position("0.4, -1").unwrap().y, -1.0);
+        assert!(parse_position("0.4").is_err());
+        assert_eq!(parse_heightmap_format("r16").unwrap(), HeightmapFormat::Raw);
+        assert!(parse_heightmap_format("exr").is_err());
+    }
+
+    #[test]
+    fn test_chunk_centers_cover_rect() {
+        let rect = RectArgs{x: -100.0, z: 0.0, width: 130, depth: 64};
+        let chunks = chunk_centers(&rect, 64);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].1.x, -68.0);
+        assert_eq!(chunks[2].0, (2, 0));
+        assert_eq!(chunks[2].1.z, 32.0);
+    }
+
+    #[test]
+    fn test_flags_override_preset() {
+        let mut saved = MountainousTerrainChunkGen::build(32, 80.0);
+        let mut params = saved.noise.params();
+        params.seed = 7;
+        params.octaves = 5;
+        params.base_noise_type = BaseNoiseType::Worley(WorleyDistance::Manhattan, WorleyReturn::Distance);
+        saved.noise = params.build();
+        saved.lod = 1;
+        let path = std::env::temp_dir().join(format!("meshgen_cli_preset_{}.toml", std::process::id()));
+        preset::save(&saved, &path).unwrap();
+
+        let command = Command::from_iter_safe(vec![
+            "meshgen", "mesh", "-o", "world.glb", "--preset", path.to_str().unwrap(),
+            "--side-len", "16", "--seed", "3", "--worley-return", "cell-value", "--fractal", "ridged", "--linear",
+        ]).unwrap();
+        let loaded = match command {
+            Command::Mesh { common, gradient, chunk, .. } => chunkgen(&common, &gradient, &chunk).unwrap(),
+            _ => unreachable!(),
+        };
+        let params = loaded.noise.params();
+        assert_eq!((loaded.side_len, loaded.height, loaded.lod), (16, 80.0, 1));
+        assert_eq!((params.seed, params.octaves, params.fractal_type), (3, 5, FractalType::Ridged));
+        assert_eq!(params.base_noise_type, BaseNoiseType::Worley(WorleyDistance::Manhattan, WorleyReturn::CellValue));
+        assert_eq!(loaded.color_gradient.blend_type, BlendType::Linear);
+
+
+        let mut saved = MountainousTerrainTextureGen::build(512, 512);
+        saved.noise = params.build();
+        preset::save(&saved, &path).unwrap();
+        let command = Command::from_iter_safe(vec![
+            "meshgen", "texture", "-o", "texture.png", "--width", "8", "--depth", "4", "--preset", path.to_str().unwrap(), "--gradient", "1:ffffff,0.3:1e50c8",
+        ]).unwrap();
+        let loaded = match command {
+            Command::Texture { common, gradient } => texturegen(&common, &gradient).unwrap(),
+            _ => unreachable!(),
+        };
+        assert_eq!((loaded.width, loaded.height), (8, 4));
+        assert_eq!(loaded.noise.params(), params);
+        assert_eq!(loaded.color_gradient.keys[0].t, 0.3);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_defaults_without_preset() {
+        let command = Command::from_iter_safe(vec!["meshgen", "mesh", "-o", "world.glb", "--height", "20"]).unwrap();
+        let loaded = match command {
+            Command::Mesh { common, gradient, chunk, .. } => chunkgen(&common, &gradient, &chunk).unwrap(),
+            _ => unreachable!(),
+        };
+        assert_eq!((loaded.side_len, loaded.height, loaded.lod), (64, 20.0, 0));
+        assert_eq!(loaded.noise.params(), MountainousTerrainChunkGen::default().noise.params());
+        assert_eq!(loaded.color_gradient.blend_type, BlendType::Discrete);
+
+        match Command::from_iter_safe(vec!["meshgen", "mesh", "-o", "world.glb", "--octaves", "0"]).unwrap() {
+            Command::Mesh { common, gradient, chunk, .. } => assert!(chunkgen(&common, &gradient, &chunk).is_err()),
+            _ => unreachable!(),
+        }
+    }
+}
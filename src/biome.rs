@@ -0,0 +1,376 @@
+use noise::{NoiseFn, Perlin, Seedable};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::{Error, Result}, gradient::{BlendType, ColorKey, ColorKeyGradient}, heightmap::HeightMap, noisegen::{MountainousTerrainNoise, MountainousTerrainNoiseParams, Noise2D}, unity::{Color32, Position2D64}};
+
+/// Number of biomes a `BiomeBlend` holds, further biomes inside the blend width are dropped.
+pub const MAX_BLENDED_BIOMES: usize = 4;
+
+/// The biomes contributing to one vertex or pixel, sorted by weight. `ids[0]` is the dominant biome,
+/// the weights add up to 1 and unused slots have a weight of 0.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+#[repr(C)]
+pub struct BiomeBlend {
+    pub ids: [u32; MAX_BLENDED_BIOMES],
+    pub weights: [f32; MAX_BLENDED_BIOMES],
+}
+
+impl BiomeBlend {
+    /// index of the biome with the largest weight
+    pub fn id(&self) -> u32 {
+        self.ids[0]
+    }
+
+    /// weight of biome `id`, 0 if it doesn't contribute
+    pub fn weight_of(&self, id: u32) -> f32 {
+        self.ids.iter().zip(self.weights.iter())
+            .filter(|(other, weight)| **other == id && **weight > 0.0)
+            // not `sum`, which starts from -0.0
+            .fold(0.0, |sum, (_, weight)| sum + *weight)
+    }
+
+    fn contributions(&self) -> impl Iterator<Item = (usize, f64)> + '_ {
+        self.ids.iter().zip(self.weights.iter())
+            .filter(|(_, weight)| **weight > 0.0)
+            .map(|(id, weight)| (*id as usize, *weight as f64))
+    }
+}
+
+/// The arguments of `ClimateNoise::build`, the form the climate takes in presets.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClimateNoiseParams {
+    pub seed: u32,
+    /// world units across one climate feature, much larger than the terrain noise scale
+    pub scale: f64,
+    pub octaves: u32,
+}
+
+impl Default for ClimateNoiseParams {
+    fn default() -> Self {
+        Self { seed: 0, scale: 2000.0, octaves: 3 }
+    }
+}
+
+/// Low frequency temperature and moisture, both in [0, 1].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "ClimateNoiseParams", into = "ClimateNoiseParams")]
+pub struct ClimateNoise {
+    params: ClimateNoiseParams,
+    temperature: Perlin,
+    moisture: Perlin,
+}
+
+impl ClimateNoise {
+    pub fn build(params: ClimateNoiseParams) -> Self {
+        Self {
+            params,
+            temperature: Perlin::new().set_seed(params.seed),
+            moisture: Perlin::new().set_seed(params.seed.wrapping_add(1)),
+        }
+    }
+
+    pub fn params(&self) -> ClimateNoiseParams {
+        self.params
+    }
+
+    /// temperature and moisture at `pos`
+    pub fn get(&self, pos: Position2D64) -> (f64, f64) {
+        (self.sample(&self.temperature, pos), self.sample(&self.moisture, pos))
+    }
+
+    fn sample(&self, noise: &Perlin, pos: Position2D64) -> f64 {
+        let octaves = self.params.octaves.max(1);
+        let (mut sum, mut amplitude, mut frequency, mut max_sum) = (0.0, 1.0, 1.0 / self.params.scale, 0.0);
+        for _ in 0..octaves {
+            sum += noise.get([pos.x * frequency, pos.y * frequency]) * amplitude;
+            max_sum += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        // perlin noise rarely leaves [-0.7, 0.7], stretched so the climate covers [0, 1]
+        (sum / max_sum * 0.75 + 0.5).clamp(0.0, 1.0)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !self.params.scale.is_finite() || self.params.scale <= 0.0 {
+            return Err(Error::invalid_parameter(format!("climate scale must be positive, got {}", self.params.scale)));
+        }
+        Ok(())
+    }
+}
+
+impl Default for ClimateNoise {
+    fn default() -> Self {
+        ClimateNoise::build(ClimateNoiseParams::default())
+    }
+}
+
+impl From<ClimateNoiseParams> for ClimateNoise {
+    fn from(params: ClimateNoiseParams) -> Self {
+        ClimateNoise::build(params)
+    }
+}
+
+impl From<ClimateNoise> for ClimateNoiseParams {
+    fn from(climate: ClimateNoise) -> Self {
+        climate.params
+    }
+}
+
+/// One terrain character, placed in climate space by the temperature and moisture it is typical for.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Biome {
+    pub name: String,
+    pub noise: MountainousTerrainNoise,
+    /// multiplies the normalized noise, 1 reaches the full height of the generator
+    pub height_scale: f64,
+    pub color_gradient: ColorKeyGradient,
+    pub temperature: f64,
+    pub moisture: f64,
+}
+
+impl Biome {
+    pub fn build<S: Into<String>>(name: S, temperature: f64, moisture: f64) -> Self {
+        Self {
+            name: name.into(),
+            noise: MountainousTerrainNoise::default(),
+            height_scale: 1.0,
+            color_gradient: ColorKeyGradient::default(),
+            temperature,
+            moisture,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !self.height_scale.is_finite() {
+            return Err(Error::invalid_parameter(format!("height_scale of biome {} must be finite, got {}", self.name, self.height_scale)));
+        }
+        if !self.temperature.is_finite() || !self.moisture.is_finite() {
+            return Err(Error::invalid_parameter(format!("climate of biome {} must be finite", self.name)));
+        }
+        self.color_gradient.validate()?;
+        self.noise.params().validate()
+    }
+}
+
+impl Default for Biome {
+    fn default() -> Self {
+        Biome::build("biome", 0.5, 0.5)
+    }
+}
+
+/// Picks the biomes at a world position from the climate, biomes closer than `blend_width`
+/// in climate space to the nearest one are blended in so borders stay smooth.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BiomeMap {
+    pub biomes: Vec<Biome>,
+    pub climate: ClimateNoise,
+    /// 0 gives hard borders
+    pub blend_width: f64,
+}
+
+impl BiomeMap {
+    pub fn build(biomes: Vec<Biome>, climate: ClimateNoiseParams, blend_width: f64) -> Self {
+        Self {
+            biomes,
+            climate: ClimateNoise::build(climate),
+            blend_width,
+        }
+    }
+
+    /// Checks for settings that would leave positions without a biome or generate NaN heights.
+    pub fn validate(&self) -> Result<()> {
+        if self.biomes.is_empty() {
+            return Err(Error::invalid_parameter("a biome map needs at least one biome"));
+        }
+        if !self.blend_width.is_finite() || self.blend_width < 0.0 {
+            return Err(Error::invalid_parameter(format!("blend_width must be finite and not negative, got {}", self.blend_width)));
+        }
+        self.climate.validate()?;
+        self.biomes.iter().try_for_each(Biome::validate)
+    }
+
+    /// Weights of the biomes at `pos`, each one falls off quadratically with its distance
+    /// to the climate at `pos` beyond the nearest biome.
+    pub fn blend_at(&self, pos: Position2D64) -> BiomeBlend {
+        let (temperature, moisture) = self.climate.get(pos);
+        let distance = |biome: &Biome| ((biome.temperature - temperature).powi(2) + (biome.moisture - moisture).powi(2)).sqrt();
+        let nearest = self.biomes.iter().map(distance).fold(f64::INFINITY, f64::min);
+
+        let mut blend = BiomeBlend::default();
+        let mut weights = [0.0_f64; MAX_BLENDED_BIOMES];
+        for (id, biome) in self.biomes.iter().enumerate() {
+            let excess = distance(biome) - nearest;
+            let weight = match self.blend_width > 0.0 {
+                true => (1.0 - excess / self.blend_width).max(0.0).powi(2),
+                _ => if excess <= 0.0 { 1.0 } else { 0.0 },
+            };
+            // insertion into the slots sorted by weight, ties keep the lower id first
+            if let Some(slot) = (0..MAX_BLENDED_BIOMES).find(|slot| weight > weights[*slot]) {
+                for k in (slot + 1..MAX_BLENDED_BIOMES).rev() {
+                    weights[k] = weights[k - 1];
+                    blend.ids[k] = blend.ids[k - 1];
+                }
+                weights[slot] = weight;
+                blend.ids[slot] = id as u32;
+            }
+        }
+        let total: f64 = weights.iter().sum();
+        for (slot, weight) in weights.iter().enumerate() {
+            blend.weights[slot] = (weight / total) as f32;
+        }
+        blend
+    }
+
+    /// Normalized height at `pos`, the heights of the blended biomes weighted by `blend`.
+    pub fn height(&self, pos: Position2D64, blend: &BiomeBlend) -> f64 {
+        blend.contributions()
+            .map(|(id, weight)| {
+                let biome = &self.biomes[id];
+                biome.noise.get(pos) * biome.height_scale * weight
+            })
+            .sum()
+    }
+
+    /// Color of the normalized height `h` in every blended biome, weighted by `blend`.
+    pub fn color(&self, h: f64, blend: &BiomeBlend) -> Color32 {
+        let mut channels = [0.0_f64; 4];
+        for (id, weight) in blend.contributions() {
+            let color = self.biomes[id].color_gradient.get_color(h);
+            for (channel, value) in channels.iter_mut().zip([color.r, color.g, color.b, color.a].iter()) {
+                *channel += *value as f64 * weight;
+            }
+        }
+        let [r, g, b, a] = channels.map(|c| c.round().clamp(0.0, 255.0) as u8);
+        Color32{ r, g, b, a }
+    }
+
+    pub fn get(&self, pos: Position2D64) -> f64 {
+        self.height(pos, &self.blend_at(pos))
+    }
+
+    /// Colors every vertex `HeightMap::fill_mesh` writes with the same border by the biomes under it.
+    pub fn fill_vertex_colors(&self, heights: &HeightMap, border: usize, tex_buffer: &mut [Color32]) {
+        heights.fill_vertex_data(border, tex_buffer, |x, y, h| self.color(h as f64, &self.blend_at(heights.world_pos(x, y))));
+    }
+
+    /// Biome blend of every vertex `HeightMap::fill_mesh` writes with the same border.
+    pub fn fill_vertex_blends(&self, heights: &HeightMap, border: usize, biome_buffer: &mut [BiomeBlend]) {
+        heights.fill_vertex_data(border, biome_buffer, |x, y, _| self.blend_at(heights.world_pos(x, y)));
+    }
+
+    /// Colors the map one pixel per sample by the biomes under it, as `HeightMap::fill_color_texture`.
+    pub fn fill_color_texture(&self, heights: &HeightMap, tex_buffer: &mut [Color32]) {
+        tex_buffer.par_iter_mut().enumerate().for_each(|(i, cur_pix)| {
+            let (x, y) = (i % heights.width, i / heights.width);
+            *cur_pix = self.color(heights.get(x, y) as f64, &self.blend_at(heights.world_pos(x, y)));
+        });
+    }
+
+    /// Biome blend of every sample of the map, one per pixel.
+    pub fn fill_texture_blends(&self, heights: &HeightMap, biome_buffer: &mut [BiomeBlend]) {
+        biome_buffer.par_iter_mut().enumerate().for_each(|(i, cur_blend)| {
+            *cur_blend = self.blend_at(heights.world_pos(i % heights.width, i / heights.width));
+        });
+    }
+}
+
+fn gradient(keys: &[(f32, u32)]) -> ColorKeyGradient {
+    ColorKeyGradient {
+        keys: keys.iter().map(|(t, rgb)| ColorKey {
+            t: *t,
+            color: Color32{ r: (rgb >> 16) as u8, g: (rgb >> 8) as u8, b: *rgb as u8, a: 255 },
+        }).collect(),
+        blend_type: BlendType::Linear,
+    }
+}
+
+impl Default for BiomeMap {
+    /// grassy plains, mountains in the cold and dunes where it is hot and dry
+    fn default() -> Self {
+        let plains = Biome {
+            height_scale: 0.3,
+            noise: MountainousTerrainNoiseParams{ seed: 1, scale: 200.0, octaves: 4, ..Default::default() }.into(),
+            color_gradient: gradient(&[(0.0, 0x3c6e28), (0.3, 0x78a03c)]),
+            ..Biome::build("plains", 0.5, 0.6)
+        };
+        let mountains = Biome {
+            color_gradient: gradient(&[(0.0, 0x3c6e28), (0.35, 0x6e6e6e), (0.7, 0xa0a0a0), (0.85, 0xffffff)]),
+            ..Biome::build("mountains", 0.1, 0.5)
+        };
+        let desert = Biome {
+            height_scale: 0.4,
+            noise: MountainousTerrainNoiseParams{ seed: 2, scale: 120.0, octaves: 3, ..Default::default() }.into(),
+            color_gradient: gradient(&[(0.0, 0xc8aa6e), (0.4, 0xe6d296)]),
+            ..Biome::build("desert", 0.9, 0.1)
+        };
+        BiomeMap::build(vec![plains, mountains, desert], ClimateNoiseParams::default(), 0.1)
+    }
+}
+
+#[cfg(test)]
+mod biome_tests {
+    use crate::{error::Error, unity::Position2D64};
+    use super::{Biome, BiomeBlend, BiomeMap, ClimateNoiseParams};
+
+    #[test]
+    fn test_blend_weights() {
+        let map = BiomeMap::default();
+        map.validate().unwrap();
+        for i in 0..200 {
+            let pos = Position2D64{ x: i as f64 * 97.0 - 9000.0, y: i as f64 * -41.0 };
+            let blend = map.blend_at(pos);
+            let total: f32 = blend.weights.iter().sum();
+            assert!((total - 1.0).abs() < 1e-5);
+            assert!(blend.weights.windows(2).all(|pair| pair[0] >= pair[1]));
+            assert!((blend.id() as usize) < map.biomes.len());
+            assert!(blend.weight_of(blend.id()) >= 1.0 / map.biomes.len() as f32);
+        }
+
+        let hard = BiomeMap { blend_width: 0.0, ..BiomeMap::default() };
+        let blend = hard.blend_at(Position2D64{ x: 123.0, y: 456.0 });
+        assert_eq!(blend.weights[0], 1.0);
+        assert_eq!(blend.id(), map.blend_at(Position2D64{ x: 123.0, y: 456.0 }).id());
+    }
+
+    #[test]
+    fn test_missing_biome_weighs_zero() {
+        let blend = BiomeBlend{ ids: [2, 0, 0, 0], weights: [1.0, 0.0, 0.0, 0.0] };
+        assert_eq!(blend.weight_of(2), 1.0);
+        // +0.0 exactly, hosts copy the bits into vertex streams
+        assert_eq!(blend.weight_of(5).to_bits(), 0.0_f32.to_bits());
+        assert_eq!(blend.weight_of(0).to_bits(), 0.0_f32.to_bits());
+    }
+
+    #[test]
+    fn test_borders_blend_smoothly() {
+        let map = BiomeMap::build(vec![Biome::build("cold", 0.0, 0.5), Biome::build("hot", 1.0, 0.5)], ClimateNoiseParams{ seed: 3, scale: 500.0, octaves: 1 }, 0.3);
+        let weights: Vec<f32> = (0..4000).map(|i| map.blend_at(Position2D64{ x: i as f64, y: 0.0 }).weight_of(1)).collect();
+        assert!(weights.iter().any(|w| *w > 0.0 && *w < 1.0));
+        assert!(weights.windows(2).all(|pair| (pair[1] - pair[0]).abs() < 0.05));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(matches!(BiomeMap::build(Vec::new(), ClimateNoiseParams::default(), 0.1).validate(), Err(Error::InvalidParameter(_))));
+        assert!(matches!(BiomeMap { blend_width: -1.0, ..BiomeMap::default() }.validate(), Err(Error::InvalidParameter(_))));
+        let climate = ClimateNoiseParams{ scale: 0.0, ..Default::default() };
+        assert!(matches!(BiomeMap::build(vec![Biome::default()], climate, 0.1).validate(), Err(Error::InvalidParameter(_))));
+    }
+
+    #[test]
+    fn test_preset_round_trip() {
+        let map = BiomeMap::default();
+        let json = serde_json::to_string(&map).unwrap();
+        let loaded: BiomeMap = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.biomes.len(), 3);
+        assert_eq!(loaded.biomes[2].name, "desert");
+        let pos = Position2D64{ x: -700.0, y: 2500.0 };
+        assert_eq!(loaded.blend_at(pos), map.blend_at(pos));
+        assert_eq!(loaded.get(pos), map.get(pos));
+    }
+}
@@ -1,73 +1,244 @@
 extern crate more_asserts;
 
-use std::{convert::TryInto, mem, panic, ptr};
-use log::{info, error};
+use log::info;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::{MountainousTerrainNoise, Noise2D, unity::SimpleVertex, gradient::{BlendType, ColorKey, ColorKeyGradient}, unity::Color32, unity::{Normal32, Position2D32, Position3D32, Quad, Tangent32, TexCoord32, Triangle, Vertex}};
+use crate::{biome::{BiomeBlend, BiomeMap}, MountainousTerrainNoise, MountainousTerrainNoiseParams, Noise2D, error::{Error, Result}, erosion::ThermalErosion, heightmap::{HeightMap, UvMapping}, layout::{VertexAttribute, VertexLayout, VertexSources}, noisegraph::BoxedNoiseSource, unity::SimpleVertex, gradient::ColorKeyGradient, unity::Color32, unity::{Position2D64, Position3D64, Quad, TriplanarWeights32, Vertex}};
+
+/// Buffer sizes of a chunk at a given level of detail.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct ChunkGeometryDesc {
+    pub quads_per_side: usize,
+    pub v_count: usize,
+    pub e_count: usize,
+    pub f_count: usize,
+}
+
+impl ChunkGeometryDesc {
+    /// Every lod level halves the number of quads along a side, the chunk keeps covering `side_len` world units.
+    /// Skirts add a strip of quads hanging below each border so neighbours at a different lod don't show cracks.
+    pub fn build(side_len: usize, lod: u32, skirts: bool) -> Self {
+        let n = side_len.checked_shr(lod).unwrap_or(0).max(1);
+        let mut v_count = (n + 1) * (n + 1);
+        let mut e_count = 2 * n + 3 * n * n;
+        let mut f_count = 2 * n * n;
+        if skirts {
+            v_count += 4 * (n + 1);
+            e_count += 4 * (3 * n + 1);
+            f_count += 8 * n;
+        }
+        Self { quads_per_side: n, v_count, e_count, f_count }
+    }
+
+    pub fn quad_count(&self) -> usize {
+        self.f_count / 2
+    }
+}
+
+/// Owned buffers for one `fill_chunk_2d` call, sized from the geometry desc of the chunkgen.
+#[derive(Clone, Debug, Default)]
+pub struct ChunkBuffers {
+    pub desc: ChunkGeometryDesc,
+    pub v_buffer: Vec<SimpleVertex>,
+    pub indx_buffer: Vec<Quad>,
+    pub tex_buffer: Vec<Color32>,
+}
+
+impl ChunkBuffers {
+    pub fn fill<C: ChunkGen2D + Sync>(chunkgen: &C, plane_pos: Position3D64) -> Self {
+        let desc = chunkgen.get_geometry_desc();
+        let mut v_buffer = vec![SimpleVertex::default(); desc.v_count];
+        let mut indx_buffer = vec![Quad::default(); desc.quad_count()];
+        let mut tex_buffer = vec![Color32::default(); desc.v_count];
+        chunkgen.fill_chunk_2d(&mut v_buffer, &mut indx_buffer, &mut tex_buffer, plane_pos);
+        Self { desc, v_buffer, indx_buffer, tex_buffer }
+    }
+
+    /// side length of the color texture, the first `tex_side * tex_side` colors of `tex_buffer`
+    pub fn tex_side(&self) -> usize {
+        self.desc.quads_per_side + 1
+    }
+}
+
+/// Chunks the exporter tests write, with and without lod and skirts, next to the desc of their chunkgen.
+#[cfg(test)]
+pub(crate) fn export_test_chunks() -> Vec<(ChunkGeometryDesc, ChunkBuffers)> {
+    [(0, 0.0), (1, 5.0)].iter().map(|&(lod, skirt_depth)| {
+        let mut chunkgen = MountainousTerrainChunkGen::build(8, 20.0);
+        chunkgen.lod = lod;
+        chunkgen.skirt_depth = skirt_depth;
+        (chunkgen.get_geometry_desc(), ChunkBuffers::fill(&chunkgen, Position3D64::default()))
+    }).collect()
+}
 
 pub trait ChunkGen2D {
-    fn get(&self, pos: Position2D32) -> f64;
+    fn get(&self, pos: Position2D64) -> f64;
     fn get_side_len(&self) -> usize;
     fn get_height(&self) -> f64;
     fn get_color_gradient(&self) -> &ColorKeyGradient;
 
-    fn fill_chunk_2d(&self, v_buffer: &mut [SimpleVertex], indx_buffer: &mut [Quad], tex_buffer: &mut [Color32], plane_pos: Position3D32) where Self: Sync {
-        info!("filling chunk with data");
+    fn get_lod(&self) -> u32 {
+        0
+    }
 
-        let half_side_len: f32 = self.get_side_len() as f32 / 2f32;
-        let vert_side: i32 = (self.get_side_len() + 1).try_into().unwrap();
-        let height = self.get_height();
-        let color_gradient = self.get_color_gradient();
-
-        v_buffer.par_iter_mut().zip(tex_buffer.par_iter_mut()).enumerate().for_each(|(i, (cur_v, cur_pix))| {
-            let x_pos = -half_side_len + (i as i32 % vert_side) as f32;
-            let z_pos = -half_side_len + (i as i32 / vert_side) as f32;
-            let cur_pos = Position2D32{ x: x_pos, y: z_pos } + Position2D32::from(plane_pos);
-            let n = self.get(cur_pos);
-            *cur_pix = color_gradient.get_color(n);
-            cur_v.pos = Position3D32{
-                x: x_pos,
-                y: (n * height) as f32 + plane_pos.y,
-                z: z_pos
-            };
-            // cur_v.norm = Normal32{
-            //     x: (0f32),
-            //     y: (1f32),
-            //     z: (0f32)
-            // };
-            // cur_v.tangent = Tangent32{
-            //     w: (1f32),
-            //     x: (1f32),
-            //     y: (0f32),
-            //     z: (0f32)
-            // };
-            cur_v.uv = TexCoord32{
-                u: ((i as i32 % vert_side) as f32 / vert_side as f32),
-                v: ((i as i32 / vert_side) as f32 / vert_side as f32), 
-            };
-        });
+    /// depth of the skirts below the border vertices, no skirts are generated if this is not positive
+    fn get_skirt_depth(&self) -> f64 {
+        0.0
+    }
+
+    fn get_geometry_desc(&self) -> ChunkGeometryDesc {
+        ChunkGeometryDesc::build(self.get_side_len(), self.get_lod(), self.get_skirt_depth() > 0.0)
+    }
+
+    /// thermal weathering applied to the heights before any vertices are written
+    fn get_thermal_erosion(&self) -> Option<&ThermalErosion> {
+        None
+    }
+
+    /// how `fill_chunk_2d` and `fill_chunk_2d_with_normals` write uvs
+    fn get_uv_mapping(&self) -> UvMapping {
+        UvMapping::default()
+    }
+
+    /// Triplanar blend weights of every vertex `fill_chunk_with_normals` writes for the chunk at `plane_pos`,
+    /// in the same order and from the same normals.
+    fn fill_chunk_triplanar_weights(&self, weight_buffer: &mut [TriplanarWeights32], plane_pos: Position3D64) -> Result<()> where Self: Sync + Sized {
+        let v_count = self.get_geometry_desc().v_count;
+        Error::check_len("weight_buffer", weight_buffer, v_count)?;
+        let heights = self.sample_height_map(plane_pos, 1);
+        heights.fill_triplanar_weights(1, self.get_height(), &self.get_uv_mapping(), &mut weight_buffer[..v_count]);
+        Ok(())
+    }
+
+    /// Biome blend of every vertex `fill_chunk_2d` writes, see `fill_vertex_biomes`.
+    fn fill_chunk_2d_biomes(&self, biome_buffer: &mut [BiomeBlend], plane_pos: Position3D64) where Self: Sync + Sized {
+        let grid = HeightMap::chunk_grid(self, plane_pos, 0);
+        self.fill_vertex_biomes(&grid, 0, biome_buffer);
+    }
+
+    /// Colors every vertex `HeightMap::fill_mesh` writes for `heights` with the same border.
+    fn fill_vertex_colors(&self, heights: &HeightMap, border: usize, tex_buffer: &mut [Color32]) where Self: Sync + Sized {
+        heights.fill_vertex_colors(border, self.get_color_gradient(), tex_buffer);
+    }
 
-        indx_buffer.par_iter_mut().enumerate().for_each(|(i, cur_tri)| {
-            let z = (i / self.get_side_len()) as i32;
-            let x = (i % self.get_side_len()) as i32;
-            let s = x * vert_side + z;
-            cur_tri.v1 = s + vert_side;
-            cur_tri.v2 = s + 1;
-            cur_tri.v3 = s;
-            cur_tri.v4 = s + 1;
-            cur_tri.v5 = s + vert_side;
-            cur_tri.v6 = s + vert_side + 1;
+    /// Biome blend of every vertex `HeightMap::fill_mesh` writes for `heights` with the same border,
+    /// a single biome 0 unless the chunkgen blends biomes.
+    fn fill_vertex_biomes(&self, _heights: &HeightMap, _border: usize, biome_buffer: &mut [BiomeBlend]) where Self: Sync + Sized {
+        let single = BiomeBlend{ weights: [1.0, 0.0, 0.0, 0.0], ..BiomeBlend::default() };
+        biome_buffer.iter_mut().for_each(|blend| *blend = single);
+    }
+
+    /// Writes the chunk at `plane_pos` into the vertex streams of `layout`, each stream holds
+    /// `v_count * stride` bytes. Vertices are the ones of `fill_chunk_2d_with_normals`.
+    fn fill_chunk_with_layout(&self, layout: &VertexLayout, streams: &mut [&mut [u8]], indx_buffer: &mut [Quad], plane_pos: Position3D64) -> Result<()> where Self: Sync + Sized {
+        let desc = self.get_geometry_desc();
+        layout.check_streams(streams, desc.v_count)?;
+        Error::check_len("indx_buffer", indx_buffer, desc.quad_count())?;
+        let mut v_buffer = vec![Vertex::default(); desc.v_count];
+        let mut tex_buffer = vec![Color32::default(); desc.v_count];
+        self.fill_chunk_2d_with_normals(&mut v_buffer, &mut indx_buffer[..desc.quad_count()], &mut tex_buffer, plane_pos);
+        let mut weight_buffer = vec![TriplanarWeights32::default(); desc.v_count];
+        if layout.contains(VertexAttribute::Uv1) {
+            self.fill_chunk_triplanar_weights(&mut weight_buffer, plane_pos)?;
+        }
+        let mut biome_buffer = vec![BiomeBlend::default(); desc.v_count];
+        if layout.contains(VertexAttribute::BiomeWeights) {
+            self.fill_chunk_2d_biomes(&mut biome_buffer, plane_pos);
+        }
+        layout.write(desc.v_count, streams, |i| VertexSources {
+            vertex: v_buffer[i],
+            color: tex_buffer[i],
+            triplanar_weights: weight_buffer[i],
+            biome_blend: biome_buffer[i],
         });
+        Ok(())
+    }
+
+    /// Heights of the vertex grid with `apron` extra samples per side, see `sample_height_grid`.
+    fn sample_height_map(&self, plane_pos: Position3D64, apron: usize) -> HeightMap where Self: Sync + Sized {
+        let grid = HeightMap::chunk_grid(self, plane_pos, 0);
+        self.sample_height_grid(grid.origin, grid.width, grid.spacing, apron)
+    }
+
+    /// Heights of a `side` x `side` grid `spacing` apart starting at `origin`, with `apron` extra samples per side,
+    /// after any filters of the chunkgen. Filters are run on a wider map so that results near the border
+    /// don't depend on where the grid ends.
+    fn sample_height_grid(&self, origin: Position2D64, side: usize, spacing: f32, apron: usize) -> HeightMap where Self: Sync + Sized {
+        let extra = self.get_thermal_erosion().map_or(0, ThermalErosion::get_reach);
+        let border = apron + extra;
+        let corner = origin - Position2D64{ x: border as f64 * spacing as f64, y: border as f64 * spacing as f64 };
+        let mut map = HeightMap::sample(side + 2 * border, side + 2 * border, corner, spacing, |pos| self.get(pos));
+        match self.get_thermal_erosion() {
+            Some(thermal_erosion) => {
+                map.thermal_erode(thermal_erosion, self.get_height() as f32);
+                map.crop(extra)
+            },
+            None => map,
+        }
+    }
+
+    /// Generates the chunk at `plane_pos` into buffers sized from `get_geometry_desc`.
+    fn generate_chunk(&self, plane_pos: Position3D64) -> ChunkBuffers where Self: Sync + Sized {
+        ChunkBuffers::fill(self, plane_pos)
+    }
+
+    /// `fill_chunk_2d` after checking the buffers are large enough for `get_geometry_desc`.
+    fn fill_chunk(&self, v_buffer: &mut [SimpleVertex], indx_buffer: &mut [Quad], tex_buffer: &mut [Color32], plane_pos: Position3D64) -> Result<()> where Self: Sync + Sized {
+        let desc = self.get_geometry_desc();
+        Error::check_len("v_buffer", v_buffer, desc.v_count)?;
+        Error::check_len("indx_buffer", indx_buffer, desc.quad_count())?;
+        Error::check_len("tex_buffer", tex_buffer, desc.v_count)?;
+        self.fill_chunk_2d(&mut v_buffer[..desc.v_count], &mut indx_buffer[..desc.quad_count()], &mut tex_buffer[..desc.v_count], plane_pos);
+        Ok(())
+    }
+
+    /// `fill_chunk_2d_with_normals` after checking the buffers are large enough for `get_geometry_desc`.
+    fn fill_chunk_with_normals(&self, v_buffer: &mut [Vertex], indx_buffer: &mut [Quad], tex_buffer: &mut [Color32], plane_pos: Position3D64) -> Result<()> where Self: Sync + Sized {
+        let desc = self.get_geometry_desc();
+        Error::check_len("v_buffer", v_buffer, desc.v_count)?;
+        Error::check_len("indx_buffer", indx_buffer, desc.quad_count())?;
+        Error::check_len("tex_buffer", tex_buffer, desc.v_count)?;
+        self.fill_chunk_2d_with_normals(&mut v_buffer[..desc.v_count], &mut indx_buffer[..desc.quad_count()], &mut tex_buffer[..desc.v_count], plane_pos);
+        Ok(())
+    }
+
+    fn fill_chunk_2d(&self, v_buffer: &mut [SimpleVertex], indx_buffer: &mut [Quad], tex_buffer: &mut [Color32], plane_pos: Position3D64) where Self: Sync + Sized {
+        info!("filling chunk with data");
+
+        let heights = self.sample_height_map(plane_pos, 0);
+        heights.fill_mesh(0, self.get_height(), plane_pos.y as f32, self.get_skirt_depth(), v_buffer, indx_buffer);
+        let uv_mapping = self.get_uv_mapping();
+        v_buffer.par_iter_mut().for_each(|v| v.uv = uv_mapping.uv(plane_pos, v.pos, v.uv));
+        self.fill_vertex_colors(&heights, 0, tex_buffer);
+    }
+
+    /// Same as `fill_chunk_2d`, but writes full `Vertex` records with normals and tangents
+    /// taken from the height field. Heights are sampled on a one cell apron around the chunk
+    /// so that border normals match the neighbouring chunk.
+    fn fill_chunk_2d_with_normals(&self, v_buffer: &mut [Vertex], indx_buffer: &mut [Quad], tex_buffer: &mut [Color32], plane_pos: Position3D64) where Self: Sync + Sized {
+        info!("filling chunk with data and normals");
+
+        let heights = self.sample_height_map(plane_pos, 1);
+        heights.fill_mesh_with_normals(1, self.get_height(), plane_pos.y as f32, self.get_skirt_depth(), v_buffer, indx_buffer);
+        let uv_mapping = self.get_uv_mapping();
+        v_buffer.par_iter_mut().for_each(|v| v.uv = uv_mapping.uv(plane_pos, v.pos, v.uv));
+        self.fill_vertex_colors(&heights, 1, tex_buffer);
     }
 }
 
-#[derive(Clone, Debug)]
+/// Every field is optional in a preset, missing ones take the values of `MountainousTerrainChunkGen::default`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MountainousTerrainChunkGen {
     pub side_len: usize,
     pub height: f64,
     pub noise: MountainousTerrainNoise,
     pub color_gradient: ColorKeyGradient,
+    pub lod: u32,
+    pub skirt_depth: f64,
+    pub thermal_erosion: Option<ThermalErosion>,
+    pub uv_mapping: UvMapping,
 }
 
 impl MountainousTerrainChunkGen {
@@ -76,24 +247,136 @@ impl MountainousTerrainChunkGen {
             side_len,
             height,
             noise: MountainousTerrainNoise::default(),
-            color_gradient: ColorKeyGradient::default()
+            color_gradient: ColorKeyGradient::default(),
+            lod: 0,
+            skirt_depth: 0.0,
+            thermal_erosion: None,
+            uv_mapping: UvMapping::default(),
         }
     }
 
-    fn to_ptr(self) -> *mut Self {
-        Box::into_raw(Box::new(self))
+    pub fn builder() -> MountainousTerrainChunkGenBuilder {
+        MountainousTerrainChunkGenBuilder::default()
     }
 
-    fn free(ptr: *mut Self) {
-        if !ptr.is_null() {
-            // SHOULD BE DROPPED AUTOMATICALLY
-            let _mynoise: Box<Self> = unsafe { Box::from_raw(ptr) };
+    /// Checks for settings that would generate NaN vertices or no chunk at all.
+    pub fn validate(&self) -> Result<()> {
+        if self.side_len < 1 {
+            return Err(Error::invalid_parameter("side_len must be at least 1"));
+        }
+        if !self.height.is_finite() {
+            return Err(Error::invalid_parameter(format!("height must be finite, got {}", self.height)));
         }
+        if !self.skirt_depth.is_finite() {
+            return Err(Error::invalid_parameter(format!("skirt_depth must be finite, got {}", self.skirt_depth)));
+        }
+        if let Some(thermal_erosion) = &self.thermal_erosion {
+            thermal_erosion.validate()?;
+        }
+        self.uv_mapping.validate()?;
+        self.color_gradient.validate()?;
+        self.noise.params().validate()
+    }
+}
+
+/// Settings of a `MountainousTerrainChunkGen`, checked once by `build`.
+///
+/// ```
+/// use meshgen::{ChunkGen2D, MountainousTerrainChunkGen, MountainousTerrainNoiseParams, Position3D64};
+///
+/// let chunkgen = MountainousTerrainChunkGen::builder()
+///     .side_len(32)
+///     .height(80.0)
+///     .noise(MountainousTerrainNoiseParams{seed: 7, ..Default::default()})
+///     .build()
+///     .unwrap();
+/// let chunk = chunkgen.generate_chunk(Position3D64::default());
+/// assert_eq!(chunk.v_buffer.len(), 33 * 33);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MountainousTerrainChunkGenBuilder {
+    side_len: usize,
+    height: f64,
+    noise: MountainousTerrainNoiseParams,
+    color_gradient: ColorKeyGradient,
+    lod: u32,
+    skirt_depth: f64,
+    thermal_erosion: Option<ThermalErosion>,
+    uv_mapping: UvMapping,
+}
+
+impl Default for MountainousTerrainChunkGenBuilder {
+    fn default() -> Self {
+        let defaults = MountainousTerrainChunkGen::default();
+        Self {
+            side_len: defaults.side_len,
+            height: defaults.height,
+            noise: MountainousTerrainNoiseParams::default(),
+            color_gradient: defaults.color_gradient,
+            lod: defaults.lod,
+            skirt_depth: defaults.skirt_depth,
+            thermal_erosion: defaults.thermal_erosion,
+            uv_mapping: defaults.uv_mapping,
+        }
+    }
+}
+
+impl MountainousTerrainChunkGenBuilder {
+    pub fn side_len(mut self, side_len: usize) -> Self {
+        self.side_len = side_len;
+        self
+    }
+
+    pub fn height(mut self, height: f64) -> Self {
+        self.height = height;
+        self
+    }
+
+    pub fn noise(mut self, noise: MountainousTerrainNoiseParams) -> Self {
+        self.noise = noise;
+        self
+    }
+
+    pub fn color_gradient(mut self, color_gradient: ColorKeyGradient) -> Self {
+        self.color_gradient = color_gradient;
+        self
+    }
+
+    /// skirts are only generated for a positive `skirt_depth`
+    pub fn lod(mut self, lod: u32, skirt_depth: f64) -> Self {
+        self.lod = lod;
+        self.skirt_depth = skirt_depth;
+        self
+    }
+
+    pub fn thermal_erosion(mut self, thermal_erosion: Option<ThermalErosion>) -> Self {
+        self.thermal_erosion = thermal_erosion;
+        self
+    }
+
+    pub fn uv_mapping(mut self, uv_mapping: UvMapping) -> Self {
+        self.uv_mapping = uv_mapping;
+        self
+    }
+
+    pub fn build(self) -> Result<MountainousTerrainChunkGen> {
+        let chunkgen = MountainousTerrainChunkGen {
+            side_len: self.side_len,
+            height: self.height,
+            noise: self.noise.try_build()?,
+            color_gradient: self.color_gradient,
+            lod: self.lod,
+            skirt_depth: self.skirt_depth,
+            thermal_erosion: self.thermal_erosion,
+            uv_mapping: self.uv_mapping,
+        };
+        chunkgen.validate()?;
+        Ok(chunkgen)
     }
 }
 
 impl ChunkGen2D for MountainousTerrainChunkGen {
-    fn get(&self, pos: Position2D32) -> f64 {
+    fn get(&self, pos: Position2D64) -> f64 {
         self.noise.get(pos)
     }
 
@@ -108,6 +391,22 @@ impl ChunkGen2D for MountainousTerrainChunkGen {
     fn get_color_gradient(&self) -> &ColorKeyGradient {
         &self.color_gradient
     }
+
+    fn get_lod(&self) -> u32 {
+        self.lod
+    }
+
+    fn get_skirt_depth(&self) -> f64 {
+        self.skirt_depth
+    }
+
+    fn get_thermal_erosion(&self) -> Option<&ThermalErosion> {
+        self.thermal_erosion.as_ref()
+    }
+
+    fn get_uv_mapping(&self) -> UvMapping {
+        self.uv_mapping
+    }
 }
 
 impl Default for MountainousTerrainChunkGen {
@@ -116,155 +415,403 @@ impl Default for MountainousTerrainChunkGen {
     }
 }
 
-#[no_mangle]
-pub extern "C" fn fill_mountainous_terrain_chunk(chunkgen: *mut MountainousTerrainChunkGen, vert_buf: *mut SimpleVertex, indx_buf: *mut Quad, tex_buf: *mut Color32, plane_pos: *mut Position3D32) -> *const u8 {
-    if chunkgen.is_null() {
-        "ERROR: pointer to texturegen is null\0".as_ptr()
-    }
-    else if vert_buf.is_null() {
-        "ERROR: pointer to vert_buf is null\0".as_ptr()
-    }
-    else if indx_buf.is_null() {
-        "ERROR: pointer to indx_buf is null\0".as_ptr()
-    }
-    else if tex_buf.is_null() {
-        "ERROR: pointer to tex_buf is null\0".as_ptr()
-    }
-    else if plane_pos.is_null() {
-        "ERROR: pointer to plane_pos is null\0".as_ptr()
-    }
-    else {
-        let res = panic::catch_unwind(|| {
-            unsafe {
-                let chunkgen = Box::from_raw(chunkgen);
-                let v_count =  (chunkgen.side_len + 1) * (chunkgen.side_len + 1);
-                let quad_count = chunkgen.side_len * chunkgen.side_len;
-                let vert_buffer: &mut [SimpleVertex] = std::slice::from_raw_parts_mut(vert_buf, v_count);
-                let tex_buffer: &mut [Color32] = std::slice::from_raw_parts_mut(tex_buf, v_count);
-                let indx_buffer: &mut [Quad] = std::slice::from_raw_parts_mut(indx_buf, quad_count);
-                let plane_pos = *plane_pos;
-                chunkgen.fill_chunk_2d(vert_buffer, indx_buffer, tex_buffer, plane_pos);
-                Box::leak(chunkgen);
-            }
-        });
-        match res {
-            Ok(_) => "OK\0".as_ptr(),
-            Err(err) => format!("{:?}\0", err).as_ptr()
-        }
-    }  
+/// Chunk generator over any noise graph, see `noisegraph::NoiseNode`.
+pub struct NoiseGraphChunkGen {
+    pub side_len: usize,
+    pub height: f64,
+    pub noise: BoxedNoiseSource,
+    pub color_gradient: ColorKeyGradient,
+    pub lod: u32,
+    pub skirt_depth: f64,
+    pub thermal_erosion: Option<ThermalErosion>,
+    pub uv_mapping: UvMapping,
 }
 
-#[no_mangle]
-pub extern "C" fn free_mountainous_terrain_chunkgen(mut _ptr: *mut MountainousTerrainChunkGen) {
-    info!("freeing mountainous terrain chunkgen");
-    if !_ptr.is_null() {
-        MountainousTerrainChunkGen::free(_ptr);
-    } 
-    _ptr = ptr::null_mut() as *mut MountainousTerrainChunkGen;
+impl NoiseGraphChunkGen {
+    pub fn build(side_len: usize, height: f64, noise: BoxedNoiseSource) -> Self {
+        Self {
+            side_len,
+            height,
+            noise,
+            color_gradient: ColorKeyGradient::default(),
+            lod: 0,
+            skirt_depth: 0.0,
+            thermal_erosion: None,
+            uv_mapping: UvMapping::default(),
+        }
+    }
 }
 
-#[no_mangle]
-pub extern "C" fn get_mountainous_terrain_chunkgen(side_len: usize, height: f64) -> *mut  MountainousTerrainChunkGen {
-    info!("getting mountainous terrain chunkgen");
-    MountainousTerrainChunkGen::build(side_len, height).to_ptr()
+impl ChunkGen2D for NoiseGraphChunkGen {
+    fn get(&self, pos: Position2D64) -> f64 {
+        self.noise.get(pos)
+    }
+
+    fn get_side_len(&self) -> usize {
+        self.side_len
+    }
+
+    fn get_height(&self) -> f64 {
+        self.height
+    }
+
+    fn get_color_gradient(&self) -> &ColorKeyGradient {
+        &self.color_gradient
+    }
+
+    fn get_lod(&self) -> u32 {
+        self.lod
+    }
+
+    fn get_skirt_depth(&self) -> f64 {
+        self.skirt_depth
+    }
+
+    fn get_thermal_erosion(&self) -> Option<&ThermalErosion> {
+        self.thermal_erosion.as_ref()
+    }
+
+    fn get_uv_mapping(&self) -> UvMapping {
+        self.uv_mapping
+    }
 }
 
-#[no_mangle]
-pub extern "C" fn set_mountainous_terrain_chunkgen_dim(chunkgen: *mut MountainousTerrainChunkGen, side_len: usize, height: f64) {
-    info!("setting mountainous terrain chunkgen dimensions");
-    if !chunkgen.is_null() {
-        let mut chunkgen = unsafe { Box::from_raw(chunkgen) };
-        chunkgen.side_len = side_len;
-        chunkgen.height = height;
-        Box::leak(chunkgen);
-    } 
+/// Chunk generator blending the terrain of the biomes of a `BiomeMap`, vertex colors blend the biome gradients.
+/// Every field is optional in a preset, missing ones take the values of `BiomeChunkGen::default`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BiomeChunkGen {
+    pub side_len: usize,
+    pub height: f64,
+    pub biome_map: BiomeMap,
+    pub lod: u32,
+    pub skirt_depth: f64,
+    pub thermal_erosion: Option<ThermalErosion>,
+    pub uv_mapping: UvMapping,
 }
 
-#[no_mangle]
-pub extern "C" fn set_mountainous_terrain_chunkgen_noise(chunkgen: *mut MountainousTerrainChunkGen, seed: u32, octaves: u32, scale: f64, persistance: f64, lacunarity: f64, displacement: f64, a: f64, bezier_bias_from: *mut Position2D32, bezier_bias_to: *mut Position2D32, bezier_bias_corner_curvature: f64) {
-    info!("setting mountainous terrain chunkgen noise");
-    if !chunkgen.is_null() {
-        let mut chunkgen = unsafe { Box::from_raw(chunkgen) };
-        unsafe {
-            chunkgen.noise = MountainousTerrainNoise::build(seed, scale, persistance, lacunarity, octaves, displacement, a, *bezier_bias_from, *bezier_bias_to, bezier_bias_corner_curvature);
+impl BiomeChunkGen {
+    pub fn build(side_len: usize, height: f64, biome_map: BiomeMap) -> Self {
+        Self {
+            side_len,
+            height,
+            biome_map,
+            lod: 0,
+            skirt_depth: 0.0,
+            thermal_erosion: None,
+            uv_mapping: UvMapping::default(),
         }
-        Box::leak(chunkgen);
-    } 
-}
+    }
 
-#[no_mangle]
-pub extern "C" fn set_mountainous_terrain_chunkgen_color_gradient(chunkgen: *mut MountainousTerrainChunkGen, color_key_arr: *mut ColorKey, key_cnt: usize, blend_mode: bool) {
-    if !chunkgen.is_null() {
-        let mut chunkgen = unsafe { Box::from_raw(chunkgen) };
-        let blend_type: BlendType = match blend_mode {
-            true => BlendType::Linear,
-            _ => BlendType::Discrete,
-        };
-        if key_cnt < 1 {
-            chunkgen.color_gradient = ColorKeyGradient{blend_type, keys: Vec::new()};
+    /// Checks for settings that would generate NaN vertices or no chunk at all.
+    pub fn validate(&self) -> Result<()> {
+        if self.side_len < 1 {
+            return Err(Error::invalid_parameter("side_len must be at least 1"));
         }
-        else {
-            let res = panic::catch_unwind(|| {
-                unsafe {
-                    let keys: &mut [ColorKey] = std::slice::from_raw_parts_mut(color_key_arr, key_cnt);
-                    info!("{:?}", keys);
-                    keys
-                }
-            });
-            chunkgen.color_gradient = match res {
-                Ok(keys) => ColorKeyGradient{blend_type, keys: (Vec::from(keys))},
-                Err(_) => {
-                    error!("failed to get colorkey gradient from pointer. using default");
-                    ColorKeyGradient{blend_type, keys: Vec::new()}
-                }
-            }
+        if !self.height.is_finite() {
+            return Err(Error::invalid_parameter(format!("height must be finite, got {}", self.height)));
+        }
+        if !self.skirt_depth.is_finite() {
+            return Err(Error::invalid_parameter(format!("skirt_depth must be finite, got {}", self.skirt_depth)));
+        }
+        if let Some(thermal_erosion) = &self.thermal_erosion {
+            thermal_erosion.validate()?;
         }
-        Box::leak(chunkgen);
-    } 
+        self.uv_mapping.validate()?;
+        self.biome_map.validate()
+    }
+
+    /// Biome blend of every vertex `fill_chunk` writes for the chunk at `plane_pos`, in the same order.
+    pub fn fill_chunk_biomes(&self, biome_buffer: &mut [BiomeBlend], plane_pos: Position3D64) -> Result<()> {
+        let v_count = self.get_geometry_desc().v_count;
+        Error::check_len("biome_buffer", biome_buffer, v_count)?;
+        self.fill_chunk_2d_biomes(&mut biome_buffer[..v_count], plane_pos);
+        Ok(())
+    }
 }
 
-#[no_mangle]
-pub extern "C" fn get_mountainous_terrain_chunk_geometry_desc(chunkgen: *mut MountainousTerrainChunkGen, v_count: ptr::NonNull<i32>, e_count: ptr::NonNull<i32>, f_count: ptr::NonNull<i32>) -> *const u8 {
-    let chunkgen = unsafe { Box::from_raw(chunkgen) };
-    let side_len = chunkgen.side_len;
-    Box::leak(chunkgen);
-    let _v_count = ((side_len + 1) * (side_len + 1)) as u64;
-    let _e_count = (2 * side_len + 3 * side_len * side_len) as u64;
-    let _f_count = (2 * side_len * side_len) as u64;
-
-    let _v_buf_bytes = _v_count * mem::size_of::<SimpleVertex>() as u64;
-    let _e_buf_bytes = _e_count * mem::size_of::<i32>() as u64;
-    let _f_buf_bytes = _f_count * mem::size_of::<Triangle>() as u64;
-    
-    if _e_buf_bytes >= i32::MAX as u64 {
-        format!("Edge list would require too many bytes! {}\0",  _e_buf_bytes).as_ptr()
-    }
-    else if _v_buf_bytes >= i32::MAX as u64 {
-        format!("Vertex list would require too many bytes! {}\0",  _v_buf_bytes).as_ptr()
-    }
-    else if _f_buf_bytes >= i32::MAX as u64 {
-        format!("Face list would require too many bytes! {}\0",  _f_buf_bytes).as_ptr()
-    }
-    else {
-        unsafe {
-            let v_count_mut = v_count.as_ptr();
-            *v_count_mut = _v_count.try_into().unwrap();
-            let e_count_mut = e_count.as_ptr();
-            *e_count_mut = _e_count.try_into().unwrap();
-            let f_count_mut = f_count.as_ptr();
-            *f_count_mut = _f_count.try_into().unwrap();
-        }
-        "OK\0".as_ptr()
+impl ChunkGen2D for BiomeChunkGen {
+    fn get(&self, pos: Position2D64) -> f64 {
+        self.biome_map.get(pos)
+    }
+
+    fn get_side_len(&self) -> usize {
+        self.side_len
+    }
+
+    fn get_height(&self) -> f64 {
+        self.height
+    }
+
+    /// gradient of the first biome, `fill_vertex_colors` blends the gradients of all biomes
+    fn get_color_gradient(&self) -> &ColorKeyGradient {
+        &self.biome_map.biomes[0].color_gradient
+    }
+
+    fn get_lod(&self) -> u32 {
+        self.lod
+    }
+
+    fn get_skirt_depth(&self) -> f64 {
+        self.skirt_depth
+    }
+
+    fn get_thermal_erosion(&self) -> Option<&ThermalErosion> {
+        self.thermal_erosion.as_ref()
+    }
+
+    fn get_uv_mapping(&self) -> UvMapping {
+        self.uv_mapping
+    }
+
+    fn fill_vertex_colors(&self, heights: &HeightMap, border: usize, tex_buffer: &mut [Color32]) {
+        self.biome_map.fill_vertex_colors(heights, border, tex_buffer);
+    }
+
+    fn fill_vertex_biomes(&self, heights: &HeightMap, border: usize, biome_buffer: &mut [BiomeBlend]) {
+        self.biome_map.fill_vertex_blends(heights, border, biome_buffer);
     }
 }
 
+impl Default for BiomeChunkGen {
+    fn default() -> Self {
+        BiomeChunkGen::build(100, 50.0, BiomeMap::default())
+    }
+}
 
 #[cfg(test)]
 mod chunk_tests {
-    // use crate::unity::{Quad, Vertex, Position3D32};
+    use crate::{error::Error, noisegen::MountainousTerrainNoiseParams, unity::{Color32, Quad, SimpleVertex, Vertex, Position2D64, Position3D64}};
+    use super::{BiomeChunkGen, ChunkGen2D, ChunkGeometryDesc, MountainousTerrainChunkGen};
 
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn test_normals_match_across_chunk_border() {
+        let chunkgen = MountainousTerrainChunkGen::build(8, 50.0);
+        let vert_side = chunkgen.side_len + 1;
+        let v_count = vert_side * vert_side;
+        let fill = |plane_pos: Position3D64| {
+            let mut v_buffer = vec![Vertex::default(); v_count];
+            let mut indx_buffer = vec![Quad::default(); chunkgen.side_len * chunkgen.side_len];
+            let mut tex_buffer = vec![Color32::default(); v_count];
+            chunkgen.fill_chunk_2d_with_normals(&mut v_buffer, &mut indx_buffer, &mut tex_buffer, plane_pos);
+            v_buffer
+        };
+        let left = fill(Position3D64{x: 0.0, y: 0.0, z: 0.0});
+        let right = fill(Position3D64{x: chunkgen.side_len as f64, y: 0.0, z: 0.0});
+
+        for v in left.iter() {
+            let len = (v.norm.x * v.norm.x + v.norm.y * v.norm.y + v.norm.z * v.norm.z).sqrt();
+            assert!((len - 1.0).abs() < 1e-5);
+            assert!(v.norm.y > 0.0);
+        }
+        for z in 0..vert_side {
+            let l = left[z * vert_side + vert_side - 1];
+            let r = right[z * vert_side];
+            assert!((l.norm.x - r.norm.x).abs() < 1e-5);
+            assert!((l.norm.y - r.norm.y).abs() < 1e-5);
+            assert!((l.norm.z - r.norm.z).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_lod_geometry_desc() {
+        assert_eq!(ChunkGeometryDesc::build(8, 0, false), ChunkGeometryDesc{quads_per_side: 8, v_count: 81, e_count: 208, f_count: 128});
+        assert_eq!(ChunkGeometryDesc::build(8, 1, false).v_count, 25);
+        assert_eq!(ChunkGeometryDesc::build(8, 1, true).v_count, 25 + 20);
+        assert_eq!(ChunkGeometryDesc::build(8, 1, true).quad_count(), 16 + 16);
+        assert_eq!(ChunkGeometryDesc::build(8, 10, false).quads_per_side, 1);
+    }
+
+    #[test]
+    fn test_lod_chunk_with_skirts() {
+        let mut chunkgen = MountainousTerrainChunkGen::build(8, 50.0);
+        chunkgen.lod = 1;
+        chunkgen.skirt_depth = 5.0;
+        let desc = chunkgen.get_geometry_desc();
+        let mut v_buffer = vec![SimpleVertex::default(); desc.v_count];
+        let mut indx_buffer = vec![Quad::default(); desc.quad_count()];
+        let mut tex_buffer = vec![Color32::default(); desc.v_count];
+        chunkgen.fill_chunk_2d(&mut v_buffer, &mut indx_buffer, &mut tex_buffer, Position3D64::default());
+
+        // same world area as lod 0
+        assert_eq!(v_buffer[0].pos.x, -4.0);
+        assert_eq!(v_buffer[24].pos.x, 4.0);
+        assert_eq!(v_buffer[24].pos.z, 4.0);
+        for quad in indx_buffer.iter() {
+            for v in [quad.v1, quad.v2, quad.v3, quad.v4, quad.v5, quad.v6].iter() {
+                assert!((*v as usize) < desc.v_count);
+            }
+        }
+        // first skirt vertex hangs below the south west corner
+        assert_eq!(v_buffer[25].pos.x, v_buffer[0].pos.x);
+        assert!((v_buffer[0].pos.y - v_buffer[25].pos.y - 5.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_builder_validates() {
+        let chunkgen = MountainousTerrainChunkGen::builder().side_len(16).height(30.0).lod(1, 2.0).build().unwrap();
+        assert_eq!((chunkgen.side_len, chunkgen.height, chunkgen.lod, chunkgen.skirt_depth), (16, 30.0, 1, 2.0));
+        assert!(matches!(MountainousTerrainChunkGen::builder().side_len(0).build(), Err(Error::InvalidParameter(_))));
+        let zero_scale = MountainousTerrainNoiseParams{scale: 0.0, ..Default::default()};
+        assert!(matches!(MountainousTerrainChunkGen::builder().noise(zero_scale).build(), Err(Error::InvalidParameter(_))));
+    }
+
+    #[test]
+    fn test_fill_chunk_checks_lengths() {
+        let chunkgen = MountainousTerrainChunkGen::build(8, 50.0);
+        let desc = chunkgen.get_geometry_desc();
+        let mut v_buffer = vec![SimpleVertex::default(); desc.v_count];
+        let mut indx_buffer = vec![Quad::default(); desc.quad_count() - 1];
+        let mut tex_buffer = vec![Color32::default(); desc.v_count];
+        match chunkgen.fill_chunk(&mut v_buffer, &mut indx_buffer, &mut tex_buffer, Position3D64::default()) {
+            Err(Error::BufferTooSmall { buffer, len, required }) => assert_eq!((buffer, len, required), ("indx_buffer", desc.quad_count() - 1, desc.quad_count())),
+            res => panic!("expected a short buffer error, got {:?}", res),
+        }
+        indx_buffer.push(Quad::default());
+        chunkgen.fill_chunk(&mut v_buffer, &mut indx_buffer, &mut tex_buffer, Position3D64::default()).unwrap();
+        let owned = chunkgen.generate_chunk(Position3D64::default());
+        assert_eq!(owned.v_buffer, v_buffer);
+        assert_eq!(owned.indx_buffer, indx_buffer);
+        assert_eq!(owned.tex_buffer, tex_buffer);
+    }
+
+    #[test]
+    fn test_far_chunks_stay_smooth() {
+        let mut chunkgen = MountainousTerrainChunkGen::build(16, 50.0);
+        let mut params = chunkgen.noise.params();
+        params.scale = 10.0;
+        chunkgen.noise = params.build();
+        let desc = chunkgen.get_geometry_desc();
+        // steps between the heights of the first vertex row of four chunks side by side, the shared border vertices once
+        let steps = |x: f64, z: f64| {
+            let row = (0..4).flat_map(|k| {
+                let mut v_buffer = vec![SimpleVertex::default(); desc.v_count];
+                let mut indx_buffer = vec![Quad::default(); desc.quad_count()];
+                let mut tex_buffer = vec![Color32::default(); desc.v_count];
+                chunkgen.fill_chunk(&mut v_buffer, &mut indx_buffer, &mut tex_buffer, Position3D64{x: x + 16.0 * k as f64, y: 0.0, z}).unwrap();
+                v_buffer[..16].iter().map(|v| v.pos.y).collect::<Vec<f32>>()
+            }).collect::<Vec<f32>>();
+            row.windows(2).map(|pair| (pair[1] - pair[0]).abs()).collect::<Vec<f32>>()
+        };
+        let near = steps(0.0, 0.0);
+        let largest_near = near.iter().cloned().fold(0.0, f32::max);
+        for &far in [1e7, -1e7].iter() {
+            // a center between two f32 values, the samples would collapse onto whole units in f32
+            let far_steps = steps(far + 0.25, far + 0.25);
+            assert!(far_steps.iter().all(|step| step.is_finite()));
+            // the mountainous noise is flat in its valleys, but not along a whole row
+            assert!(far_steps.iter().filter(|step| **step > 0.0).count() > far_steps.len() / 2);
+            // a seam or a jump between collapsed samples would be far steeper than the terrain near the origin
+            assert!(far_steps.iter().cloned().fold(0.0, f32::max) < largest_near * 4.0);
+        }
+    }
+
+    #[test]
+    fn test_biome_chunk() {
+        use crate::biome::{Biome, BiomeBlend, BiomeMap, ClimateNoiseParams};
+        // a single biome is the mountainous terrain of its noise
+        let single = BiomeMap::build(vec![Biome::build("only", 0.5, 0.5)], ClimateNoiseParams::default(), 0.1);
+        let chunkgen = BiomeChunkGen::build(8, 50.0, single);
+        chunkgen.validate().unwrap();
+        let mountainous = MountainousTerrainChunkGen::build(8, 50.0);
+        let plane_pos = Position3D64{x: 40.0, y: 0.0, z: -16.0};
+        assert_eq!(chunkgen.generate_chunk(plane_pos).v_buffer, mountainous.generate_chunk(plane_pos).v_buffer);
+
+        let chunkgen = BiomeChunkGen { skirt_depth: 2.0, ..BiomeChunkGen::default() };
+        let desc = chunkgen.get_geometry_desc();
+        let mut biome_buffer = vec![BiomeBlend::default(); desc.v_count - 1];
+        assert!(matches!(chunkgen.fill_chunk_biomes(&mut biome_buffer, plane_pos), Err(Error::BufferTooSmall { .. })));
+        biome_buffer.push(BiomeBlend::default());
+        chunkgen.fill_chunk_biomes(&mut biome_buffer, plane_pos).unwrap();
+        let chunk = chunkgen.generate_chunk(plane_pos);
+        for (i, v) in chunk.v_buffer.iter().enumerate().take(101 * 101) {
+            let world = Position2D64{x: v.pos.x as f64 + plane_pos.x, y: v.pos.z as f64 + plane_pos.z};
+            let blend = chunkgen.biome_map.blend_at(world);
+            assert_eq!(biome_buffer[i], blend);
+            assert_eq!(chunk.tex_buffer[i], chunkgen.biome_map.color(chunkgen.get(world) as f32 as f64, &blend));
+        }
+        // skirts take the blend of the vertex above them
+        assert_eq!(biome_buffer[101 * 101], biome_buffer[0]);
+    }
+
+    #[test]
+    fn test_uv_modes() {
+        use crate::heightmap::{UvMapping, UvMode};
+        use crate::unity::TriplanarWeights32;
+        let mut chunkgen = MountainousTerrainChunkGen::builder().side_len(8).height(60.0).build().unwrap();
+        let chunk = chunkgen.generate_chunk(Position3D64::default());
+        // per chunk uvs reach 1 on the far corner
+        assert_eq!((chunk.v_buffer[0].uv.u, chunk.v_buffer[0].uv.v), (0.0, 0.0));
+        assert_eq!((chunk.v_buffer[80].uv.u, chunk.v_buffer[80].uv.v), (1.0, 1.0));
+
+        for &x in [0.0, 1e7].iter() {
+            chunkgen.uv_mapping = UvMapping::build(UvMode::World, 6.0);
+            let west = chunkgen.generate_chunk(Position3D64{x, y: 0.0, z: 4.0});
+            let east = chunkgen.generate_chunk(Position3D64{x: x + 8.0, y: 0.0, z: 4.0});
+            for row in 0..9 {
+                let (w, e) = (west.v_buffer[row * 9 + 8].uv, east.v_buffer[row * 9].uv);
+                // shared border vertices are a whole number of repeats apart
+                let shift = e.u - w.u;
+                assert!((shift - shift.round()).abs() < 1e-5);
+                assert_eq!(w.v, e.v);
+                assert!((west.v_buffer[row * 9 + 1].uv.u - w.u + 7.0 / 6.0).abs() < 1e-5);
+            }
+        }
+
+        chunkgen.uv_mapping = UvMapping::build(UvMode::Triplanar, 6.0);
+        let mut weights = vec![TriplanarWeights32::default(); 81];
+        chunkgen.fill_chunk_triplanar_weights(&mut weights, Position3D64::default()).unwrap();
+        assert!(weights.iter().all(|w| (w.x + w.y + w.z - 1.0).abs() < 1e-5 && w.y > 0.0));
+        assert!(matches!(chunkgen.fill_chunk_triplanar_weights(&mut weights[..80], Position3D64::default()), Err(Error::BufferTooSmall { .. })));
+        let flat = UvMapping::default().triplanar_weights(crate::unity::Normal32{x: 0.0, y: 1.0, z: 0.0});
+        assert_eq!(flat, TriplanarWeights32{x: 0.0, y: 1.0, z: 0.0});
+        assert!(matches!(MountainousTerrainChunkGen::builder().uv_mapping(UvMapping::build(UvMode::World, 0.0)).build(), Err(Error::InvalidParameter(_))));
+    }
+
+    #[test]
+    fn test_layout_streams() {
+        use crate::layout::{VertexAttribute, VertexAttributeDesc, VertexFormat, VertexLayout};
+        let chunkgen = MountainousTerrainChunkGen::builder().side_len(4).build().unwrap();
+        let plane_pos = Position3D64{x: 30.0, y: 2.0, z: -12.0};
+        let layout = VertexLayout::build(&[
+            VertexAttributeDesc::build(VertexAttribute::Position, VertexFormat::Float32, 3, 0),
+            VertexAttributeDesc::build(VertexAttribute::Uv0, VertexFormat::Float32, 2, 0),
+            VertexAttributeDesc::build(VertexAttribute::Color, VertexFormat::UNorm8, 4, 1),
+            VertexAttributeDesc::build(VertexAttribute::BiomeWeights, VertexFormat::Float16, 4, 2),
+        ]).unwrap();
+        let (mut positions, mut colors, mut biomes) = (vec![0_u8; 25 * 20], vec![0_u8; 25 * 4], vec![0_u8; 25 * 8]);
+        let mut indx_buffer = vec![Quad::default(); 16];
+        chunkgen.fill_chunk_with_layout(&layout, &mut [&mut positions[..], &mut colors[..], &mut biomes[..]], &mut indx_buffer, plane_pos).unwrap();
+
+        // the first two attributes match the memory of a SimpleVertex
+        let chunk = chunkgen.generate_chunk(plane_pos);
+        let simple: Vec<u8> = chunk.v_buffer.iter()
+            .flat_map(|v| [v.pos.x, v.pos.y, v.pos.z, v.uv.u, v.uv.v].iter().flat_map(|c| c.to_le_bytes().to_vec()).collect::<Vec<u8>>())
+            .collect();
+        assert_eq!(positions, simple);
+        assert_eq!(indx_buffer, chunk.indx_buffer);
+        let rgba: Vec<u8> = chunk.tex_buffer.iter().flat_map(|c| vec![c.r, c.g, c.b, c.a]).collect();
+        assert_eq!(colors, rgba);
+        // a single biome everywhere
+        assert!(biomes.chunks(8).all(|weights| weights == [0x00, 0x3c, 0, 0, 0, 0, 0, 0]));
+
+        let mut short = [0_u8; 24 * 4];
+        assert!(matches!(chunkgen.fill_chunk_with_layout(&layout, &mut [&mut positions[..], &mut short[..], &mut biomes[..]], &mut indx_buffer, plane_pos), Err(Error::BufferTooSmall { .. })));
+        assert!(matches!(chunkgen.fill_chunk_with_layout(&layout, &mut [&mut positions[..]], &mut indx_buffer, plane_pos), Err(Error::BufferTooSmall { .. })));
+
+        let biome_chunkgen = BiomeChunkGen { side_len: 4, ..BiomeChunkGen::default() };
+        let far = Position3D64{x: 5000.0, y: 0.0, z: 800.0};
+        biome_chunkgen.fill_chunk_with_layout(&layout, &mut [&mut positions[..], &mut colors[..], &mut biomes[..]], &mut indx_buffer, far).unwrap();
+        let mut blends = vec![crate::biome::BiomeBlend::default(); 25];
+        biome_chunkgen.fill_chunk_biomes(&mut blends, far).unwrap();
+        for (weights, blend) in biomes.chunks(8).zip(blends.iter()) {
+            let weight = half::f16::from_le_bytes([weights[2], weights[3]]).to_f32();
+            assert!((weight - blend.weight_of(1)).abs() < 1e-3);
+        }
+    }
 }
@@ -0,0 +1,428 @@
+use log::info;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{chunkgen::ChunkGen2D, error::{Error, Result}, heightmap::HeightMap, unity::{Position2D64, Position3D64}};
+
+/// Heights after erosion, plus the sediment each sample received and the water that flowed over it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErosionResult {
+    pub heights: HeightMap,
+    pub sediment: HeightMap,
+    pub flow: HeightMap,
+}
+
+/// Particle based hydraulic erosion, every world cell spawns the same droplets no matter which map it is part of.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HydraulicErosion {
+    pub seed: u32,
+    pub droplets_per_cell: u32,
+    pub max_lifetime: u32,
+    /// how much of its previous direction a droplet keeps, 0 follows the gradient exactly
+    pub inertia: f32,
+    pub sediment_capacity: f32,
+    pub min_sediment_capacity: f32,
+    pub erode_speed: f32,
+    pub deposit_speed: f32,
+    pub evaporate_speed: f32,
+    pub gravity: f32,
+    /// radius in samples of the brush that removes material
+    pub radius: usize,
+    /// extra samples simulated around a tile so droplets from outside can reach it
+    pub apron: usize,
+    /// side of the world aligned tiles `erode_chunk` blends, in samples
+    pub tile_size: usize,
+}
+
+impl HydraulicErosion {
+    pub fn build(seed: u32) -> Self {
+        Self {
+            seed,
+            droplets_per_cell: 1,
+            max_lifetime: 30,
+            inertia: 0.05,
+            sediment_capacity: 4.0,
+            min_sediment_capacity: 0.01,
+            erode_speed: 0.3,
+            deposit_speed: 0.3,
+            evaporate_speed: 0.01,
+            gravity: 4.0,
+            radius: 3,
+            apron: 33,
+            tile_size: 64,
+        }
+    }
+
+    /// Erodes `map` on its own. Droplets leaving the map are dropped, so samples within `apron`
+    /// of the border should be cropped before use.
+    pub fn erode(&self, map: &HeightMap) -> ErosionResult {
+        let mut heights = map.clone();
+        let mut sediment = HeightMap::build(map.width, map.height, map.origin, map.spacing);
+        let mut flow = HeightMap::build(map.width, map.height, map.origin, map.spacing);
+        if map.width < 2 || map.height < 2 {
+            return ErosionResult { heights, sediment, flow };
+        }
+
+        let brush = self.brush();
+        let (cell_x, cell_y) = map.world_cell();
+        for k in 0..self.droplets_per_cell {
+            for y in 0..map.height {
+                for x in 0..map.width {
+                    let hash = hash_cell(self.seed, cell_x + x as i64, cell_y + y as i64, k);
+                    let start_x = x as f32 + (hash & 0xffff) as f32 / 65536.0;
+                    let start_y = y as f32 + ((hash >> 16) & 0xffff) as f32 / 65536.0;
+                    self.run_droplet(&mut heights, &mut sediment, &mut flow, &brush, start_x, start_y);
+                }
+            }
+        }
+        ErosionResult { heights, sediment, flow }
+    }
+
+    /// Erodes the vertex grid of a chunk so that it matches its neighbours at the same lod.
+    /// The world is cut into tiles of `tile_size` samples, each eroded on its own with an apron,
+    /// and every sample blends the two closest tiles per axis with tent weights. Each tile only
+    /// depends on its world position and the seed, so shared border samples come out identical.
+    pub fn erode_chunk<C: ChunkGen2D + Sync>(&self, chunkgen: &C, plane_pos: Position3D64) -> ErosionResult {
+        info!("eroding chunk");
+        let grid = HeightMap::from_chunkgen(chunkgen, plane_pos, 0);
+        let spacing = grid.spacing;
+        let tile = self.tile_size.max(1) as i64;
+        let (cell_x, cell_y) = grid.world_cell();
+        // tiles sample the lattice of the chunk, which is half a cell off the world grid for an odd side_len
+        let offset = grid.lattice_offset();
+        let tile_range = |cell: i64, len: usize| cell.div_euclid(tile)..=(cell + len as i64 - 1).div_euclid(tile) + 1;
+
+        let tiles: Vec<(i64, i64)> = tile_range(cell_y, grid.height)
+            .flat_map(|ty| tile_range(cell_x, grid.width).map(move |tx| (tx, ty)))
+            .collect();
+        let eroded: Vec<(i64, i64, ErosionResult)> = tiles.par_iter().map(|&(tx, ty)| {
+            // a tile covers the two tile lengths around its center plus the apron
+            let border = self.apron as i64;
+            let side = (2 * tile + 2 * border + 1) as usize;
+            let origin = Position2D64{
+                x: (((tx - 1) * tile - border) as f64 + offset.x) * spacing as f64,
+                y: (((ty - 1) * tile - border) as f64 + offset.y) * spacing as f64,
+            };
+            let map = HeightMap::sample(side, side, origin, spacing, |pos| chunkgen.get(pos));
+            (tx, ty, self.erode(&map))
+        }).collect();
+
+        let mut result = ErosionResult {
+            heights: grid.clone(),
+            sediment: HeightMap::build(grid.width, grid.height, grid.origin, spacing),
+            flow: HeightMap::build(grid.width, grid.height, grid.origin, spacing),
+        };
+        result.heights.data.iter_mut().for_each(|h| *h = 0.0);
+        for (tx, ty, tile_result) in eroded.iter() {
+            let tile_origin = ((tx - 1) * tile - self.apron as i64, (ty - 1) * tile - self.apron as i64);
+            for y in 0..grid.height {
+                for x in 0..grid.width {
+                    let gx = cell_x + x as i64;
+                    let gy = cell_y + y as i64;
+                    let weight = tent(gx, tx * tile, tile) * tent(gy, ty * tile, tile);
+                    if weight > 0.0 {
+                        let lx = (gx - tile_origin.0) as usize;
+                        let ly = (gy - tile_origin.1) as usize;
+                        let i = y * grid.width + x;
+                        result.heights.data[i] += weight * tile_result.heights.get(lx, ly);
+                        result.sediment.data[i] += weight * tile_result.sediment.get(lx, ly);
+                        result.flow.data[i] += weight * tile_result.flow.get(lx, ly);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// offsets and normalized weights of the erosion brush
+    fn brush(&self) -> Vec<(i64, i64, f32)> {
+        let r = self.radius as i64;
+        let mut brush: Vec<(i64, i64, f32)> = (-r..=r)
+            .flat_map(|y| (-r..=r).map(move |x| (x, y)))
+            .filter_map(|(x, y)| {
+                let weight = self.radius.max(1) as f32 - ((x * x + y * y) as f32).sqrt();
+                if weight > 0.0 { Some((x, y, weight)) } else { None }
+            })
+            .collect();
+        let sum: f32 = brush.iter().map(|b| b.2).sum();
+        brush.iter_mut().for_each(|b| b.2 /= sum);
+        brush
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn run_droplet(&self, heights: &mut HeightMap, sediment_map: &mut HeightMap, flow: &mut HeightMap, brush: &[(i64, i64, f32)], start_x: f32, start_y: f32) {
+        let max_x = (heights.width - 1) as f32;
+        let max_y = (heights.height - 1) as f32;
+        let (mut pos_x, mut pos_y) = (start_x.min(max_x - 1e-3), start_y.min(max_y - 1e-3));
+        let (mut dir_x, mut dir_y) = (0f32, 0f32);
+        let mut speed = 1f32;
+        let mut water = 1f32;
+        let mut sediment = 0f32;
+
+        for _ in 0..self.max_lifetime {
+            let node_x = pos_x.floor() as usize;
+            let node_y = pos_y.floor() as usize;
+            let (u, v) = (pos_x - node_x as f32, pos_y - node_y as f32);
+            let (h, grad_x, grad_y) = height_and_gradient(heights, pos_x, pos_y);
+
+            dir_x = dir_x * self.inertia - grad_x * (1.0 - self.inertia);
+            dir_y = dir_y * self.inertia - grad_y * (1.0 - self.inertia);
+            let len = (dir_x * dir_x + dir_y * dir_y).sqrt();
+            if len <= f32::EPSILON {
+                break;
+            }
+            dir_x /= len;
+            dir_y /= len;
+            pos_x += dir_x;
+            pos_y += dir_y;
+
+            flow.data[node_y * flow.width + node_x] += water;
+            if pos_x < 0.0 || pos_y < 0.0 || pos_x >= max_x || pos_y >= max_y {
+                break;
+            }
+
+            let dh = height_and_gradient(heights, pos_x, pos_y).0 - h;
+            let capacity = (-dh * speed * water * self.sediment_capacity).max(self.min_sediment_capacity);
+
+            if sediment > capacity || dh > 0.0 {
+                // fill the pit when going uphill, otherwise drop the excess
+                let deposit = if dh > 0.0 { dh.min(sediment) } else { (sediment - capacity) * self.deposit_speed };
+                sediment -= deposit;
+                let corners = [(0, 0, (1.0 - u) * (1.0 - v)), (1, 0, u * (1.0 - v)), (0, 1, (1.0 - u) * v), (1, 1, u * v)];
+                for (cx, cy, weight) in corners.iter() {
+                    let i = (node_y + cy) * heights.width + node_x + cx;
+                    heights.data[i] += deposit * weight;
+                    sediment_map.data[i] += deposit * weight;
+                }
+            }
+            else {
+                let erode = ((capacity - sediment) * self.erode_speed).min(-dh);
+                for (bx, by, weight) in brush.iter() {
+                    let x = node_x as i64 + bx;
+                    let y = node_y as i64 + by;
+                    if x < 0 || y < 0 || x >= heights.width as i64 || y >= heights.height as i64 {
+                        continue;
+                    }
+                    let i = y as usize * heights.width + x as usize;
+                    let delta = heights.data[i].min(erode * weight);
+                    heights.data[i] -= delta;
+                    sediment += delta;
+                }
+            }
+
+            speed = (speed * speed - dh * self.gravity).max(0.0).sqrt();
+            water *= 1.0 - self.evaporate_speed;
+        }
+    }
+}
+
+impl Default for HydraulicErosion {
+    fn default() -> Self {
+        HydraulicErosion::build(0)
+    }
+}
+
+/// Thermal weathering, material slides to lower neighbours wherever the slope is steeper than the talus angle.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ThermalErosion {
+    pub iterations: u32,
+    /// steepest stable slope in degrees
+    pub talus_angle: f32,
+    /// fraction of the material above the talus slope moved per iteration
+    pub rate: f32,
+}
+
+impl ThermalErosion {
+    pub fn build(iterations: u32, talus_angle: f32) -> Self {
+        Self {
+            iterations,
+            talus_angle,
+            rate: 0.5,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.talus_angle >= 0.0 && self.talus_angle < 90.0) {
+            return Err(Error::invalid_parameter(format!("talus_angle must be in [0, 90) degrees, got {}", self.talus_angle)));
+        }
+        if !(self.rate >= 0.0 && self.rate <= 1.0) {
+            return Err(Error::invalid_parameter(format!("thermal erosion rate must be in [0, 1], got {}", self.rate)));
+        }
+        Ok(())
+    }
+
+    /// How far in samples the result of a sample can be affected by the map border.
+    /// Every iteration reads the neighbours of the neighbours.
+    pub fn get_reach(&self) -> usize {
+        2 * self.iterations as usize
+    }
+
+    pub fn apply(&self, map: &mut HeightMap, height_scale: f32) {
+        const NEIGHBOURS: [(i64, i64); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+        if height_scale <= 0.0 || map.width == 0 || map.height == 0 {
+            return;
+        }
+        let (width, height) = (map.width as i64, map.height as i64);
+        let talus = self.talus_angle.to_radians().tan() * map.spacing / height_scale;
+        let rate = self.rate.clamp(0.0, 1.0);
+
+        for _ in 0..self.iterations {
+            // every sample works out what it sends to each neighbour from the previous heights
+            let outflow: Vec<[f32; 8]> = (0..map.data.len()).into_par_iter().map(|i| {
+                let (x, y) = (i as i64 % width, i as i64 / width);
+                let h = map.data[i];
+                let mut diffs = [0f32; 8];
+                let mut max_excess = 0f32;
+                let mut total = 0f32;
+                for (k, (dx, dy)) in NEIGHBOURS.iter().enumerate() {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                        continue;
+                    }
+                    let dist = if *dx != 0 && *dy != 0 { std::f32::consts::SQRT_2 } else { 1.0 };
+                    let d = h - map.data[(ny * width + nx) as usize];
+                    if d > talus * dist {
+                        diffs[k] = d;
+                        total += d;
+                        max_excess = max_excess.max(d - talus * dist);
+                    }
+                }
+                if total > 0.0 {
+                    let moved = rate * max_excess / 2.0;
+                    diffs.iter_mut().for_each(|d| *d *= moved / total);
+                }
+                diffs
+            }).collect();
+
+            let outflow = &outflow;
+            map.data.par_iter_mut().enumerate().for_each(|(i, h)| {
+                let (x, y) = (i as i64 % width, i as i64 / width);
+                *h -= outflow[i].iter().sum::<f32>();
+                for (k, (dx, dy)) in NEIGHBOURS.iter().enumerate() {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                        continue;
+                    }
+                    // the neighbour sends to us through the opposite direction
+                    *h += outflow[(ny * width + nx) as usize][7 - k];
+                }
+            });
+        }
+    }
+}
+
+/// bilinear height and gradient at a position inside the map
+fn height_and_gradient(map: &HeightMap, x: f32, y: f32) -> (f32, f32, f32) {
+    let node_x = (x.floor() as usize).min(map.width - 2);
+    let node_y = (y.floor() as usize).min(map.height - 2);
+    let (u, v) = (x - node_x as f32, y - node_y as f32);
+    let nw = map.get(node_x, node_y);
+    let ne = map.get(node_x + 1, node_y);
+    let sw = map.get(node_x, node_y + 1);
+    let se = map.get(node_x + 1, node_y + 1);
+    let grad_x = (ne - nw) * (1.0 - v) + (se - sw) * v;
+    let grad_y = (sw - nw) * (1.0 - u) + (se - ne) * u;
+    let h = nw * (1.0 - u) * (1.0 - v) + ne * u * (1.0 - v) + sw * (1.0 - u) * v + se * u * v;
+    (h, grad_x, grad_y)
+}
+
+fn tent(cell: i64, center: i64, tile: i64) -> f32 {
+    (1.0 - (cell - center).abs() as f32 / tile as f32).max(0.0)
+}
+
+/// splitmix64 over the seed, the world cell and the droplet index
+fn hash_cell(seed: u32, x: i64, y: i64, k: u32) -> u64 {
+    let mut h = (seed as u64) ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F) ^ (k as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^ (h >> 31)
+}
+
+#[cfg(test)]
+mod erosion_tests {
+    use crate::{chunkgen::{ChunkGen2D, MountainousTerrainChunkGen}, heightmap::HeightMap, noisegen::{BaseNoiseType, FractalType, MountainousTerrainNoise}, unity::{Position2D32, Position2D64, Position3D64}};
+
+    use super::{HydraulicErosion, ThermalErosion};
+
+    #[test]
+    fn test_erosion_is_deterministic() {
+        let chunkgen = MountainousTerrainChunkGen::build(16, 50.0);
+        let map = HeightMap::from_chunkgen(&chunkgen, Position3D64::default(), 8);
+        let erosion = HydraulicErosion::build(42);
+        let first = erosion.erode(&map);
+        let second = erosion.erode(&map);
+        assert_eq!(first, second);
+        assert!(first.heights != map);
+        assert!(first.flow.data.iter().any(|f| *f > 0.0));
+    }
+
+    #[test]
+    fn test_thermal_erosion_limits_slope() {
+        let mut map = HeightMap::build(9, 9, Position2D64::default(), 1.0);
+        map.set(4, 4, 1.0);
+        let total: f32 = map.data.iter().sum();
+        let mut eroded = map.clone();
+        eroded.thermal_erode(&ThermalErosion::build(200, 30.0), 10.0);
+
+        assert!((eroded.data.iter().sum::<f32>() - total).abs() < 1e-4);
+        assert!(eroded.get(4, 4) < 0.5);
+        let talus = 30f32.to_radians().tan() / 10.0;
+        assert!(eroded.get(4, 4) - eroded.get(5, 4) < talus * 1.1);
+    }
+
+    #[test]
+    fn test_thermal_chunks_match_at_edges() {
+        let mut chunkgen = MountainousTerrainChunkGen::build(8, 200.0);
+        chunkgen.noise = MountainousTerrainNoise::build(1, 5.0, 0.5, 2.0, 4, -1.0, 0.5, Position2D32{x: 0.4, y: 0.1}, Position2D32{x: 0.6, y: 0.5}, 0.5, FractalType::Fbm, BaseNoiseType::Perlin);
+        chunkgen.thermal_erosion = Some(ThermalErosion::build(4, 20.0));
+        let left = chunkgen.sample_height_map(Position3D64::default(), 0);
+        let right = chunkgen.sample_height_map(Position3D64{x: 8.0, y: 0.0, z: 0.0}, 0);
+        assert!(left != HeightMap::from_chunkgen(&chunkgen, Position3D64::default(), 0));
+        for y in 0..left.height {
+            assert!((left.get(8, y) - right.get(0, y)).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_chunks_match_at_edges() {
+        let chunkgen = MountainousTerrainChunkGen::build(16, 50.0);
+        let mut erosion = HydraulicErosion::build(7);
+        erosion.tile_size = 8;
+        erosion.apron = 8;
+        erosion.max_lifetime = 8;
+        let left = erosion.erode_chunk(&chunkgen, Position3D64::default());
+        let right = erosion.erode_chunk(&chunkgen, Position3D64{x: 16.0, y: 0.0, z: 0.0});
+        for y in 0..left.heights.height {
+            assert_eq!(left.heights.get(16, y), right.heights.get(0, y));
+            assert_eq!(left.flow.get(16, y), right.flow.get(0, y));
+        }
+    }
+
+    #[test]
+    fn test_odd_chunks_match_at_edges() {
+        // an odd side_len puts chunk origins half a cell off the world grid
+        let chunkgen = MountainousTerrainChunkGen::build(15, 50.0);
+        let mut erosion = HydraulicErosion::build(7);
+        erosion.tile_size = 8;
+        erosion.apron = 8;
+        erosion.max_lifetime = 8;
+        let left = erosion.erode_chunk(&chunkgen, Position3D64::default());
+        let right = erosion.erode_chunk(&chunkgen, Position3D64{x: 15.0, y: 0.0, z: 0.0});
+        let below = erosion.erode_chunk(&chunkgen, Position3D64{x: 0.0, y: 0.0, z: -15.0});
+        assert_eq!(left.heights.lattice_offset(), Position2D64{ x: 0.5, y: 0.5 });
+        for i in 0..16 {
+            assert_eq!(left.heights.get(15, i), right.heights.get(0, i));
+            assert_eq!(left.flow.get(15, i), right.flow.get(0, i));
+            assert_eq!(left.heights.get(i, 0), below.heights.get(i, 15));
+        }
+
+        // without erosion the tiles give back the heights at the chunk's own sample positions
+        erosion.erode_speed = 0.0;
+        erosion.deposit_speed = 0.0;
+        let still = erosion.erode_chunk(&chunkgen, Position3D64{x: 15.0, y: 0.0, z: 30.0});
+        let grid = HeightMap::from_chunkgen(&chunkgen, Position3D64{x: 15.0, y: 0.0, z: 30.0}, 0);
+        for (eroded, h) in still.heights.data.iter().zip(grid.data.iter()) {
+            assert!((eroded - h).abs() < 1e-5);
+        }
+    }
+}
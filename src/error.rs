@@ -0,0 +1,56 @@
+use std::{error, fmt};
+
+use crate::preset::PresetError;
+
+/// Errors of the generators and loggers.
+#[derive(Debug)]
+pub enum Error {
+    /// a setting that would generate NaN values or no output at all
+    InvalidParameter(String),
+    /// a caller buffer shorter than the output needs
+    BufferTooSmall { buffer: &'static str, len: usize, required: usize },
+    Preset(PresetError),
+    Logger(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub(crate) fn invalid_parameter<S: Into<String>>(message: S) -> Self {
+        Error::InvalidParameter(message.into())
+    }
+
+    /// Checks that `buffer` holds at least `required` elements.
+    pub(crate) fn check_len<T>(buffer: &'static str, slice: &[T], required: usize) -> Result<()> {
+        match slice.len() < required {
+            true => Err(Error::BufferTooSmall { buffer, len: slice.len(), required }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidParameter(message) => write!(f, "{}", message),
+            Error::BufferTooSmall { buffer, len, required } => write!(f, "{} holds {} elements, {} are needed", buffer, len, required),
+            Error::Preset(err) => write!(f, "{}", err),
+            Error::Logger(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Preset(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<PresetError> for Error {
+    fn from(err: PresetError) -> Self {
+        Error::Preset(err)
+    }
+}
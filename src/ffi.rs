@@ -0,0 +1,1686 @@
+//! The C ABI of the library, a thin layer over the rust api for hosts such as Unity.
+//! Every entry point returns a `MeshgenError` and borrows the handles it is given.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
+use std::{cell::RefCell, convert::TryInto, ffi::{CStr, CString}, fmt, mem, os::raw::c_char, panic::{self, AssertUnwindSafe}, ptr};
+use half::f16;
+use log::{error, info, LevelFilter};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    analysis::TerrainAnalysis,
+    biome::BiomeBlend,
+    chunkgen::{BiomeChunkGen, ChunkGen2D, ChunkGeometryDesc, MountainousTerrainChunkGen},
+    error::{self, Error},
+    erosion::ThermalErosion,
+    gradient::{BlendType, ColorKey, ColorKeyGradient},
+    heightmap::{NormalMap, UvMapping},
+    layout::{VertexAttributeDesc, VertexLayout},
+    logger::{init_callback, init_file, set_module_level, shutdown, LogCallback},
+    noisegen::{BaseNoiseType, MountainousTerrainNoiseParams},
+    preset::{self, PresetError},
+    quadtree::{NodeId, Quadtree, QuadtreeParams},
+    streaming::{ChunkCoord, ChunkStreamer},
+    texturegen::{BiomeTextureGen, MountainousTerrainTextureGen, TextureGen2D},
+    unity::{Color32, Position2D32, Position2D64, Position3D32, Position3D64, Quad, SimpleVertex, Triangle, TriplanarWeights32, Vertex},
+};
+
+/// Status returned by every ffi entry point. Anything but `Ok` leaves a message
+/// for `meshgen_last_error_message` on the calling thread.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MeshgenError {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidParameter = 2,
+    InvalidPreset = 3,
+    BufferTooLarge = 4,
+    Panic = 5,
+    Logger = 6,
+    /// the chunk is not generated yet or was evicted from the cache
+    ChunkNotReady = 7,
+}
+
+/// Error of an ffi entry point, the code handed to the host and the message kept for it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FfiError {
+    pub code: MeshgenError,
+    pub message: String,
+}
+
+impl FfiError {
+    pub fn new<S: Into<String>>(code: MeshgenError, message: S) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    pub fn null_pointer(name: &str) -> Self {
+        Self::new(MeshgenError::NullPointer, format!("pointer to {} is null", name))
+    }
+
+    pub fn invalid_parameter<S: Into<String>>(message: S) -> Self {
+        Self::new(MeshgenError::InvalidParameter, message)
+    }
+}
+
+impl fmt::Display for FfiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for FfiError {}
+
+impl From<PresetError> for FfiError {
+    fn from(err: PresetError) -> Self {
+        Self::new(MeshgenError::InvalidPreset, err.to_string())
+    }
+}
+
+impl From<Error> for FfiError {
+    fn from(err: Error) -> Self {
+        let code = match err {
+            Error::InvalidParameter(_) | Error::BufferTooSmall { .. } => MeshgenError::InvalidParameter,
+            Error::Preset(_) => MeshgenError::InvalidPreset,
+            Error::Logger(_) => MeshgenError::Logger,
+        };
+        Self::new(code, err.to_string())
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: &str) {
+    // interior nuls would truncate the message on the host side anyway
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
+
+/// Runs the body of the ffi entry point `name`. The last error of the thread is cleared first,
+/// errors are logged and stored for `meshgen_last_error_message`, panics are reported as `MeshgenError::Panic`.
+pub(crate) fn ffi_call<F: FnOnce() -> Result<(), FfiError>>(name: &str, f: F) -> MeshgenError {
+    clear_last_error();
+    let err = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return MeshgenError::Ok,
+        Ok(Err(err)) => err,
+        Err(payload) => {
+            let reason = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            FfiError::new(MeshgenError::Panic, format!("panicked: {}", reason))
+        },
+    };
+    let message = format!("{}: {}", name, err.message);
+    error!("{}", message);
+    set_last_error(&message);
+    err.code
+}
+
+pub(crate) fn check_not_null<T>(ptr: *const T, name: &str) -> Result<(), FfiError> {
+    match ptr.is_null() {
+        true => Err(FfiError::null_pointer(name)),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects values past the last variant of an enum handed over the ffi as a plain integer.
+pub(crate) fn check_enum(value: u32, count: u32, name: &str) -> Result<(), FfiError> {
+    match value < count {
+        true => Ok(()),
+        _ => Err(FfiError::invalid_parameter(format!("unknown {} {}, expected 0 to {}", name, value, count - 1))),
+    }
+}
+
+/// Borrows the object behind a handle the host got from one of the `get_*` functions.
+pub(crate) fn deref_mut_ptr<'a, T>(ptr: *mut T, name: &str) -> Result<&'a mut T, FfiError> {
+    unsafe { ptr.as_mut() }.ok_or_else(|| FfiError::null_pointer(name))
+}
+
+pub(crate) fn deref_ptr<'a, T>(ptr: *const T, name: &str) -> Result<&'a T, FfiError> {
+    unsafe { ptr.as_ref() }.ok_or_else(|| FfiError::null_pointer(name))
+}
+
+/// Moves a generator to the heap, the host owns it until it hands it back to `free_handle`.
+fn into_handle<T>(value: T) -> *mut T {
+    Box::into_raw(Box::new(value))
+}
+
+fn free_handle<T>(ptr: *mut T) {
+    if !ptr.is_null() {
+        let _value = unsafe { Box::from_raw(ptr) };
+    }
+}
+
+/// Copies the `key_cnt` keys handed over the ffi, `blend_mode` true blends linearly.
+fn gradient_from_raw(color_key_arr: *const ColorKey, key_cnt: usize, blend_mode: bool) -> Result<ColorKeyGradient, FfiError> {
+    let blend_type = match blend_mode {
+        true => BlendType::Linear,
+        _ => BlendType::Discrete,
+    };
+    if key_cnt < 1 {
+        return Ok(ColorKeyGradient{blend_type, keys: Vec::new()});
+    }
+    check_not_null(color_key_arr, "color_key_arr")?;
+    let keys = unsafe { std::slice::from_raw_parts(color_key_arr, key_cnt) }.to_vec();
+    let gradient = ColorKeyGradient{blend_type, keys};
+    gradient.validate()?;
+    Ok(gradient)
+}
+
+/// Parses and checks a nul terminated preset, format: 0 json, 1 ron, 2 toml
+fn load_preset<T, F>(preset: *const c_char, format: u32, validate: F) -> Result<T, FfiError>
+where
+    T: DeserializeOwned,
+    F: FnOnce(&T) -> error::Result<()>,
+{
+    check_not_null(preset, "preset")?;
+    check_enum(format, 3, "preset format")?;
+    let preset = unsafe { CStr::from_ptr(preset) }.to_str().map_err(PresetError::from)?;
+    let loaded = preset::from_str::<T>(preset, format.into())?;
+    validate(&loaded).map_err(|err| FfiError::new(MeshgenError::InvalidPreset, err.to_string()))?;
+    Ok(loaded)
+}
+
+/// Copy of the message left by the last call on this thread that didn't return `Ok`,
+/// null if it succeeded. The copy belongs to the caller, release it with `meshgen_free_error_message`.
+#[no_mangle]
+pub extern "C" fn meshgen_last_error_message() -> *mut c_char {
+    LAST_ERROR.with(|last| match last.borrow().as_ref() {
+        Some(message) => message.clone().into_raw(),
+        None => ptr::null_mut(),
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn meshgen_free_error_message(message: *mut c_char) {
+    if !message.is_null() {
+        let _message = unsafe { CString::from_raw(message) };
+    }
+}
+
+fn fill_chunk(chunkgen: *mut MountainousTerrainChunkGen, vert_buf: *mut SimpleVertex, indx_buf: *mut Quad, tex_buf: *mut Color32, plane_pos: Position3D64) -> Result<(), FfiError> {
+    let chunkgen = deref_ptr(chunkgen, "chunkgen")?;
+    check_not_null(vert_buf, "vert_buf")?;
+    check_not_null(indx_buf, "indx_buf")?;
+    check_not_null(tex_buf, "tex_buf")?;
+    let desc = chunkgen.get_geometry_desc();
+    let (vert_buffer, indx_buffer, tex_buffer) = unsafe {(
+        std::slice::from_raw_parts_mut(vert_buf, desc.v_count),
+        std::slice::from_raw_parts_mut(indx_buf, desc.quad_count()),
+        std::slice::from_raw_parts_mut(tex_buf, desc.v_count),
+    )};
+    Ok(chunkgen.fill_chunk(vert_buffer, indx_buffer, tex_buffer, plane_pos)?)
+}
+
+fn fill_chunk_with_normals(chunkgen: *mut MountainousTerrainChunkGen, vert_buf: *mut Vertex, indx_buf: *mut Quad, tex_buf: *mut Color32, plane_pos: Position3D64) -> Result<(), FfiError> {
+    let chunkgen = deref_ptr(chunkgen, "chunkgen")?;
+    check_not_null(vert_buf, "vert_buf")?;
+    check_not_null(indx_buf, "indx_buf")?;
+    check_not_null(tex_buf, "tex_buf")?;
+    let desc = chunkgen.get_geometry_desc();
+    let (vert_buffer, indx_buffer, tex_buffer) = unsafe {(
+        std::slice::from_raw_parts_mut(vert_buf, desc.v_count),
+        std::slice::from_raw_parts_mut(indx_buf, desc.quad_count()),
+        std::slice::from_raw_parts_mut(tex_buf, desc.v_count),
+    )};
+    Ok(chunkgen.fill_chunk_with_normals(vert_buffer, indx_buffer, tex_buffer, plane_pos)?)
+}
+
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_chunk(chunkgen: *mut MountainousTerrainChunkGen, vert_buf: *mut SimpleVertex, indx_buf: *mut Quad, tex_buf: *mut Color32, plane_pos: *mut Position3D32) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_chunk", || {
+        let plane_pos = *deref_ptr(plane_pos, "plane_pos")?;
+        fill_chunk(chunkgen, vert_buf, indx_buf, tex_buf, plane_pos.into())
+    })
+}
+
+/// Same as `fill_mountainous_terrain_chunk` with the chunk center in double precision,
+/// for worlds reaching past where an `f32` resolves the vertex spacing. Vertices stay relative to the center.
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_chunk_f64(chunkgen: *mut MountainousTerrainChunkGen, vert_buf: *mut SimpleVertex, indx_buf: *mut Quad, tex_buf: *mut Color32, plane_pos: *mut Position3D64) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_chunk_f64", || {
+        let plane_pos = *deref_ptr(plane_pos, "plane_pos")?;
+        fill_chunk(chunkgen, vert_buf, indx_buf, tex_buf, plane_pos)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_chunk_with_normals(chunkgen: *mut MountainousTerrainChunkGen, vert_buf: *mut Vertex, indx_buf: *mut Quad, tex_buf: *mut Color32, plane_pos: *mut Position3D32) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_chunk_with_normals", || {
+        let plane_pos = *deref_ptr(plane_pos, "plane_pos")?;
+        fill_chunk_with_normals(chunkgen, vert_buf, indx_buf, tex_buf, plane_pos.into())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_chunk_with_normals_f64(chunkgen: *mut MountainousTerrainChunkGen, vert_buf: *mut Vertex, indx_buf: *mut Quad, tex_buf: *mut Color32, plane_pos: *mut Position3D64) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_chunk_with_normals_f64", || {
+        let plane_pos = *deref_ptr(plane_pos, "plane_pos")?;
+        fill_chunk_with_normals(chunkgen, vert_buf, indx_buf, tex_buf, plane_pos)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn free_mountainous_terrain_chunkgen(chunkgen: *mut MountainousTerrainChunkGen) -> MeshgenError {
+    info!("freeing mountainous terrain chunkgen");
+    ffi_call("free_mountainous_terrain_chunkgen", || {
+        free_handle(chunkgen);
+        Ok(())
+    })
+}
+
+/// writes the new chunkgen to `chunkgen`, free it with `free_mountainous_terrain_chunkgen`
+#[no_mangle]
+pub extern "C" fn get_mountainous_terrain_chunkgen(side_len: usize, height: f64, chunkgen: *mut *mut MountainousTerrainChunkGen) -> MeshgenError {
+    info!("getting mountainous terrain chunkgen");
+    ffi_call("get_mountainous_terrain_chunkgen", || {
+        let out = deref_mut_ptr(chunkgen, "chunkgen")?;
+        let built = MountainousTerrainChunkGen::build(side_len, height);
+        built.validate()?;
+        *out = into_handle(built);
+        Ok(())
+    })
+}
+
+/// format: 0 json, 1 ron, 2 toml
+/// fields missing from the preset keep their defaults, `chunkgen` is left untouched if the preset is invalid
+#[no_mangle]
+pub extern "C" fn get_mountainous_terrain_chunkgen_from_preset(preset: *const c_char, format: u32, chunkgen: *mut *mut MountainousTerrainChunkGen) -> MeshgenError {
+    info!("getting mountainous terrain chunkgen from preset");
+    ffi_call("get_mountainous_terrain_chunkgen_from_preset", || {
+        let out = deref_mut_ptr(chunkgen, "chunkgen")?;
+        *out = into_handle(load_preset(preset, format, MountainousTerrainChunkGen::validate)?);
+        Ok(())
+    })
+}
+
+
+/// Replaces the whole configuration of the chunkgen, format: 0 json, 1 ron, 2 toml
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_chunkgen_preset(chunkgen: *mut MountainousTerrainChunkGen, preset: *const c_char, format: u32) -> MeshgenError {
+    info!("setting mountainous terrain chunkgen preset");
+    ffi_call("set_mountainous_terrain_chunkgen_preset", || {
+        let chunkgen = deref_mut_ptr(chunkgen, "chunkgen")?;
+        *chunkgen = load_preset(preset, format, MountainousTerrainChunkGen::validate)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_chunkgen_dim(chunkgen: *mut MountainousTerrainChunkGen, side_len: usize, height: f64) -> MeshgenError {
+    info!("setting mountainous terrain chunkgen dimensions");
+    ffi_call("set_mountainous_terrain_chunkgen_dim", || {
+        let chunkgen = deref_mut_ptr(chunkgen, "chunkgen")?;
+        if side_len < 1 {
+            return Err(FfiError::invalid_parameter("side_len must be at least 1"));
+        }
+        if !height.is_finite() {
+            return Err(FfiError::invalid_parameter(format!("height must be finite, got {}", height)));
+        }
+        chunkgen.side_len = side_len;
+        chunkgen.height = height;
+        Ok(())
+    })
+}
+
+/// fractal_type: 0 fbm, 1 ridged, 2 billow, 3 hybrid multi
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_chunkgen_noise(chunkgen: *mut MountainousTerrainChunkGen, seed: u32, octaves: u32, scale: f64, persistance: f64, lacunarity: f64, displacement: f64, a: f64, bezier_bias_from: *mut Position2D32, bezier_bias_to: *mut Position2D32, bezier_bias_corner_curvature: f64, fractal_type: u32) -> MeshgenError {
+    info!("setting mountainous terrain chunkgen noise");
+    ffi_call("set_mountainous_terrain_chunkgen_noise", || {
+        let chunkgen = deref_mut_ptr(chunkgen, "chunkgen")?;
+        let bezier_bias_from = *deref_ptr(bezier_bias_from, "bezier_bias_from")?;
+        let bezier_bias_to = *deref_ptr(bezier_bias_to, "bezier_bias_to")?;
+        check_enum(fractal_type, 4, "fractal type")?;
+        let params = MountainousTerrainNoiseParams {
+            seed,
+            scale,
+            persistance,
+            lacunarity,
+            octaves,
+            displacement,
+            bias_gain: a,
+            bezier_bias_from,
+            bezier_bias_to,
+            bezier_bias_corner_curvature,
+            fractal_type: fractal_type.into(),
+            base_noise_type: chunkgen.noise.base_noise_type,
+        };
+        chunkgen.noise = params.try_build()?;
+        Ok(())
+    })
+}
+
+/// base_noise: 0 perlin, 1 open simplex, 2 super simplex, 3 value, 4 worley
+/// worley_distance: 0 euclidean, 1 euclidean squared, 2 manhattan, 3 chebyshev, 4 quadratic
+/// worley_return: 0 cell value, 1 distance
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_chunkgen_base_noise(chunkgen: *mut MountainousTerrainChunkGen, base_noise: u32, worley_distance: u32, worley_return: u32) -> MeshgenError {
+    info!("setting mountainous terrain chunkgen base noise");
+    ffi_call("set_mountainous_terrain_chunkgen_base_noise", || {
+        let chunkgen = deref_mut_ptr(chunkgen, "chunkgen")?;
+        check_enum(base_noise, 5, "base noise")?;
+        check_enum(worley_distance, 5, "worley distance")?;
+        check_enum(worley_return, 2, "worley return")?;
+        chunkgen.noise.set_base_noise(BaseNoiseType::build(base_noise, worley_distance, worley_return));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_chunkgen_color_gradient(chunkgen: *mut MountainousTerrainChunkGen, color_key_arr: *mut ColorKey, key_cnt: usize, blend_mode: bool) -> MeshgenError {
+    ffi_call("set_mountainous_terrain_chunkgen_color_gradient", || {
+        let chunkgen = deref_mut_ptr(chunkgen, "chunkgen")?;
+        chunkgen.color_gradient = gradient_from_raw(color_key_arr, key_cnt, blend_mode)?;
+        info!("{:?}", chunkgen.color_gradient.keys);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_chunkgen_lod(chunkgen: *mut MountainousTerrainChunkGen, lod: u32, skirt_depth: f64) -> MeshgenError {
+    info!("setting mountainous terrain chunkgen lod");
+    ffi_call("set_mountainous_terrain_chunkgen_lod", || {
+        let chunkgen = deref_mut_ptr(chunkgen, "chunkgen")?;
+        if !skirt_depth.is_finite() {
+            return Err(FfiError::invalid_parameter(format!("skirt_depth must be finite, got {}", skirt_depth)));
+        }
+        chunkgen.lod = lod;
+        chunkgen.skirt_depth = skirt_depth;
+        Ok(())
+    })
+}
+
+/// talus_angle is in degrees, rate is the fraction of the excess material moved per iteration
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_chunkgen_thermal_erosion(chunkgen: *mut MountainousTerrainChunkGen, enabled: bool, iterations: u32, talus_angle: f64, rate: f64) -> MeshgenError {
+    info!("setting mountainous terrain chunkgen thermal erosion");
+    ffi_call("set_mountainous_terrain_chunkgen_thermal_erosion", || {
+        let chunkgen = deref_mut_ptr(chunkgen, "chunkgen")?;
+        chunkgen.thermal_erosion = match enabled {
+            true => {
+                let thermal_erosion = ThermalErosion{ iterations, talus_angle: talus_angle as f32, rate: rate as f32 };
+                thermal_erosion.validate()?;
+                Some(thermal_erosion)
+            },
+            _ => None,
+        };
+        Ok(())
+    })
+}
+
+/// mode: 0 per chunk uvs, 1 world uvs repeating every `tiling` world units, 2 world uvs with triplanar weights,
+/// `sharpness` is the exponent of the weights from `fill_mountainous_terrain_chunk_triplanar_weights`
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_chunkgen_uv_mapping(chunkgen: *mut MountainousTerrainChunkGen, mode: u32, tiling: f64, sharpness: f64) -> MeshgenError {
+    info!("setting mountainous terrain chunkgen uv mapping");
+    ffi_call("set_mountainous_terrain_chunkgen_uv_mapping", || {
+        let chunkgen = deref_mut_ptr(chunkgen, "chunkgen")?;
+        check_enum(mode, 3, "uv mode")?;
+        let uv_mapping = UvMapping { mode: mode.into(), tiling, sharpness };
+        uv_mapping.validate()?;
+        chunkgen.uv_mapping = uv_mapping;
+        Ok(())
+    })
+}
+
+fn fill_chunk_triplanar_weights(chunkgen: *mut MountainousTerrainChunkGen, weight_buf: *mut TriplanarWeights32, plane_pos: Position3D64) -> Result<(), FfiError> {
+    let chunkgen = deref_ptr(chunkgen, "chunkgen")?;
+    check_not_null(weight_buf, "weight_buf")?;
+    let v_count = chunkgen.get_geometry_desc().v_count;
+    let weight_buffer = unsafe { std::slice::from_raw_parts_mut(weight_buf, v_count) };
+    Ok(chunkgen.fill_chunk_triplanar_weights(weight_buffer, plane_pos)?)
+}
+
+/// Triplanar blend weights, one per vertex of `fill_mountainous_terrain_chunk_with_normals` and in the same order.
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_chunk_triplanar_weights(chunkgen: *mut MountainousTerrainChunkGen, weight_buf: *mut TriplanarWeights32, plane_pos: *mut Position3D32) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_chunk_triplanar_weights", || {
+        let plane_pos = *deref_ptr(plane_pos, "plane_pos")?;
+        fill_chunk_triplanar_weights(chunkgen, weight_buf, plane_pos.into())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_chunk_triplanar_weights_f64(chunkgen: *mut MountainousTerrainChunkGen, weight_buf: *mut TriplanarWeights32, plane_pos: *mut Position3D64) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_chunk_triplanar_weights_f64", || {
+        let plane_pos = *deref_ptr(plane_pos, "plane_pos")?;
+        fill_chunk_triplanar_weights(chunkgen, weight_buf, plane_pos)
+    })
+}
+
+/// Writes the layout of the `desc_cnt` attributes at `descs` to `layout`, free it with `free_vertex_layout`.
+/// The descriptors have the memory layout of unity's `VertexAttributeDescriptor`, attribute: 0 position,
+/// 1 normal, 2 tangent, 3 color, 4 uv0, 5 uv1 (triplanar weights), 6 biome weights,
+/// format: 0 float32, 1 float16, 2 unorm8, 3 snorm8
+#[no_mangle]
+pub extern "C" fn get_vertex_layout(descs: *const VertexAttributeDesc, desc_cnt: usize, layout: *mut *mut VertexLayout) -> MeshgenError {
+    info!("getting vertex layout");
+    ffi_call("get_vertex_layout", || {
+        let out = deref_mut_ptr(layout, "layout")?;
+        check_not_null(descs, "descs")?;
+        let descs = unsafe { std::slice::from_raw_parts(descs, desc_cnt) };
+        *out = into_handle(VertexLayout::build(descs)?);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn free_vertex_layout(layout: *mut VertexLayout) -> MeshgenError {
+    info!("freeing vertex layout");
+    ffi_call("free_vertex_layout", || {
+        free_handle(layout);
+        Ok(())
+    })
+}
+
+/// Bytes per vertex of `stream`, a stream holds `v_count * stride` bytes. Streams without attributes have a stride of 0.
+#[no_mangle]
+pub extern "C" fn get_vertex_layout_stride(layout: *mut VertexLayout, stream: u32, stride: *mut u32) -> MeshgenError {
+    ffi_call("get_vertex_layout_stride", || {
+        let layout = deref_ptr(layout, "layout")?;
+        let out = deref_mut_ptr(stride, "stride")?;
+        *out = layout.strides.get(stream as usize).copied().unwrap_or(0) as u32;
+        Ok(())
+    })
+}
+
+/// `streams` points to one buffer per stream of the layout, buffers of streams with a stride of 0 can be null.
+fn fill_chunk_with_layout<C: ChunkGen2D + Sync>(chunkgen: &C, layout: *mut VertexLayout, streams: *mut *mut u8, indx_buf: *mut Quad, plane_pos: Position3D64) -> Result<(), FfiError> {
+    let layout = deref_ptr(layout, "layout")?;
+    check_not_null(indx_buf, "indx_buf")?;
+    let desc = chunkgen.get_geometry_desc();
+    let mut stream_buffers = Vec::with_capacity(layout.stream_count());
+    if layout.stream_count() > 0 {
+        check_not_null(streams, "streams")?;
+        let stream_ptrs = unsafe { std::slice::from_raw_parts(streams, layout.stream_count()) };
+        for (stream_ptr, stride) in stream_ptrs.iter().zip(layout.strides.iter()) {
+            let len = desc.v_count * stride;
+            if len == 0 {
+                stream_buffers.push(&mut [][..]);
+                continue;
+            }
+            check_not_null(*stream_ptr, "stream")?;
+            stream_buffers.push(unsafe { std::slice::from_raw_parts_mut(*stream_ptr, len) });
+        }
+    }
+    let indx_buffer = unsafe { std::slice::from_raw_parts_mut(indx_buf, desc.quad_count()) };
+    Ok(chunkgen.fill_chunk_with_layout(layout, &mut stream_buffers, indx_buffer, plane_pos)?)
+}
+
+/// Fills the vertex streams of `layout` instead of a fixed vertex struct, see `get_vertex_layout`.
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_chunk_with_layout(chunkgen: *mut MountainousTerrainChunkGen, layout: *mut VertexLayout, streams: *mut *mut u8, indx_buf: *mut Quad, plane_pos: *mut Position3D32) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_chunk_with_layout", || {
+        let chunkgen = deref_ptr(chunkgen, "chunkgen")?;
+        let plane_pos = *deref_ptr(plane_pos, "plane_pos")?;
+        fill_chunk_with_layout(chunkgen, layout, streams, indx_buf, plane_pos.into())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_chunk_with_layout_f64(chunkgen: *mut MountainousTerrainChunkGen, layout: *mut VertexLayout, streams: *mut *mut u8, indx_buf: *mut Quad, plane_pos: *mut Position3D64) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_chunk_with_layout_f64", || {
+        let chunkgen = deref_ptr(chunkgen, "chunkgen")?;
+        let plane_pos = *deref_ptr(plane_pos, "plane_pos")?;
+        fill_chunk_with_layout(chunkgen, layout, streams, indx_buf, plane_pos)
+    })
+}
+
+fn write_geometry_desc(desc: ChunkGeometryDesc, v_count: ptr::NonNull<i32>, e_count: ptr::NonNull<i32>, f_count: ptr::NonNull<i32>) -> Result<(), FfiError> {
+    let _v_count = desc.v_count as u64;
+    let _e_count = desc.e_count as u64;
+    let _f_count = desc.f_count as u64;
+
+    let _v_buf_bytes = _v_count * mem::size_of::<SimpleVertex>() as u64;
+    let _e_buf_bytes = _e_count * mem::size_of::<i32>() as u64;
+    let _f_buf_bytes = _f_count * mem::size_of::<Triangle>() as u64;
+
+    let too_large = |list: &str, bytes: u64| FfiError::new(MeshgenError::BufferTooLarge, format!("{} list would require too many bytes! {}", list, bytes));
+    if _e_buf_bytes >= i32::MAX as u64 {
+        Err(too_large("Edge", _e_buf_bytes))
+    }
+    else if _v_buf_bytes >= i32::MAX as u64 {
+        Err(too_large("Vertex", _v_buf_bytes))
+    }
+    else if _f_buf_bytes >= i32::MAX as u64 {
+        Err(too_large("Face", _f_buf_bytes))
+    }
+    else {
+        unsafe {
+            let v_count_mut = v_count.as_ptr();
+            *v_count_mut = _v_count.try_into().unwrap();
+            let e_count_mut = e_count.as_ptr();
+            *e_count_mut = _e_count.try_into().unwrap();
+            let f_count_mut = f_count.as_ptr();
+            *f_count_mut = _f_count.try_into().unwrap();
+        }
+        Ok(())
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn get_mountainous_terrain_chunk_geometry_desc(chunkgen: *mut MountainousTerrainChunkGen, v_count: ptr::NonNull<i32>, e_count: ptr::NonNull<i32>, f_count: ptr::NonNull<i32>) -> MeshgenError {
+    ffi_call("get_mountainous_terrain_chunk_geometry_desc", || {
+        let chunkgen = deref_ptr(chunkgen, "chunkgen")?;
+        write_geometry_desc(chunkgen.get_geometry_desc(), v_count, e_count, f_count)
+    })
+}
+
+/// buffer sizes the chunkgen would need at `lod`, with its current skirt setting
+#[no_mangle]
+pub extern "C" fn get_mountainous_terrain_chunk_geometry_desc_lod(chunkgen: *mut MountainousTerrainChunkGen, lod: u32, v_count: ptr::NonNull<i32>, e_count: ptr::NonNull<i32>, f_count: ptr::NonNull<i32>) -> MeshgenError {
+    ffi_call("get_mountainous_terrain_chunk_geometry_desc_lod", || {
+        let chunkgen = deref_ptr(chunkgen, "chunkgen")?;
+        let desc = ChunkGeometryDesc::build(chunkgen.side_len, lod, chunkgen.skirt_depth > 0.0);
+        write_geometry_desc(desc, v_count, e_count, f_count)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn free_mountainous_terrain_texturegen(texturegen: *mut MountainousTerrainTextureGen) -> MeshgenError {
+    ffi_call("free_mountainous_terrain_texturegen", || {
+        free_handle(texturegen);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_texturegen_dim(texturegen: *mut MountainousTerrainTextureGen, width: usize, height: usize) -> MeshgenError {
+    ffi_call("set_mountainous_terrain_texturegen_dim", || {
+        let texturegen = deref_mut_ptr(texturegen, "texturegen")?;
+        if width < 1 || height < 1 {
+            return Err(FfiError::invalid_parameter(format!("texture must be at least 1x1, got {}x{}", width, height)));
+        }
+        texturegen.width = width;
+        texturegen.height = height;
+        Ok(())
+    })
+}
+
+/// fractal_type: 0 fbm, 1 ridged, 2 billow, 3 hybrid multi
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_texturegen_noise(texturegen: *mut MountainousTerrainTextureGen, seed: u32, octaves: u32, scale: f64, persistance: f64, lacunarity: f64, displacement: f64, a: f64, bezier_bias_from: Position2D32, bezier_bias_to: Position2D32, bezier_bias_corner_curvature: f64, fractal_type: u32) -> MeshgenError {
+    ffi_call("set_mountainous_terrain_texturegen_noise", || {
+        let texturegen = deref_mut_ptr(texturegen, "texturegen")?;
+        check_enum(fractal_type, 4, "fractal type")?;
+        let params = MountainousTerrainNoiseParams {
+            seed,
+            scale,
+            persistance,
+            lacunarity,
+            octaves,
+            displacement,
+            bias_gain: a,
+            bezier_bias_from,
+            bezier_bias_to,
+            bezier_bias_corner_curvature,
+            fractal_type: fractal_type.into(),
+            base_noise_type: texturegen.noise.base_noise_type,
+        };
+        texturegen.noise = params.try_build()?;
+        Ok(())
+    })
+}
+
+/// base_noise: 0 perlin, 1 open simplex, 2 super simplex, 3 value, 4 worley
+/// worley_distance: 0 euclidean, 1 euclidean squared, 2 manhattan, 3 chebyshev, 4 quadratic
+/// worley_return: 0 cell value, 1 distance
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_texturegen_base_noise(texturegen: *mut MountainousTerrainTextureGen, base_noise: u32, worley_distance: u32, worley_return: u32) -> MeshgenError {
+    info!("setting mountainous terrain texturegen base noise");
+    ffi_call("set_mountainous_terrain_texturegen_base_noise", || {
+        let texturegen = deref_mut_ptr(texturegen, "texturegen")?;
+        check_enum(base_noise, 5, "base noise")?;
+        check_enum(worley_distance, 5, "worley distance")?;
+        check_enum(worley_return, 2, "worley return")?;
+        texturegen.noise.set_base_noise(BaseNoiseType::build(base_noise, worley_distance, worley_return));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_texturegen_color_gradient(texturegen: *mut MountainousTerrainTextureGen, color_key_arr: *mut ColorKey, key_cnt: usize, blend_mode: bool) -> MeshgenError {
+    ffi_call("set_mountainous_terrain_texturegen_color_gradient", || {
+        let texturegen = deref_mut_ptr(texturegen, "texturegen")?;
+        texturegen.color_gradient = gradient_from_raw(color_key_arr, key_cnt, blend_mode)?;
+        info!("{:?}", texturegen.color_gradient.keys);
+        Ok(())
+    })
+}
+
+/// settings of `fill_mountainous_terrain_normal_map_2d`, `strength` scales the normalized heights,
+/// filter: 0 central differences, 1 sobel, space: 0 tangent, 1 object
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_texturegen_normal_map(texturegen: *mut MountainousTerrainTextureGen, strength: f64, filter: u32, space: u32) -> MeshgenError {
+    ffi_call("set_mountainous_terrain_texturegen_normal_map", || {
+        let texturegen = deref_mut_ptr(texturegen, "texturegen")?;
+        check_enum(filter, 2, "normal filter")?;
+        check_enum(space, 2, "normal space")?;
+        let normal_map = NormalMap { strength, filter: filter.into(), space: space.into() };
+        normal_map.validate()?;
+        texturegen.normal_map = normal_map;
+        Ok(())
+    })
+}
+
+/// settings of the `fill_mountainous_terrain_analysis_*` functions, `height_scale` is the world height of a
+/// normalized height of 1, occlusion looks `occlusion_radius` pixels out in `occlusion_directions` directions
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_texturegen_analysis(texturegen: *mut MountainousTerrainTextureGen, height_scale: f64, occlusion_radius: usize, occlusion_directions: u32, curvature_scale: f64) -> MeshgenError {
+    ffi_call("set_mountainous_terrain_texturegen_analysis", || {
+        let texturegen = deref_mut_ptr(texturegen, "texturegen")?;
+        let analysis = TerrainAnalysis { height_scale, occlusion_radius, occlusion_directions, curvature_scale };
+        analysis.validate()?;
+        texturegen.analysis = analysis;
+        Ok(())
+    })
+}
+
+/// writes the new texturegen to `texturegen`, free it with `free_mountainous_terrain_texturegen`
+#[no_mangle]
+pub extern "C" fn get_mountainous_terrain_texturegen(width: usize, height: usize, texturegen: *mut *mut MountainousTerrainTextureGen) -> MeshgenError {
+    info!("getting MountainousTerrainTextureGen...");
+    ffi_call("get_mountainous_terrain_texturegen", || {
+        let out = deref_mut_ptr(texturegen, "texturegen")?;
+        let built = MountainousTerrainTextureGen::build(width, height);
+        built.validate()?;
+        *out = into_handle(built);
+        Ok(())
+    })
+}
+
+/// format: 0 json, 1 ron, 2 toml
+/// fields missing from the preset keep their defaults, `texturegen` is left untouched if the preset is invalid
+#[no_mangle]
+pub extern "C" fn get_mountainous_terrain_texturegen_from_preset(preset: *const c_char, format: u32, texturegen: *mut *mut MountainousTerrainTextureGen) -> MeshgenError {
+    info!("getting MountainousTerrainTextureGen from preset...");
+    ffi_call("get_mountainous_terrain_texturegen_from_preset", || {
+        let out = deref_mut_ptr(texturegen, "texturegen")?;
+        *out = into_handle(load_preset(preset, format, MountainousTerrainTextureGen::validate)?);
+        Ok(())
+    })
+}
+
+
+/// Replaces the whole configuration of the texturegen, format: 0 json, 1 ron, 2 toml
+#[no_mangle]
+pub extern "C" fn set_mountainous_terrain_texturegen_preset(texturegen: *mut MountainousTerrainTextureGen, preset: *const c_char, format: u32) -> MeshgenError {
+    info!("setting mountainous terrain texturegen preset");
+    ffi_call("set_mountainous_terrain_texturegen_preset", || {
+        let texturegen = deref_mut_ptr(texturegen, "texturegen")?;
+        *texturegen = load_preset(preset, format, MountainousTerrainTextureGen::validate)?;
+        Ok(())
+    })
+}
+
+fn fill_texture(texturegen: *mut MountainousTerrainTextureGen, bufptr: *mut Color32, pos: Position2D64) -> Result<(), FfiError> {
+    let texturegen = deref_ptr(texturegen, "texturegen")?;
+    check_not_null(bufptr, "bufptr")?;
+    let pix_cnt = texturegen.width * texturegen.height;
+    let tx_buffer: &mut [Color32] = unsafe { std::slice::from_raw_parts_mut(bufptr, pix_cnt) };
+    Ok(texturegen.fill_texture(tx_buffer, pos)?)
+}
+
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_texture_2d(texturegen: *mut MountainousTerrainTextureGen, bufptr: *mut Color32, pos: *mut Position2D32) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_texture_2d", || {
+        let pos = *deref_ptr(pos, "pos")?;
+        fill_texture(texturegen, bufptr, pos.into())
+    })
+}
+
+/// Same as `fill_mountainous_terrain_texture_2d` with the texture center in double precision.
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_texture_2d_f64(texturegen: *mut MountainousTerrainTextureGen, bufptr: *mut Color32, pos: *mut Position2D64) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_texture_2d_f64", || {
+        let pos = *deref_ptr(pos, "pos")?;
+        fill_texture(texturegen, bufptr, pos)
+    })
+}
+
+/// Writes the new chunkgen to `chunkgen`, free it with `free_biome_chunkgen`.
+/// format: 0 json, 1 ron, 2 toml, fields missing from the preset keep their defaults
+#[no_mangle]
+pub extern "C" fn get_biome_chunkgen_from_preset(preset: *const c_char, format: u32, chunkgen: *mut *mut BiomeChunkGen) -> MeshgenError {
+    info!("getting biome chunkgen from preset");
+    ffi_call("get_biome_chunkgen_from_preset", || {
+        let out = deref_mut_ptr(chunkgen, "chunkgen")?;
+        *out = into_handle(load_preset(preset, format, BiomeChunkGen::validate)?);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn free_biome_chunkgen(chunkgen: *mut BiomeChunkGen) -> MeshgenError {
+    info!("freeing biome chunkgen");
+    ffi_call("free_biome_chunkgen", || {
+        free_handle(chunkgen);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn get_biome_chunk_geometry_desc(chunkgen: *mut BiomeChunkGen, v_count: ptr::NonNull<i32>, e_count: ptr::NonNull<i32>, f_count: ptr::NonNull<i32>) -> MeshgenError {
+    ffi_call("get_biome_chunk_geometry_desc", || {
+        let chunkgen = deref_ptr(chunkgen, "chunkgen")?;
+        write_geometry_desc(chunkgen.get_geometry_desc(), v_count, e_count, f_count)
+    })
+}
+
+/// Fills the chunk like `fill_mountainous_terrain_chunk_f64` and the biome blend of every vertex
+/// into `biome_buf`, which holds `v_count` entries. `biome_buf` can be null if the host doesn't need them.
+#[no_mangle]
+pub extern "C" fn fill_biome_chunk(chunkgen: *mut BiomeChunkGen, vert_buf: *mut SimpleVertex, indx_buf: *mut Quad, tex_buf: *mut Color32, biome_buf: *mut BiomeBlend, plane_pos: *mut Position3D64) -> MeshgenError {
+    ffi_call("fill_biome_chunk", || {
+        let chunkgen = deref_ptr(chunkgen, "chunkgen")?;
+        let plane_pos = *deref_ptr(plane_pos, "plane_pos")?;
+        check_not_null(vert_buf, "vert_buf")?;
+        check_not_null(indx_buf, "indx_buf")?;
+        check_not_null(tex_buf, "tex_buf")?;
+        let desc = chunkgen.get_geometry_desc();
+        let (vert_buffer, indx_buffer, tex_buffer) = unsafe {(
+            std::slice::from_raw_parts_mut(vert_buf, desc.v_count),
+            std::slice::from_raw_parts_mut(indx_buf, desc.quad_count()),
+            std::slice::from_raw_parts_mut(tex_buf, desc.v_count),
+        )};
+        chunkgen.fill_chunk(vert_buffer, indx_buffer, tex_buffer, plane_pos)?;
+        if !biome_buf.is_null() {
+            let biome_buffer = unsafe { std::slice::from_raw_parts_mut(biome_buf, desc.v_count) };
+            chunkgen.fill_chunk_biomes(biome_buffer, plane_pos)?;
+        }
+        Ok(())
+    })
+}
+
+/// Same as `fill_mountainous_terrain_chunk_with_layout_f64`, biome weights are the weights of biomes 0 to 3.
+#[no_mangle]
+pub extern "C" fn fill_biome_chunk_with_layout(chunkgen: *mut BiomeChunkGen, layout: *mut VertexLayout, streams: *mut *mut u8, indx_buf: *mut Quad, plane_pos: *mut Position3D64) -> MeshgenError {
+    ffi_call("fill_biome_chunk_with_layout", || {
+        let chunkgen = deref_ptr(chunkgen, "chunkgen")?;
+        let plane_pos = *deref_ptr(plane_pos, "plane_pos")?;
+        fill_chunk_with_layout(chunkgen, layout, streams, indx_buf, plane_pos)
+    })
+}
+
+/// Writes the new texturegen to `texturegen`, free it with `free_biome_texturegen`.
+/// format: 0 json, 1 ron, 2 toml, fields missing from the preset keep their defaults
+#[no_mangle]
+pub extern "C" fn get_biome_texturegen_from_preset(preset: *const c_char, format: u32, texturegen: *mut *mut BiomeTextureGen) -> MeshgenError {
+    info!("getting biome texturegen from preset");
+    ffi_call("get_biome_texturegen_from_preset", || {
+        let out = deref_mut_ptr(texturegen, "texturegen")?;
+        *out = into_handle(load_preset(preset, format, BiomeTextureGen::validate)?);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn free_biome_texturegen(texturegen: *mut BiomeTextureGen) -> MeshgenError {
+    info!("freeing biome texturegen");
+    ffi_call("free_biome_texturegen", || {
+        free_handle(texturegen);
+        Ok(())
+    })
+}
+
+/// Fills `width * height` pixels and, unless `biome_buf` is null, as many biome blends.
+#[no_mangle]
+pub extern "C" fn fill_biome_texture(texturegen: *mut BiomeTextureGen, bufptr: *mut Color32, biome_buf: *mut BiomeBlend, pos: *mut Position2D64) -> MeshgenError {
+    ffi_call("fill_biome_texture", || {
+        let texturegen = deref_ptr(texturegen, "texturegen")?;
+        let pos = *deref_ptr(pos, "pos")?;
+        check_not_null(bufptr, "bufptr")?;
+        let pix_cnt = texturegen.width * texturegen.height;
+        let tx_buffer = unsafe { std::slice::from_raw_parts_mut(bufptr, pix_cnt) };
+        texturegen.fill_texture(tx_buffer, pos)?;
+        if !biome_buf.is_null() {
+            let biome_buffer = unsafe { std::slice::from_raw_parts_mut(biome_buf, pix_cnt) };
+            texturegen.fill_texture_biomes(biome_buffer, pos)?;
+        }
+        Ok(())
+    })
+}
+
+fn fill_normal_map(texturegen: *mut MountainousTerrainTextureGen, bufptr: *mut Color32, pos: Position2D64) -> Result<(), FfiError> {
+    let texturegen = deref_ptr(texturegen, "texturegen")?;
+    check_not_null(bufptr, "bufptr")?;
+    let pix_cnt = texturegen.width * texturegen.height;
+    let tx_buffer: &mut [Color32] = unsafe { std::slice::from_raw_parts_mut(bufptr, pix_cnt) };
+    Ok(texturegen.fill_normal_map(tx_buffer, pos)?)
+}
+
+/// Normal map in unity's encoding, same size and placement as `fill_mountainous_terrain_texture_2d`.
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_normal_map_2d(texturegen: *mut MountainousTerrainTextureGen, bufptr: *mut Color32, pos: *mut Position2D32) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_normal_map_2d", || {
+        let pos = *deref_ptr(pos, "pos")?;
+        fill_normal_map(texturegen, bufptr, pos.into())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_normal_map_2d_f64(texturegen: *mut MountainousTerrainTextureGen, bufptr: *mut Color32, pos: *mut Position2D64) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_normal_map_2d_f64", || {
+        let pos = *deref_ptr(pos, "pos")?;
+        fill_normal_map(texturegen, bufptr, pos)
+    })
+}
+
+fn fill_heights<T>(texturegen: *mut MountainousTerrainTextureGen, bufptr: *mut T, pos: Position2D64, fill: fn(&MountainousTerrainTextureGen, &mut [T], Position2D64) -> error::Result<()>) -> Result<(), FfiError> {
+    let texturegen = deref_ptr(texturegen, "texturegen")?;
+    check_not_null(bufptr, "bufptr")?;
+    let pix_cnt = texturegen.width * texturegen.height;
+    let buffer: &mut [T] = unsafe { std::slice::from_raw_parts_mut(bufptr, pix_cnt) };
+    Ok(fill(texturegen, buffer, pos)?)
+}
+
+/// Normalized heights quantized to 16 bits, same size and placement as `fill_mountainous_terrain_texture_2d`.
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_heights_r16_2d(texturegen: *mut MountainousTerrainTextureGen, bufptr: *mut u16, pos: *mut Position2D32) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_heights_r16_2d", || {
+        let pos = *deref_ptr(pos, "pos")?;
+        fill_heights(texturegen, bufptr, pos.into(), MountainousTerrainTextureGen::fill_heights_r16)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_heights_r16_2d_f64(texturegen: *mut MountainousTerrainTextureGen, bufptr: *mut u16, pos: *mut Position2D64) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_heights_r16_2d_f64", || {
+        let pos = *deref_ptr(pos, "pos")?;
+        fill_heights(texturegen, bufptr, pos, MountainousTerrainTextureGen::fill_heights_r16)
+    })
+}
+
+/// Same as `fill_mountainous_terrain_heights_r16_2d` with one float per pixel.
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_heights_f32_2d(texturegen: *mut MountainousTerrainTextureGen, bufptr: *mut f32, pos: *mut Position2D32) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_heights_f32_2d", || {
+        let pos = *deref_ptr(pos, "pos")?;
+        fill_heights(texturegen, bufptr, pos.into(), MountainousTerrainTextureGen::fill_heights_f32)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_heights_f32_2d_f64(texturegen: *mut MountainousTerrainTextureGen, bufptr: *mut f32, pos: *mut Position2D64) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_heights_f32_2d_f64", || {
+        let pos = *deref_ptr(pos, "pos")?;
+        fill_heights(texturegen, bufptr, pos, MountainousTerrainTextureGen::fill_heights_f32)
+    })
+}
+
+/// Same as `fill_mountainous_terrain_heights_r16_2d` with one IEEE half per pixel, e.g. for an `RHalf` texture.
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_heights_f16_2d(texturegen: *mut MountainousTerrainTextureGen, bufptr: *mut f16, pos: *mut Position2D32) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_heights_f16_2d", || {
+        let pos = *deref_ptr(pos, "pos")?;
+        fill_heights(texturegen, bufptr, pos.into(), MountainousTerrainTextureGen::fill_heights_f16)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_heights_f16_2d_f64(texturegen: *mut MountainousTerrainTextureGen, bufptr: *mut f16, pos: *mut Position2D64) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_heights_f16_2d_f64", || {
+        let pos = *deref_ptr(pos, "pos")?;
+        fill_heights(texturegen, bufptr, pos, MountainousTerrainTextureGen::fill_heights_f16)
+    })
+}
+
+fn fill_analysis_texture(texturegen: *mut MountainousTerrainTextureGen, attribute: u32, bufptr: *mut Color32, pos: Position2D64) -> Result<(), FfiError> {
+    let texturegen = deref_ptr(texturegen, "texturegen")?;
+    check_enum(attribute, 5, "terrain attribute")?;
+    check_not_null(bufptr, "bufptr")?;
+    let pix_cnt = texturegen.width * texturegen.height;
+    let tx_buffer: &mut [Color32] = unsafe { std::slice::from_raw_parts_mut(bufptr, pix_cnt) };
+    Ok(texturegen.fill_analysis_texture(attribute.into(), tx_buffer, pos)?)
+}
+
+fn fill_analysis_values(texturegen: *mut MountainousTerrainTextureGen, attribute: u32, bufptr: *mut f32, pos: Position2D64) -> Result<(), FfiError> {
+    let texturegen = deref_ptr(texturegen, "texturegen")?;
+    check_enum(attribute, 5, "terrain attribute")?;
+    check_not_null(bufptr, "bufptr")?;
+    let pix_cnt = texturegen.width * texturegen.height;
+    let buffer: &mut [f32] = unsafe { std::slice::from_raw_parts_mut(bufptr, pix_cnt) };
+    Ok(texturegen.fill_analysis(attribute.into(), buffer, pos)?)
+}
+
+/// Grayscale texture of a terrain attribute, same size and placement as `fill_mountainous_terrain_texture_2d`.
+/// attribute: 0 slope, 1 aspect, 2 plan curvature, 3 profile curvature, 4 ambient occlusion
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_analysis_2d(texturegen: *mut MountainousTerrainTextureGen, attribute: u32, bufptr: *mut Color32, pos: *mut Position2D32) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_analysis_2d", || {
+        let pos = *deref_ptr(pos, "pos")?;
+        fill_analysis_texture(texturegen, attribute, bufptr, pos.into())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_analysis_2d_f64(texturegen: *mut MountainousTerrainTextureGen, attribute: u32, bufptr: *mut Color32, pos: *mut Position2D64) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_analysis_2d_f64", || {
+        let pos = *deref_ptr(pos, "pos")?;
+        fill_analysis_texture(texturegen, attribute, bufptr, pos)
+    })
+}
+
+/// Same as `fill_mountainous_terrain_analysis_2d` with one float per pixel, slope and aspect
+/// in degrees, curvatures in 1 / world units and occlusion in [0, 1]
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_analysis_values_2d(texturegen: *mut MountainousTerrainTextureGen, attribute: u32, bufptr: *mut f32, pos: *mut Position2D32) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_analysis_values_2d", || {
+        let pos = *deref_ptr(pos, "pos")?;
+        fill_analysis_values(texturegen, attribute, bufptr, pos.into())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn fill_mountainous_terrain_analysis_values_2d_f64(texturegen: *mut MountainousTerrainTextureGen, attribute: u32, bufptr: *mut f32, pos: *mut Position2D64) -> MeshgenError {
+    ffi_call("fill_mountainous_terrain_analysis_values_2d_f64", || {
+        let pos = *deref_ptr(pos, "pos")?;
+        fill_analysis_values(texturegen, attribute, bufptr, pos)
+    })
+}
+
+/// Chunk streamer handed to the host, it generates with its own copy of a chunkgen.
+pub type MountainousTerrainChunkStreamer = ChunkStreamer<MountainousTerrainChunkGen>;
+
+/// Writes the new streamer to `streamer`, free it with `free_chunk_streamer`.
+/// `view_radius` is in chunks, `cache_capacity` is raised to the number of chunks in view.
+#[no_mangle]
+pub extern "C" fn get_chunk_streamer(chunkgen: *const MountainousTerrainChunkGen, view_radius: u32, cache_capacity: usize, streamer: *mut *mut MountainousTerrainChunkStreamer) -> MeshgenError {
+    info!("getting chunk streamer");
+    ffi_call("get_chunk_streamer", || {
+        let out = deref_mut_ptr(streamer, "streamer")?;
+        let chunkgen = deref_ptr(chunkgen, "chunkgen")?;
+        chunkgen.validate()?;
+        *out = into_handle(ChunkStreamer::build(chunkgen.clone(), view_radius, cache_capacity));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn free_chunk_streamer(streamer: *mut MountainousTerrainChunkStreamer) -> MeshgenError {
+    info!("freeing chunk streamer");
+    ffi_call("free_chunk_streamer", || {
+        free_handle(streamer);
+        Ok(())
+    })
+}
+
+/// Generates with a copy of `chunkgen` from now on, every ready chunk is reported for unloading.
+#[no_mangle]
+pub extern "C" fn set_chunk_streamer_chunkgen(streamer: *mut MountainousTerrainChunkStreamer, chunkgen: *const MountainousTerrainChunkGen) -> MeshgenError {
+    ffi_call("set_chunk_streamer_chunkgen", || {
+        let streamer = deref_mut_ptr(streamer, "streamer")?;
+        let chunkgen = deref_ptr(chunkgen, "chunkgen")?;
+        chunkgen.validate()?;
+        streamer.set_chunkgen(chunkgen.clone());
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn set_chunk_streamer_view_radius(streamer: *mut MountainousTerrainChunkStreamer, view_radius: u32) -> MeshgenError {
+    ffi_call("set_chunk_streamer_view_radius", || {
+        deref_mut_ptr(streamer, "streamer")?.set_view_radius(view_radius);
+        Ok(())
+    })
+}
+
+/// Moves the viewer and collects finished chunks without blocking, call it once per frame.
+#[no_mangle]
+pub extern "C" fn update_chunk_streamer(streamer: *mut MountainousTerrainChunkStreamer, viewer_pos: *const Position3D32) -> MeshgenError {
+    ffi_call("update_chunk_streamer", || {
+        let streamer = deref_mut_ptr(streamer, "streamer")?;
+        let viewer_pos = *deref_ptr(viewer_pos, "viewer_pos")?;
+        update_streamer(streamer, viewer_pos.into())
+    })
+}
+
+/// Same as `update_chunk_streamer` with the viewer in double precision.
+#[no_mangle]
+pub extern "C" fn update_chunk_streamer_f64(streamer: *mut MountainousTerrainChunkStreamer, viewer_pos: *const Position3D64) -> MeshgenError {
+    ffi_call("update_chunk_streamer_f64", || {
+        let streamer = deref_mut_ptr(streamer, "streamer")?;
+        let viewer_pos = *deref_ptr(viewer_pos, "viewer_pos")?;
+        update_streamer(streamer, viewer_pos)
+    })
+}
+
+fn update_streamer(streamer: &mut MountainousTerrainChunkStreamer, viewer_pos: Position3D64) -> Result<(), FfiError> {
+    if !viewer_pos.x.is_finite() || !viewer_pos.z.is_finite() {
+        return Err(FfiError::invalid_parameter(format!("viewer_pos must be finite, got {:?}", viewer_pos)));
+    }
+    streamer.update(viewer_pos);
+    Ok(())
+}
+
+fn write_coords<T: Copy>(coords: Vec<T>, coord_buf: *mut T, count: *mut usize) -> Result<(), FfiError> {
+    let count = deref_mut_ptr(count, "count")?;
+    if !coords.is_empty() {
+        let coord_buffer = unsafe { std::slice::from_raw_parts_mut(coord_buf, coords.len()) };
+        coord_buffer.copy_from_slice(&coords);
+    }
+    *count = coords.len();
+    Ok(())
+}
+
+/// Writes up to `max` chunks that became ready to `coord_buf` and their number to `count`.
+/// Chunks that didn't fit are returned by the next call.
+#[no_mangle]
+pub extern "C" fn take_chunk_streamer_ready(streamer: *mut MountainousTerrainChunkStreamer, coord_buf: *mut ChunkCoord, max: usize, count: *mut usize) -> MeshgenError {
+    ffi_call("take_chunk_streamer_ready", || {
+        let streamer = deref_mut_ptr(streamer, "streamer")?;
+        check_not_null(coord_buf, "coord_buf")?;
+        check_not_null(count, "count")?;
+        write_coords(streamer.take_ready_up_to(max), coord_buf, count)
+    })
+}
+
+/// Writes up to `max` chunks the host should drop to `coord_buf` and their number to `count`.
+#[no_mangle]
+pub extern "C" fn take_chunk_streamer_unload(streamer: *mut MountainousTerrainChunkStreamer, coord_buf: *mut ChunkCoord, max: usize, count: *mut usize) -> MeshgenError {
+    ffi_call("take_chunk_streamer_unload", || {
+        let streamer = deref_mut_ptr(streamer, "streamer")?;
+        check_not_null(coord_buf, "coord_buf")?;
+        check_not_null(count, "count")?;
+        write_coords(streamer.take_unload_up_to(max), coord_buf, count)
+    })
+}
+
+/// Copies a generated chunk into buffers sized from `get_mountainous_terrain_chunk_geometry_desc`
+/// of the streamer's chunkgen, returns `ChunkNotReady` if it is not in the cache.
+#[no_mangle]
+pub extern "C" fn fill_chunk_from_streamer(streamer: *mut MountainousTerrainChunkStreamer, coord: ChunkCoord, vert_buf: *mut SimpleVertex, indx_buf: *mut Quad, tex_buf: *mut Color32) -> MeshgenError {
+    ffi_call("fill_chunk_from_streamer", || {
+        let streamer = deref_mut_ptr(streamer, "streamer")?;
+        check_not_null(vert_buf, "vert_buf")?;
+        check_not_null(indx_buf, "indx_buf")?;
+        check_not_null(tex_buf, "tex_buf")?;
+        let chunk = streamer.get(coord)
+            .ok_or_else(|| FfiError::new(MeshgenError::ChunkNotReady, format!("chunk {},{} is not ready", coord.x, coord.z)))?;
+        let (vert_buffer, indx_buffer, tex_buffer) = unsafe {(
+            std::slice::from_raw_parts_mut(vert_buf, chunk.v_buffer.len()),
+            std::slice::from_raw_parts_mut(indx_buf, chunk.indx_buffer.len()),
+            std::slice::from_raw_parts_mut(tex_buf, chunk.tex_buffer.len()),
+        )};
+        vert_buffer.copy_from_slice(&chunk.v_buffer);
+        indx_buffer.copy_from_slice(&chunk.indx_buffer);
+        tex_buffer.copy_from_slice(&chunk.tex_buffer);
+        Ok(())
+    })
+}
+
+/// Quadtree terrain handed to the host, it generates with its own copy of a chunkgen.
+pub type MountainousTerrainQuadtree = Quadtree<MountainousTerrainChunkGen>;
+
+/// Writes a new quadtree centered on the origin to `tree`, free it with `free_quadtree`.
+/// `resolution` is the number of quads along the side of every node and must be even.
+#[no_mangle]
+pub extern "C" fn get_quadtree(chunkgen: *const MountainousTerrainChunkGen, root_size: f64, max_depth: u32, resolution: u32, tree: *mut *mut MountainousTerrainQuadtree) -> MeshgenError {
+    info!("getting quadtree");
+    ffi_call("get_quadtree", || {
+        let out = deref_mut_ptr(tree, "tree")?;
+        let chunkgen = deref_ptr(chunkgen, "chunkgen")?;
+        chunkgen.validate()?;
+        let params = QuadtreeParams{ resolution: resolution as usize, ..QuadtreeParams::build(root_size, max_depth) };
+        params.validate()?;
+        *out = into_handle(Quadtree::build(chunkgen.clone(), params));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn free_quadtree(tree: *mut MountainousTerrainQuadtree) -> MeshgenError {
+    info!("freeing quadtree");
+    ffi_call("free_quadtree", || {
+        free_handle(tree);
+        Ok(())
+    })
+}
+
+/// Sets the pixel error nodes are split at and the camera it is measured with, `fov_y` in degrees.
+/// Every visible node is reported as removed and the next update starts over.
+#[no_mangle]
+pub extern "C" fn set_quadtree_error(tree: *mut MountainousTerrainQuadtree, error_threshold: f64, fov_y: f64, viewport_height: f64, morph_range: f64) -> MeshgenError {
+    ffi_call("set_quadtree_error", || {
+        let tree = deref_mut_ptr(tree, "tree")?;
+        let params = QuadtreeParams{ error_threshold, fov_y, viewport_height, morph_range, ..*tree.params() };
+        params.validate()?;
+        tree.set_params(params);
+        Ok(())
+    })
+}
+
+/// Selects the nodes for the camera and generates the new ones, call it whenever the camera moved.
+#[no_mangle]
+pub extern "C" fn update_quadtree(tree: *mut MountainousTerrainQuadtree, camera_pos: *const Position3D32) -> MeshgenError {
+    ffi_call("update_quadtree", || {
+        let tree = deref_mut_ptr(tree, "tree")?;
+        let camera_pos = *deref_ptr(camera_pos, "camera_pos")?;
+        update_tree(tree, camera_pos.into())
+    })
+}
+
+/// Same as `update_quadtree` with the camera in double precision.
+#[no_mangle]
+pub extern "C" fn update_quadtree_f64(tree: *mut MountainousTerrainQuadtree, camera_pos: *const Position3D64) -> MeshgenError {
+    ffi_call("update_quadtree_f64", || {
+        let tree = deref_mut_ptr(tree, "tree")?;
+        let camera_pos = *deref_ptr(camera_pos, "camera_pos")?;
+        update_tree(tree, camera_pos)
+    })
+}
+
+fn update_tree(tree: &mut MountainousTerrainQuadtree, camera_pos: Position3D64) -> Result<(), FfiError> {
+    if !camera_pos.x.is_finite() || !camera_pos.y.is_finite() || !camera_pos.z.is_finite() {
+        return Err(FfiError::invalid_parameter(format!("camera_pos must be finite, got {:?}", camera_pos)));
+    }
+    tree.update(camera_pos);
+    Ok(())
+}
+
+/// Writes up to `max` nodes that became visible to `node_buf` and their number to `count`.
+/// Show them before dropping the removed ones so the terrain has no holes.
+#[no_mangle]
+pub extern "C" fn take_quadtree_added(tree: *mut MountainousTerrainQuadtree, node_buf: *mut NodeId, max: usize, count: *mut usize) -> MeshgenError {
+    ffi_call("take_quadtree_added", || {
+        let tree = deref_mut_ptr(tree, "tree")?;
+        check_not_null(node_buf, "node_buf")?;
+        check_not_null(count, "count")?;
+        write_coords(tree.take_added_up_to(max), node_buf, count)
+    })
+}
+
+/// Writes up to `max` nodes the host should drop to `node_buf` and their number to `count`.
+#[no_mangle]
+pub extern "C" fn take_quadtree_removed(tree: *mut MountainousTerrainQuadtree, node_buf: *mut NodeId, max: usize, count: *mut usize) -> MeshgenError {
+    ffi_call("take_quadtree_removed", || {
+        let tree = deref_mut_ptr(tree, "tree")?;
+        check_not_null(node_buf, "node_buf")?;
+        check_not_null(count, "count")?;
+        write_coords(tree.take_removed_up_to(max), node_buf, count)
+    })
+}
+
+/// buffer sizes of every node mesh, `morph_buf` takes one float per vertex
+#[no_mangle]
+pub extern "C" fn get_quadtree_geometry_desc(tree: *mut MountainousTerrainQuadtree, v_count: ptr::NonNull<i32>, e_count: ptr::NonNull<i32>, f_count: ptr::NonNull<i32>) -> MeshgenError {
+    ffi_call("get_quadtree_geometry_desc", || {
+        let tree = deref_ptr(tree, "tree")?;
+        write_geometry_desc(tree.geometry_desc(), v_count, e_count, f_count)
+    })
+}
+
+/// Writes the world position node vertices are relative to and the side length of the node.
+#[no_mangle]
+pub extern "C" fn get_quadtree_node_bounds(tree: *mut MountainousTerrainQuadtree, node: NodeId, center: *mut Position3D64, size: *mut f64) -> MeshgenError {
+    ffi_call("get_quadtree_node_bounds", || {
+        let tree = deref_ptr(tree, "tree")?;
+        let center = deref_mut_ptr(center, "center")?;
+        let size = deref_mut_ptr(size, "size")?;
+        check_node_level(tree, node)?;
+        *center = tree.params().node_center(node);
+        *size = tree.params().node_size(node.level);
+        Ok(())
+    })
+}
+
+/// Rejects nodes deeper than `max_depth`, the tree never selects them and their size could overflow.
+fn check_node_level(tree: &MountainousTerrainQuadtree, node: NodeId) -> Result<(), FfiError> {
+    if node.level > tree.params().max_depth {
+        return Err(FfiError::invalid_parameter(format!("node level must be at most {}, got {}", tree.params().max_depth, node.level)));
+    }
+    Ok(())
+}
+
+fn node_not_visible(node: NodeId) -> FfiError {
+    FfiError::new(MeshgenError::ChunkNotReady, format!("node {} {},{} is not visible", node.level, node.x, node.z))
+}
+
+/// Copies the mesh of a visible node into buffers sized from `get_quadtree_geometry_desc`,
+/// returns `ChunkNotReady` if the last update didn't select it.
+#[no_mangle]
+pub extern "C" fn fill_quadtree_node(tree: *mut MountainousTerrainQuadtree, node: NodeId, vert_buf: *mut Vertex, indx_buf: *mut Quad, tex_buf: *mut Color32, morph_buf: *mut f32) -> MeshgenError {
+    ffi_call("fill_quadtree_node", || {
+        let tree = deref_ptr(tree, "tree")?;
+        check_not_null(vert_buf, "vert_buf")?;
+        check_not_null(indx_buf, "indx_buf")?;
+        check_not_null(tex_buf, "tex_buf")?;
+        check_not_null(morph_buf, "morph_buf")?;
+        check_node_level(tree, node)?;
+        let mesh = tree.get(node).ok_or_else(|| node_not_visible(node))?;
+        let (vert_buffer, indx_buffer, tex_buffer, morph_buffer) = unsafe {(
+            std::slice::from_raw_parts_mut(vert_buf, mesh.v_buffer.len()),
+            std::slice::from_raw_parts_mut(indx_buf, mesh.indx_buffer.len()),
+            std::slice::from_raw_parts_mut(tex_buf, mesh.tex_buffer.len()),
+            std::slice::from_raw_parts_mut(morph_buf, mesh.morph_buffer.len()),
+        )};
+        vert_buffer.copy_from_slice(&mesh.v_buffer);
+        indx_buffer.copy_from_slice(&mesh.indx_buffer);
+        tex_buffer.copy_from_slice(&mesh.tex_buffer);
+        morph_buffer.copy_from_slice(&mesh.morph_buffer);
+        Ok(())
+    })
+}
+
+/// Writes the geomorph weight of a visible node for the last update, 0 draws the node as is
+/// and 1 moves every vertex by its morph offset onto the parent surface.
+#[no_mangle]
+pub extern "C" fn get_quadtree_morph_weight(tree: *mut MountainousTerrainQuadtree, node: NodeId, weight: *mut f32) -> MeshgenError {
+    ffi_call("get_quadtree_morph_weight", || {
+        let tree = deref_ptr(tree, "tree")?;
+        let weight = deref_mut_ptr(weight, "weight")?;
+        check_node_level(tree, node)?;
+        *weight = tree.morph_weight(node).ok_or_else(|| node_not_visible(node))?;
+        Ok(())
+    })
+}
+
+/// level: 0 off, 1 error, 2 warn, 3 info, 4 debug, 5 trace
+fn level_filter(level: u32) -> Result<LevelFilter, FfiError> {
+    check_enum(level, 6, "log level")?;
+    Ok(match level {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    })
+}
+
+fn c_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, FfiError> {
+    check_not_null(ptr, name)?;
+    unsafe { CStr::from_ptr(ptr) }.to_str().map_err(|err| FfiError::invalid_parameter(format!("{} is not valid utf-8: {}", name, err)))
+}
+
+/// Logs to the file at `path`, appending if it exists. Calling it again switches the file or level.
+/// level: 0 off, 1 error, 2 warn, 3 info, 4 debug, 5 trace
+#[no_mangle]
+pub extern "C" fn init_logger(path: *const c_char, level: u32) -> MeshgenError {
+    ffi_call("init_logger", || {
+        let path = c_str(path, "path")?;
+        init_file(path, level_filter(level)?)?;
+        info!("logger initialized, writing to {}", path);
+        Ok(())
+    })
+}
+
+/// Hands log records to `callback`, e.g. to show them in the console of the engine.
+/// Call `shutdown_logger` before the callback becomes invalid, such as on a domain reload in Unity.
+#[no_mangle]
+pub extern "C" fn init_logger_with_callback(callback: Option<LogCallback>, level: u32) -> MeshgenError {
+    ffi_call("init_logger_with_callback", || {
+        let callback = callback.ok_or_else(|| FfiError::null_pointer("callback"))?;
+        init_callback(callback, level_filter(level)?)?;
+        info!("logger initialized with host callback");
+        Ok(())
+    })
+}
+
+/// Overrides the log level for a module such as `meshgen::erosion`, the longest matching module wins.
+#[no_mangle]
+pub extern "C" fn set_logger_module_level(module: *const c_char, level: u32) -> MeshgenError {
+    ffi_call("set_logger_module_level", || {
+        Ok(set_module_level(c_str(module, "module")?, level_filter(level)?)?)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn shutdown_logger() -> MeshgenError {
+    ffi_call("shutdown_logger", || Ok(shutdown()?))
+}
+
+#[cfg(test)]
+mod ffi_tests {
+    use std::{ffi::{CStr, CString}, ptr::{self, NonNull}};
+    use super::*;
+
+    fn last_error() -> Option<String> {
+        let message = meshgen_last_error_message();
+        if message.is_null() {
+            return None;
+        }
+        let copy = unsafe { CStr::from_ptr(message) }.to_str().unwrap().to_string();
+        meshgen_free_error_message(message);
+        Some(copy)
+    }
+
+    #[test]
+    fn test_last_error_follows_calls() {
+        assert_eq!(ffi_call("test", || Err(FfiError::null_pointer("chunkgen"))), MeshgenError::NullPointer);
+        assert_eq!(last_error().unwrap(), "test: pointer to chunkgen is null");
+        // fetching doesn't consume the message
+        assert!(last_error().is_some());
+        assert_eq!(ffi_call("test", || Ok(())), MeshgenError::Ok);
+        assert_eq!(last_error(), None);
+    }
+
+    #[test]
+    fn test_panic_is_reported() {
+        let res = ffi_call("test", || {
+            let empty: Vec<u32> = Vec::new();
+            if empty.is_empty() {
+                panic!("index {} out of range", 3);
+            }
+            Ok(())
+        });
+        assert_eq!(res, MeshgenError::Panic);
+        assert!(last_error().unwrap().contains("index 3 out of range"));
+    }
+
+    #[test]
+    fn test_last_error_is_per_thread() {
+        ffi_call("test", || Err(FfiError::invalid_parameter("scale is zero")));
+        std::thread::spawn(|| assert_eq!(last_error(), None)).join().unwrap();
+        assert!(last_error().is_some());
+    }
+
+    #[test]
+    fn test_chunkgen_from_preset() {
+        let preset = CString::new("(side_len: 12, height: 30.0, noise: (seed: 4, fractal_type: ridged))").unwrap();
+        let mut chunkgen = ptr::null_mut();
+        assert_eq!(get_mountainous_terrain_chunkgen_from_preset(preset.as_ptr(), 1, &mut chunkgen), MeshgenError::Ok);
+        assert!(!chunkgen.is_null());
+        unsafe {
+            assert_eq!((*chunkgen).side_len, 12);
+            assert_eq!((*chunkgen).noise.params().seed, 4);
+        }
+
+        let invalid = CString::new("{\"side_len\": -1}").unwrap();
+        let mut unset = ptr::null_mut();
+        assert_eq!(get_mountainous_terrain_chunkgen_from_preset(invalid.as_ptr(), 0, &mut unset), MeshgenError::InvalidPreset);
+        assert!(unset.is_null());
+        assert_eq!(set_mountainous_terrain_chunkgen_preset(chunkgen, invalid.as_ptr(), 0), MeshgenError::InvalidPreset);
+        unsafe {
+            assert_eq!((*chunkgen).side_len, 12);
+        }
+        // parses fine but would divide by a zero scale
+        let zero_scale = CString::new("{\"noise\": {\"scale\": 0.0}}").unwrap();
+        assert_eq!(set_mountainous_terrain_chunkgen_preset(chunkgen, zero_scale.as_ptr(), 0), MeshgenError::InvalidPreset);
+        let json = CString::new("{\"height\": 5.0}").unwrap();
+        assert_eq!(set_mountainous_terrain_chunkgen_preset(chunkgen, json.as_ptr(), 0), MeshgenError::Ok);
+        unsafe {
+            assert_eq!((*chunkgen).height, 5.0);
+            assert_eq!((*chunkgen).side_len, MountainousTerrainChunkGen::default().side_len);
+        }
+        assert_eq!(set_mountainous_terrain_chunkgen_uv_mapping(chunkgen, 3, 8.0, 4.0), MeshgenError::InvalidParameter);
+        assert_eq!(set_mountainous_terrain_chunkgen_uv_mapping(chunkgen, 2, 0.0, 4.0), MeshgenError::InvalidParameter);
+        assert_eq!(set_mountainous_terrain_chunkgen_uv_mapping(chunkgen, 2, 8.0, 4.0), MeshgenError::Ok);
+        let v_count = unsafe { &*chunkgen }.get_geometry_desc().v_count;
+        let mut weights = vec![TriplanarWeights32::default(); v_count];
+        let mut plane_pos = Position3D64{x: 1e6, y: 0.0, z: -20.0};
+        assert_eq!(fill_mountainous_terrain_chunk_triplanar_weights_f64(chunkgen, weights.as_mut_ptr(), &mut plane_pos), MeshgenError::Ok);
+        assert!(weights.iter().all(|w| (w.x + w.y + w.z - 1.0).abs() < 1e-5));
+        assert_eq!(fill_mountainous_terrain_chunk_triplanar_weights_f64(chunkgen, ptr::null_mut(), &mut plane_pos), MeshgenError::NullPointer);
+        assert_eq!(free_mountainous_terrain_chunkgen(chunkgen), MeshgenError::Ok);
+    }
+
+    #[test]
+    fn test_invalid_parameters_are_rejected() {
+        let mut chunkgen = ptr::null_mut();
+        assert_eq!(get_mountainous_terrain_chunkgen(0, 50.0, &mut chunkgen), MeshgenError::InvalidParameter);
+        assert!(chunkgen.is_null());
+        assert_eq!(get_mountainous_terrain_chunkgen(8, 50.0, &mut chunkgen), MeshgenError::Ok);
+        let seed = unsafe { (*chunkgen).noise.params().seed };
+
+        let mut from = Position2D32{x: 0.4, y: 0.0};
+        let mut to = Position2D32{x: 0.5, y: 0.1};
+        assert_eq!(set_mountainous_terrain_chunkgen_noise(chunkgen, 9, 3, 0.0, 0.5, 2.0, 0.0, 0.3, &mut from, &mut to, 0.5, 0), MeshgenError::InvalidParameter);
+        assert!(last_error().unwrap().contains("scale"));
+        // a bezier point on x = 0 gives an infinite slope
+        let mut vertical = Position2D32{x: 0.0, y: 0.2};
+        assert_eq!(set_mountainous_terrain_chunkgen_noise(chunkgen, 9, 3, 20.0, 0.5, 2.0, 0.0, 0.3, &mut vertical, &mut to, 0.5, 0), MeshgenError::InvalidParameter);
+        assert_eq!(set_mountainous_terrain_chunkgen_noise(chunkgen, 9, 3, 20.0, 0.5, 2.0, 0.0, 0.3, &mut from, &mut to, 0.5, 7), MeshgenError::InvalidParameter);
+        assert_eq!(set_mountainous_terrain_chunkgen_noise(chunkgen, 9, 3, 20.0, 0.5, 2.0, 0.0, 0.3, ptr::null_mut(), &mut to, 0.5, 0), MeshgenError::NullPointer);
+        assert!(last_error().unwrap().contains("bezier_bias_from"));
+        // failed calls leave the noise as it was
+        assert_eq!(unsafe { (*chunkgen).noise.params().seed }, seed);
+        assert_eq!(set_mountainous_terrain_chunkgen_noise(chunkgen, 9, 3, 20.0, 0.5, 2.0, 0.0, 0.3, &mut from, &mut to, 0.5, 0), MeshgenError::Ok);
+        assert_eq!(unsafe { (*chunkgen).noise.params().seed }, 9);
+        assert!(meshgen_last_error_message().is_null());
+
+        let unsorted = [ColorKey{color: Color32::default(), t: 0.8}, ColorKey{color: Color32::default(), t: 0.2}];
+        assert_eq!(set_mountainous_terrain_chunkgen_color_gradient(chunkgen, unsorted.as_ptr() as *mut ColorKey, 2, true), MeshgenError::InvalidParameter);
+        assert_eq!(set_mountainous_terrain_chunkgen_color_gradient(chunkgen, ptr::null_mut(), 2, true), MeshgenError::NullPointer);
+        assert_eq!(set_mountainous_terrain_chunkgen_dim(chunkgen, 8, f64::NAN), MeshgenError::InvalidParameter);
+        assert_eq!(set_mountainous_terrain_chunkgen_thermal_erosion(chunkgen, true, 3, 95.0, 0.5), MeshgenError::InvalidParameter);
+        assert_eq!(set_mountainous_terrain_chunkgen_base_noise(chunkgen, 9, 0, 0), MeshgenError::InvalidParameter);
+        assert_eq!(set_mountainous_terrain_chunkgen_dim(ptr::null_mut(), 8, 50.0), MeshgenError::NullPointer);
+        assert_eq!(fill_mountainous_terrain_chunk(chunkgen, ptr::null_mut(), ptr::null_mut(), ptr::null_mut(), ptr::null_mut()), MeshgenError::NullPointer);
+
+        let (mut v_count, mut e_count, mut f_count) = (0, 0, 0);
+        assert_eq!(set_mountainous_terrain_chunkgen_dim(chunkgen, 1 << 20, 50.0), MeshgenError::Ok);
+        assert_eq!(get_mountainous_terrain_chunk_geometry_desc(chunkgen, NonNull::from(&mut v_count), NonNull::from(&mut e_count), NonNull::from(&mut f_count)), MeshgenError::BufferTooLarge);
+        assert_eq!(v_count, 0);
+        free_mountainous_terrain_chunkgen(chunkgen);
+    }
+
+    #[test]
+    fn test_texturegen_handles() {
+        let mut texturegen = ptr::null_mut();
+        assert_eq!(get_mountainous_terrain_texturegen(0, 4, &mut texturegen), MeshgenError::InvalidParameter);
+        assert_eq!(get_mountainous_terrain_texturegen(4, 4, &mut texturegen), MeshgenError::Ok);
+        let mut pixels = vec![Color32::default(); 16];
+        let mut pos = Position2D32{x: 0.0, y: 0.0};
+        assert_eq!(fill_mountainous_terrain_texture_2d(texturegen, pixels.as_mut_ptr(), &mut pos), MeshgenError::Ok);
+        assert!(pixels.iter().all(|pixel| pixel.a == 255));
+        assert_eq!(set_mountainous_terrain_texturegen_dim(texturegen, 4, 0), MeshgenError::InvalidParameter);
+        assert_eq!(set_mountainous_terrain_texturegen_normal_map(texturegen, 80.0, 2, 0), MeshgenError::InvalidParameter);
+        assert_eq!(set_mountainous_terrain_texturegen_normal_map(texturegen, 80.0, 1, 1), MeshgenError::Ok);
+        assert_eq!(fill_mountainous_terrain_normal_map_2d(texturegen, pixels.as_mut_ptr(), &mut pos), MeshgenError::Ok);
+        assert_eq!(pixels, unsafe { &*texturegen }.generate_normal_map(pos.into()));
+        assert_eq!(fill_mountainous_terrain_normal_map_2d(texturegen, ptr::null_mut(), &mut pos), MeshgenError::NullPointer);
+        assert_eq!(set_mountainous_terrain_texturegen_analysis(texturegen, 80.0, 4, 0, 10.0), MeshgenError::InvalidParameter);
+        assert_eq!(set_mountainous_terrain_texturegen_analysis(texturegen, 80.0, 4, 8, 10.0), MeshgenError::Ok);
+        assert_eq!(fill_mountainous_terrain_analysis_2d(texturegen, 5, pixels.as_mut_ptr(), &mut pos), MeshgenError::InvalidParameter);
+        assert_eq!(fill_mountainous_terrain_analysis_2d(texturegen, 4, pixels.as_mut_ptr(), &mut pos), MeshgenError::Ok);
+        let mut values = vec![0.0_f32; 16];
+        let mut far = Position2D64{x: 1e7, y: 0.0};
+        assert_eq!(fill_mountainous_terrain_analysis_values_2d_f64(texturegen, 0, values.as_mut_ptr(), &mut far), MeshgenError::Ok);
+        assert!(values.iter().all(|slope| *slope >= 0.0 && *slope < 90.0));
+        let mut heights = vec![0_u16; 16];
+        assert_eq!(fill_mountainous_terrain_heights_r16_2d(texturegen, heights.as_mut_ptr(), &mut pos), MeshgenError::Ok);
+        assert_eq!(fill_mountainous_terrain_heights_r16_2d(texturegen, ptr::null_mut(), &mut pos), MeshgenError::NullPointer);
+        let mut floats = vec![0.0_f32; 16];
+        assert_eq!(fill_mountainous_terrain_heights_f32_2d_f64(texturegen, floats.as_mut_ptr(), &mut far), MeshgenError::Ok);
+        let mut halves = vec![f16::from_f32(0.0); 16];
+        assert_eq!(fill_mountainous_terrain_heights_f16_2d(texturegen, halves.as_mut_ptr(), &mut pos), MeshgenError::Ok);
+        let mut expected = vec![0_u16; 16];
+        unsafe { &*texturegen }.fill_heights_r16(&mut expected, pos.into()).unwrap();
+        assert_eq!(heights, expected);
+        assert_eq!(free_mountainous_terrain_texturegen(texturegen), MeshgenError::Ok);
+        assert_eq!(free_mountainous_terrain_texturegen(ptr::null_mut()), MeshgenError::Ok);
+    }
+
+    #[test]
+    fn test_biome_handles() {
+        let preset = CString::new(r#"{"side_len": 4, "height": 20.0, "biome_map": {"blend_width": 0.2, "biomes": [{"name": "low", "height_scale": 0.2}, {"name": "high", "temperature": 0.0}]}}"#).unwrap();
+        let mut chunkgen = ptr::null_mut();
+        assert_eq!(get_biome_chunkgen_from_preset(preset.as_ptr(), 0, &mut chunkgen), MeshgenError::Ok);
+        let (mut v_count, mut e_count, mut f_count) = (0, 0, 0);
+        assert_eq!(get_biome_chunk_geometry_desc(chunkgen, NonNull::from(&mut v_count), NonNull::from(&mut e_count), NonNull::from(&mut f_count)), MeshgenError::Ok);
+        assert_eq!(v_count, 25);
+        let mut v_buffer = vec![SimpleVertex::default(); 25];
+        let mut indx_buffer = vec![Quad::default(); 16];
+        let mut tex_buffer = vec![Color32::default(); 25];
+        let mut biome_buffer = vec![BiomeBlend::default(); 25];
+        let mut plane_pos = Position3D64{x: 1e7, y: 0.0, z: 0.0};
+        assert_eq!(fill_biome_chunk(chunkgen, v_buffer.as_mut_ptr(), indx_buffer.as_mut_ptr(), tex_buffer.as_mut_ptr(), biome_buffer.as_mut_ptr(), &mut plane_pos), MeshgenError::Ok);
+        assert!(biome_buffer.iter().all(|blend| (blend.weights.iter().sum::<f32>() - 1.0).abs() < 1e-5));
+        assert_eq!(fill_biome_chunk(chunkgen, v_buffer.as_mut_ptr(), indx_buffer.as_mut_ptr(), tex_buffer.as_mut_ptr(), ptr::null_mut(), &mut plane_pos), MeshgenError::Ok);
+        assert_eq!(free_biome_chunkgen(chunkgen), MeshgenError::Ok);
+
+        let empty = CString::new(r#"{"biome_map": {"biomes": []}}"#).unwrap();
+        let mut texturegen = ptr::null_mut();
+        assert_eq!(get_biome_texturegen_from_preset(empty.as_ptr(), 0, &mut texturegen), MeshgenError::InvalidPreset);
+        assert!(texturegen.is_null());
+        let preset = CString::new(r#"{"width": 4, "height": 2}"#).unwrap();
+        assert_eq!(get_biome_texturegen_from_preset(preset.as_ptr(), 0, &mut texturegen), MeshgenError::Ok);
+        let mut pixels = vec![Color32::default(); 8];
+        let mut blends = vec![BiomeBlend::default(); 8];
+        let mut pos = Position2D64{x: 0.0, y: 0.0};
+        assert_eq!(fill_biome_texture(texturegen, pixels.as_mut_ptr(), blends.as_mut_ptr(), &mut pos), MeshgenError::Ok);
+        assert!(pixels.iter().all(|pixel| pixel.a == 255));
+        assert_eq!(blends[0], unsafe { &*texturegen }.biome_map.blend_at(Position2D64{x: -2.0, y: -1.0}));
+        assert_eq!(free_biome_texturegen(texturegen), MeshgenError::Ok);
+    }
+
+    #[test]
+    fn test_vertex_layout_handles() {
+        use crate::layout::{VertexAttribute, VertexFormat};
+        let descs = [
+            VertexAttributeDesc::build(VertexAttribute::Position, VertexFormat::Float32, 3, 0),
+            VertexAttributeDesc::build(VertexAttribute::Normal, VertexFormat::SNorm8, 4, 0),
+            VertexAttributeDesc::build(VertexAttribute::Uv1, VertexFormat::Float16, 4, 2),
+        ];
+        let mut layout = ptr::null_mut();
+        assert_eq!(get_vertex_layout(descs.as_ptr(), 3, &mut layout), MeshgenError::Ok);
+        let mut strides = [0_u32; 4];
+        for (stream, stride) in strides.iter_mut().enumerate() {
+            assert_eq!(get_vertex_layout_stride(layout, stream as u32, stride), MeshgenError::Ok);
+        }
+        assert_eq!(strides, [16, 0, 8, 0]);
+
+        let mut chunkgen = ptr::null_mut();
+        assert_eq!(get_mountainous_terrain_chunkgen(4, 20.0, &mut chunkgen), MeshgenError::Ok);
+        let (mut first, mut third) = (vec![0_u8; 25 * 16], vec![0_u8; 25 * 8]);
+        let mut streams = [first.as_mut_ptr(), ptr::null_mut(), third.as_mut_ptr()];
+        let mut indx_buffer = vec![Quad::default(); 16];
+        let mut plane_pos = Position3D32{x: 8.0, y: 0.0, z: 0.0};
+        assert_eq!(fill_mountainous_terrain_chunk_with_layout(chunkgen, layout, streams.as_mut_ptr(), indx_buffer.as_mut_ptr(), &mut plane_pos), MeshgenError::Ok);
+        // the vertex normals point up
+        assert!(first.chunks(16).all(|vert| (vert[13] as i8) > 0));
+        assert!(third.iter().any(|byte| *byte != 0));
+        streams[2] = ptr::null_mut();
+        assert_eq!(fill_mountainous_terrain_chunk_with_layout(chunkgen, layout, streams.as_mut_ptr(), indx_buffer.as_mut_ptr(), &mut plane_pos), MeshgenError::NullPointer);
+        assert_eq!(free_mountainous_terrain_chunkgen(chunkgen), MeshgenError::Ok);
+        assert_eq!(free_vertex_layout(layout), MeshgenError::Ok);
+
+        let twice = [descs[0], descs[0]];
+        let mut unset = ptr::null_mut();
+        assert_eq!(get_vertex_layout(twice.as_ptr(), 2, &mut unset), MeshgenError::InvalidParameter);
+        assert!(unset.is_null());
+        assert_eq!(get_vertex_layout(ptr::null(), 2, &mut unset), MeshgenError::NullPointer);
+    }
+
+    // only calls that fail before touching the process wide logger, `logger_tests` installs it
+    #[test]
+    fn test_logger_arguments() {
+        let dir = std::env::temp_dir().join(format!("meshgen_ffi_logger_test_{}", std::process::id()));
+        let path = CString::new(dir.join("meshgen.log").to_str().unwrap()).unwrap();
+        assert_eq!(init_logger(ptr::null(), 3), MeshgenError::NullPointer);
+        assert_eq!(init_logger(path.as_ptr(), 9), MeshgenError::InvalidParameter);
+        // the directory is never created
+        assert_eq!(init_logger(path.as_ptr(), 3), MeshgenError::Logger);
+        assert_eq!(init_logger_with_callback(None, 3), MeshgenError::NullPointer);
+        let module = CString::new("meshgen::erosion").unwrap();
+        assert_eq!(set_logger_module_level(module.as_ptr(), 6), MeshgenError::InvalidParameter);
+        assert_eq!(set_logger_module_level(ptr::null(), 3), MeshgenError::NullPointer);
+    }
+
+    #[test]
+    fn test_chunk_streamer() {
+        let mut chunkgen = ptr::null_mut();
+        assert_eq!(get_mountainous_terrain_chunkgen(4, 10.0, &mut chunkgen), MeshgenError::Ok);
+        let mut streamer = ptr::null_mut();
+        assert_eq!(get_chunk_streamer(chunkgen, 1, 16, &mut streamer), MeshgenError::Ok);
+        let viewer = Position3D32{x: 0.0, y: 0.0, z: 0.0};
+        assert_eq!(update_chunk_streamer(streamer, &viewer), MeshgenError::Ok);
+        unsafe { (*streamer).wait() };
+
+        let mut coords = [ChunkCoord::default(); 4];
+        let mut count = 0;
+        assert_eq!(take_chunk_streamer_ready(streamer, coords.as_mut_ptr(), coords.len(), &mut count), MeshgenError::Ok);
+        assert_eq!(count, 4);
+        assert_eq!(coords[0], ChunkCoord{x: 0, z: 0});
+        // the fifth chunk waits for the next call
+        assert_eq!(take_chunk_streamer_ready(streamer, coords.as_mut_ptr(), coords.len(), &mut count), MeshgenError::Ok);
+        assert_eq!(count, 1);
+        assert_eq!(take_chunk_streamer_unload(streamer, coords.as_mut_ptr(), coords.len(), &mut count), MeshgenError::Ok);
+        assert_eq!(count, 0);
+
+        let desc = unsafe { (*chunkgen).get_geometry_desc() };
+        let mut v_buffer = vec![SimpleVertex::default(); desc.v_count];
+        let mut indx_buffer = vec![Quad::default(); desc.quad_count()];
+        let mut tex_buffer = vec![Color32::default(); desc.v_count];
+        let fill = |coord, v_buffer: &mut Vec<SimpleVertex>, indx_buffer: &mut Vec<Quad>, tex_buffer: &mut Vec<Color32>| {
+            fill_chunk_from_streamer(streamer, coord, v_buffer.as_mut_ptr(), indx_buffer.as_mut_ptr(), tex_buffer.as_mut_ptr())
+        };
+        assert_eq!(fill(ChunkCoord{x: 1, z: 0}, &mut v_buffer, &mut indx_buffer, &mut tex_buffer), MeshgenError::Ok);
+        let mut expected = vec![SimpleVertex::default(); desc.v_count];
+        let (mut expected_indx, mut expected_tex) = (indx_buffer.clone(), tex_buffer.clone());
+        let mut plane_pos = Position3D32{x: 4.0, y: 0.0, z: 0.0};
+        assert_eq!(fill_mountainous_terrain_chunk(chunkgen, expected.as_mut_ptr(), expected_indx.as_mut_ptr(), expected_tex.as_mut_ptr(), &mut plane_pos), MeshgenError::Ok);
+        assert_eq!(v_buffer, expected);
+        assert_eq!(fill(ChunkCoord{x: 5, z: 5}, &mut v_buffer, &mut indx_buffer, &mut tex_buffer), MeshgenError::ChunkNotReady);
+        assert!(last_error().unwrap().contains("5,5"));
+
+        assert_eq!(update_chunk_streamer(streamer, ptr::null()), MeshgenError::NullPointer);
+        let nan = Position3D32{x: f32::NAN, y: 0.0, z: 0.0};
+        assert_eq!(update_chunk_streamer(streamer, &nan), MeshgenError::InvalidParameter);
+        assert_eq!(get_chunk_streamer(ptr::null(), 1, 16, &mut streamer), MeshgenError::NullPointer);
+        assert_eq!(free_chunk_streamer(streamer), MeshgenError::Ok);
+        assert_eq!(free_mountainous_terrain_chunkgen(chunkgen), MeshgenError::Ok);
+    }
+
+    #[test]
+    fn test_quadtree() {
+        let mut chunkgen = ptr::null_mut();
+        assert_eq!(get_mountainous_terrain_chunkgen(4, 10.0, &mut chunkgen), MeshgenError::Ok);
+        let mut tree = ptr::null_mut();
+        assert_eq!(get_quadtree(chunkgen, 256.0, 3, 7, &mut tree), MeshgenError::InvalidParameter);
+        assert_eq!(get_quadtree(chunkgen, 256.0, 3, 4, &mut tree), MeshgenError::Ok);
+        assert_eq!(set_quadtree_error(tree, 0.0, 60.0, 1080.0, 0.5), MeshgenError::InvalidParameter);
+        assert_eq!(set_quadtree_error(tree, 1.0, 60.0, 1080.0, 0.5), MeshgenError::Ok);
+        let camera = Position3D32{x: 0.0, y: 20.0, z: 0.0};
+        assert_eq!(update_quadtree(tree, &camera), MeshgenError::Ok);
+
+        let mut nodes = [NodeId::default(); 64];
+        let mut count = 0;
+        assert_eq!(take_quadtree_added(tree, nodes.as_mut_ptr(), 2, &mut count), MeshgenError::Ok);
+        assert_eq!(count, 2);
+        assert_eq!(take_quadtree_added(tree, nodes[2..].as_mut_ptr(), 62, &mut count), MeshgenError::Ok);
+        let visible = unsafe { (*tree).visible_nodes() };
+        assert_eq!(count + 2, visible.len());
+        assert_eq!(take_quadtree_removed(tree, nodes.as_mut_ptr(), 64, &mut count), MeshgenError::Ok);
+        assert_eq!(count, 0);
+
+        let (mut v_count, mut e_count, mut f_count) = (0, 0, 0);
+        assert_eq!(get_quadtree_geometry_desc(tree, NonNull::from(&mut v_count), NonNull::from(&mut e_count), NonNull::from(&mut f_count)), MeshgenError::Ok);
+        assert_eq!(v_count, 25);
+        let mut v_buffer = vec![Vertex::default(); v_count as usize];
+        let mut indx_buffer = vec![Quad::default(); f_count as usize / 2];
+        let mut tex_buffer = vec![Color32::default(); v_count as usize];
+        let mut morph_buffer = vec![0.0; v_count as usize];
+        let mut fill = |node| fill_quadtree_node(tree, node, v_buffer.as_mut_ptr(), indx_buffer.as_mut_ptr(), tex_buffer.as_mut_ptr(), morph_buffer.as_mut_ptr());
+        assert_eq!(fill(visible[0]), MeshgenError::Ok);
+        assert_eq!(fill(NodeId::root()), MeshgenError::ChunkNotReady);
+        assert!(last_error().unwrap().contains("node 0 0,0"));
+        assert_eq!(v_buffer, unsafe { (*tree).get(visible[0]).unwrap().v_buffer.clone() });
+
+        let (mut center, mut size) = (Position3D64::default(), 0.0);
+        assert_eq!(get_quadtree_node_bounds(tree, NodeId{level: 1, x: 1, z: 0}, &mut center, &mut size), MeshgenError::Ok);
+        assert_eq!((center, size), (Position3D64{x: 64.0, y: 0.0, z: -64.0}, 128.0));
+        let mut weight = -1.0;
+        assert_eq!(get_quadtree_morph_weight(tree, visible[0], &mut weight), MeshgenError::Ok);
+        assert!((0.0..=1.0).contains(&weight));
+        // levels past max_depth would overflow the node size
+        let deep = NodeId{level: 64, x: 0, z: 0};
+        assert_eq!(get_quadtree_node_bounds(tree, deep, &mut center, &mut size), MeshgenError::InvalidParameter);
+        assert_eq!(fill_quadtree_node(tree, deep, v_buffer.as_mut_ptr(), indx_buffer.as_mut_ptr(), tex_buffer.as_mut_ptr(), morph_buffer.as_mut_ptr()), MeshgenError::InvalidParameter);
+        assert_eq!(get_quadtree_morph_weight(tree, NodeId{level: 4, ..deep}, &mut weight), MeshgenError::InvalidParameter);
+        assert!(last_error().unwrap().contains("at most 3"));
+
+        let nan = Position3D64{x: 0.0, y: f64::NAN, z: 0.0};
+        assert_eq!(update_quadtree_f64(tree, &nan), MeshgenError::InvalidParameter);
+        assert_eq!(update_quadtree(tree, ptr::null()), MeshgenError::NullPointer);
+        assert_eq!(free_quadtree(tree), MeshgenError::Ok);
+        assert_eq!(free_mountainous_terrain_chunkgen(chunkgen), MeshgenError::Ok);
+    }
+}
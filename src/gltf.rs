@@ -0,0 +1,332 @@
+use std::{fs::File, io::{self, BufWriter, Write}, path::Path};
+use serde_json::{json, Value};
+
+use crate::{chunkgen::{ChunkBuffers, ChunkGen2D}, texturegen::write_png, unity::{Color32, Faces, Position3D64, VertexAttributes}};
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const COMPONENT_FLOAT: u32 = 5126;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FILTER_LINEAR: u32 = 9729;
+const WRAP_CLAMP_TO_EDGE: u32 = 33071;
+
+/// One chunk converted to glTF's right handed, counter clockwise convention.
+#[derive(Clone, Debug, Default)]
+struct GltfChunk {
+    translation: [f32; 3],
+    positions: Vec<[f32; 3]>,
+    normals: Option<Vec<[f32; 3]>>,
+    tangents: Option<Vec<[f32; 4]>>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+    texture: Option<(usize, usize, Vec<Color32>)>,
+}
+
+/// A glTF 2.0 scene with one node per chunk, written as `.gltf` + `.bin` or as a single `.glb`.
+///
+/// Unity is left handed with clockwise front faces, glTF is right handed with counter clockwise
+/// front faces: z is negated on positions, normals and tangents, tangent handedness is flipped
+/// and the winding of every triangle is reversed.
+#[derive(Clone, Debug, Default)]
+pub struct GltfScene {
+    chunks: Vec<GltfChunk>,
+}
+
+impl GltfScene {
+    pub fn build() -> Self {
+        Self::default()
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Adds the buffers of one `fill_chunk_2d` call as a node placed at `plane_pos`.
+    /// The first `tex_width * tex_height` colors of `tex_buffer` are used as the base color texture,
+    /// pass an empty buffer to export the mesh without material.
+    /// The vertex heights already contain `plane_pos.y`, so the node is only moved in x and z.
+    pub fn add_chunk<V: VertexAttributes, F: Faces>(&mut self, v_buffer: &[V], indx_buffer: &[F], tex_buffer: &[Color32], tex_width: usize, tex_height: usize, plane_pos: Position3D64) {
+        let flip = |x: f32, y: f32, z: f32| [x, y, -z];
+        let positions = v_buffer.iter().map(|v| {
+            let p = v.position();
+            flip(p.x, p.y, p.z)
+        }).collect();
+        let normals = v_buffer.iter().map(|v| v.normal().map(|n| flip(n.x, n.y, n.z))).collect();
+        let tangents = v_buffer.iter().map(|v| v.tangent().map(|t| [t.x, t.y, -t.z, -t.w])).collect();
+        let uvs = v_buffer.iter().map(|v| {
+            let uv = v.uv();
+            [uv.u, uv.v]
+        }).collect();
+        let mut indices = Vec::with_capacity(indx_buffer.len() * F::TRIANGLES * 3);
+        for face in indx_buffer.iter() {
+            for k in 0..F::TRIANGLES {
+                let tri = face.triangle(k);
+                indices.extend_from_slice(&[tri.v1 as u32, tri.v3 as u32, tri.v2 as u32]);
+            }
+        }
+        let texture = match tex_width * tex_height {
+            0 => None,
+            n if n <= tex_buffer.len() => Some((tex_width, tex_height, tex_buffer[..n].to_vec())),
+            _ => None,
+        };
+        self.chunks.push(GltfChunk {
+            translation: flip(plane_pos.x as f32, 0.0, plane_pos.z as f32),
+            positions,
+            normals,
+            tangents,
+            uvs,
+            indices,
+            texture,
+        });
+    }
+
+    /// Generates the chunk at `plane_pos` with `chunkgen` and adds it to the scene.
+    pub fn add_chunkgen<C: ChunkGen2D + Sync>(&mut self, chunkgen: &C, plane_pos: Position3D64) {
+        let chunk = ChunkBuffers::fill(chunkgen, plane_pos);
+        let tex_side = chunk.tex_side();
+        self.add_chunk(&chunk.v_buffer, &chunk.indx_buffer, &chunk.tex_buffer, tex_side, tex_side, plane_pos);
+    }
+
+    /// Writes the json document to `json_out` and the binary buffer it references as `bin_uri` to `bin_out`.
+    pub fn write_gltf<J: Write, B: Write>(&self, mut json_out: J, mut bin_out: B, bin_uri: &str) -> io::Result<()> {
+        let (json, bin) = self.encode(Some(bin_uri))?;
+        serde_json::to_writer_pretty(&mut json_out, &json)?;
+        bin_out.write_all(&bin)
+    }
+
+    pub fn write_glb<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let (json, mut bin) = self.encode(None)?;
+        let mut json = serde_json::to_vec(&json)?;
+        pad(&mut json, b' ');
+        pad(&mut bin, 0);
+        let total_len = 12 + 8 + json.len() + if bin.is_empty() { 0 } else { 8 + bin.len() };
+
+        out.write_all(&GLB_MAGIC.to_le_bytes())?;
+        out.write_all(&2u32.to_le_bytes())?;
+        out.write_all(&(total_len as u32).to_le_bytes())?;
+        out.write_all(&(json.len() as u32).to_le_bytes())?;
+        out.write_all(&GLB_CHUNK_JSON.to_le_bytes())?;
+        out.write_all(&json)?;
+        if !bin.is_empty() {
+            out.write_all(&(bin.len() as u32).to_le_bytes())?;
+            out.write_all(&GLB_CHUNK_BIN.to_le_bytes())?;
+            out.write_all(&bin)?;
+        }
+        Ok(())
+    }
+
+    /// Saves `path` and a `.bin` file with the same stem next to it.
+    pub fn save_gltf<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let bin_path = path.with_extension("bin");
+        let bin_uri = bin_path.file_name().and_then(|name| name.to_str()).unwrap_or("scene.bin");
+        self.write_gltf(BufWriter::new(File::create(path)?), BufWriter::new(File::create(&bin_path)?), bin_uri)
+    }
+
+    pub fn save_glb<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_glb(BufWriter::new(File::create(path)?))
+    }
+
+    /// Builds the json document and the single binary buffer holding every chunk.
+    fn encode(&self, bin_uri: Option<&str>) -> io::Result<(Value, Vec<u8>)> {
+        let mut bin = Vec::new();
+        let mut buffer_views = Vec::new();
+        let mut accessors = Vec::new();
+        let mut nodes = Vec::new();
+        let mut meshes = Vec::new();
+        let mut materials = Vec::new();
+        let mut textures = Vec::new();
+        let mut images = Vec::new();
+
+        let mut push_view = |bin: &mut Vec<u8>, bytes: &[u8], target: Option<u32>| -> usize {
+            pad(bin, 0);
+            let mut view = json!({"buffer": 0, "byteOffset": bin.len(), "byteLength": bytes.len()});
+            if let Some(target) = target {
+                view["target"] = json!(target);
+            }
+            bin.extend_from_slice(bytes);
+            buffer_views.push(view);
+            buffer_views.len() - 1
+        };
+
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            let mut attributes = json!({});
+
+            let view = push_view(&mut bin, &f32_bytes(chunk.positions.iter().flatten()), Some(TARGET_ARRAY_BUFFER));
+            let (min, max) = bounds(&chunk.positions);
+            accessors.push(json!({"bufferView": view, "componentType": COMPONENT_FLOAT, "count": chunk.positions.len(), "type": "VEC3", "min": min, "max": max}));
+            attributes["POSITION"] = json!(accessors.len() - 1);
+
+            if let Some(normals) = &chunk.normals {
+                let view = push_view(&mut bin, &f32_bytes(normals.iter().flatten()), Some(TARGET_ARRAY_BUFFER));
+                accessors.push(json!({"bufferView": view, "componentType": COMPONENT_FLOAT, "count": normals.len(), "type": "VEC3"}));
+                attributes["NORMAL"] = json!(accessors.len() - 1);
+            }
+            if let Some(tangents) = &chunk.tangents {
+                let view = push_view(&mut bin, &f32_bytes(tangents.iter().flatten()), Some(TARGET_ARRAY_BUFFER));
+                accessors.push(json!({"bufferView": view, "componentType": COMPONENT_FLOAT, "count": tangents.len(), "type": "VEC4"}));
+                attributes["TANGENT"] = json!(accessors.len() - 1);
+            }
+
+            let view = push_view(&mut bin, &f32_bytes(chunk.uvs.iter().flatten()), Some(TARGET_ARRAY_BUFFER));
+            accessors.push(json!({"bufferView": view, "componentType": COMPONENT_FLOAT, "count": chunk.uvs.len(), "type": "VEC2"}));
+            attributes["TEXCOORD_0"] = json!(accessors.len() - 1);
+
+            let index_bytes: Vec<u8> = chunk.indices.iter().flat_map(|i| i.to_le_bytes().to_vec()).collect();
+            let view = push_view(&mut bin, &index_bytes, Some(TARGET_ELEMENT_ARRAY_BUFFER));
+            accessors.push(json!({"bufferView": view, "componentType": COMPONENT_UNSIGNED_INT, "count": chunk.indices.len(), "type": "SCALAR"}));
+            let mut primitive = json!({"attributes": attributes, "indices": accessors.len() - 1, "mode": 4});
+
+            if let Some((width, height, pixels)) = &chunk.texture {
+                let mut png_bytes = Vec::new();
+                write_png(&mut png_bytes, *width, *height, pixels)?;
+                let view = push_view(&mut bin, &png_bytes, None);
+                images.push(json!({"bufferView": view, "mimeType": "image/png"}));
+                textures.push(json!({"sampler": 0, "source": images.len() - 1}));
+                materials.push(json!({
+                    "name": format!("chunk_{}", i),
+                    "pbrMetallicRoughness": {
+                        "baseColorTexture": {"index": textures.len() - 1},
+                        "metallicFactor": 0.0,
+                        "roughnessFactor": 1.0,
+                    },
+                }));
+                primitive["material"] = json!(materials.len() - 1);
+            }
+
+            meshes.push(json!({"name": format!("chunk_{}", i), "primitives": [primitive]}));
+            nodes.push(json!({"name": format!("chunk_{}", i), "mesh": i, "translation": chunk.translation}));
+        }
+        pad(&mut bin, 0);
+
+        let mut buffer = json!({"byteLength": bin.len()});
+        if let Some(uri) = bin_uri {
+            buffer["uri"] = json!(uri);
+        }
+        let mut root = json!({
+            "asset": {"version": "2.0", "generator": "meshgen"},
+            "scene": 0,
+            "scenes": [{"nodes": (0..nodes.len()).collect::<Vec<_>>()}],
+            "nodes": nodes,
+            "meshes": meshes,
+            "accessors": accessors,
+            "bufferViews": buffer_views,
+        });
+        if !bin.is_empty() {
+            root["buffers"] = json!([buffer]);
+        }
+        if !materials.is_empty() {
+            root["materials"] = json!(materials);
+            root["textures"] = json!(textures);
+            root["images"] = json!(images);
+            root["samplers"] = json!([{"magFilter": FILTER_LINEAR, "minFilter": FILTER_LINEAR, "wrapS": WRAP_CLAMP_TO_EDGE, "wrapT": WRAP_CLAMP_TO_EDGE}]);
+        }
+        Ok((root, bin))
+    }
+}
+
+fn pad(bytes: &mut Vec<u8>, with: u8) {
+    while !bytes.len().is_multiple_of(4) {
+        bytes.push(with);
+    }
+}
+
+fn f32_bytes<'a, I: Iterator<Item = &'a f32>>(values: I) -> Vec<u8> {
+    values.flat_map(|v| v.to_le_bytes().to_vec()).collect()
+}
+
+fn bounds(positions: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
+    if positions.is_empty() {
+        return ([0.0; 3], [0.0; 3]);
+    }
+    positions.iter().fold(([f32::MAX; 3], [f32::MIN; 3]), |(mut min, mut max), p| {
+        for k in 0..3 {
+            min[k] = min[k].min(p[k]);
+            max[k] = max[k].max(p[k]);
+        }
+        (min, max)
+    })
+}
+
+#[cfg(test)]
+mod gltf_tests {
+    use serde_json::Value;
+    use crate::{chunkgen::{ChunkGen2D, MountainousTerrainChunkGen}, unity::{Color32, Position3D64, Quad, Vertex}};
+    use super::GltfScene;
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+    }
+
+    fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_bits(read_u32(bytes, offset))
+    }
+
+    #[test]
+    fn test_glb_layout_and_nodes() {
+        let chunkgen = MountainousTerrainChunkGen::build(4, 10.0);
+        let mut scene = GltfScene::build();
+        scene.add_chunkgen(&chunkgen, Position3D64{x: 0.0, y: 0.0, z: 0.0});
+        scene.add_chunkgen(&chunkgen, Position3D64{x: 4.0, y: 0.0, z: 4.0});
+        let mut glb = Vec::new();
+        scene.write_glb(&mut glb).unwrap();
+
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(read_u32(&glb, 4), 2);
+        assert_eq!(read_u32(&glb, 8) as usize, glb.len());
+        let json_len = read_u32(&glb, 12) as usize;
+        assert_eq!(json_len % 4, 0);
+        let json: Value = serde_json::from_slice(&glb[20..20 + json_len]).unwrap();
+        let bin_len = read_u32(&glb, 20 + json_len) as usize;
+        assert_eq!(json["buffers"][0]["byteLength"].as_u64().unwrap() as usize, bin_len);
+        assert!(json["buffers"][0].get("uri").is_none());
+
+        assert_eq!(json["scenes"][0]["nodes"].as_array().unwrap().len(), 2);
+        assert_eq!(json["nodes"][1]["translation"], serde_json::json!([4.0, 0.0, -4.0]));
+        assert_eq!(json["images"][0]["mimeType"], "image/png");
+        assert_eq!(json["accessors"][0]["count"].as_u64().unwrap(), 25);
+    }
+
+    #[test]
+    fn test_handedness_flip() {
+        let chunkgen = MountainousTerrainChunkGen::build(4, 10.0);
+        let desc = chunkgen.get_geometry_desc();
+        let mut v_buffer = vec![Vertex::default(); desc.v_count];
+        let mut indx_buffer = vec![Quad::default(); desc.quad_count()];
+        let mut tex_buffer = vec![Color32::default(); desc.v_count];
+        chunkgen.fill_chunk_2d_with_normals(&mut v_buffer, &mut indx_buffer, &mut tex_buffer, Position3D64::default());
+        let mut scene = GltfScene::build();
+        scene.add_chunk(&v_buffer, &indx_buffer, &tex_buffer, 5, 5, Position3D64::default());
+
+        let (mut json, mut bin) = (Vec::new(), Vec::new());
+        scene.write_gltf(&mut json, &mut bin, "chunk.bin").unwrap();
+        let json: Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["buffers"][0]["uri"], "chunk.bin");
+        assert_eq!(json["buffers"][0]["byteLength"].as_u64().unwrap() as usize, bin.len());
+
+        let view_offset = |accessor: usize| {
+            let view = json["accessors"][accessor]["bufferView"].as_u64().unwrap() as usize;
+            json["bufferViews"][view]["byteOffset"].as_u64().unwrap() as usize
+        };
+        let (positions, normals, tangents, indices) = (view_offset(0), view_offset(1), view_offset(2), view_offset(4));
+        let pos = |v: usize| [read_f32(&bin, positions + 12 * v), read_f32(&bin, positions + 12 * v + 4), read_f32(&bin, positions + 12 * v + 8)];
+
+        for (v, vertex) in v_buffer.iter().enumerate() {
+            assert_eq!(pos(v), [vertex.pos.x, vertex.pos.y, -vertex.pos.z]);
+            assert_eq!(read_f32(&bin, normals + 12 * v + 8), -vertex.norm.z);
+            assert_eq!(read_f32(&bin, tangents + 16 * v + 12), 1.0);
+        }
+        // counter clockwise triangles face up in a right handed frame
+        let count = json["accessors"][4]["count"].as_u64().unwrap() as usize;
+        assert_eq!(count, desc.f_count * 3);
+        for t in 0..count / 3 {
+            let [a, b, c] = [0, 1, 2].map(|k| pos(read_u32(&bin, indices + 4 * (3 * t + k)) as usize));
+            let (e1, e2) = ([b[0] - a[0], b[1] - a[1], b[2] - a[2]], [c[0] - a[0], c[1] - a[1], c[2] - a[2]]);
+            assert!(e1[2] * e2[0] - e1[0] * e2[2] > 0.0);
+        }
+    }
+}
@@ -1,12 +1,15 @@
-use crate::unity::{Lerp, Color32};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Default, PartialEq, PartialOrd, Debug)]
+use crate::{error::{Error, Result}, unity::{Lerp, Color32}};
+
+#[derive(Clone, Copy, Default, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
 #[repr(C)]
 pub struct ColorKey {
     pub color: Color32,
     pub t: f32,
 }
 
+#[allow(dead_code)]
 #[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
 #[repr(C)]
 pub struct ColorKeyMessage
@@ -15,14 +18,16 @@ pub struct ColorKeyMessage
     key_cnt: usize,
     blend_mode: bool,
 }
-#[derive(Clone, PartialEq, PartialOrd, Debug)]
+#[derive(Clone, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BlendType {
     Discrete,
     Linear,
 }
 
 #[repr(C)]
-#[derive(Clone, PartialEq, PartialOrd, Debug)]
+#[derive(Clone, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ColorKeyGradient {
     pub keys: Vec<ColorKey>,
     pub blend_type: BlendType
@@ -30,7 +35,7 @@ pub struct ColorKeyGradient {
 
 impl ColorKeyGradient {
     pub fn get_color(&self, t: f64) -> Color32 {
-        if self.keys.len() > 0 {
+        if !self.keys.is_empty() {
             let mut l = self.keys[0];
             let mut r = self.keys[self.keys.len() - 1];
             let mut adjusted_t = t;
@@ -52,6 +57,17 @@ impl ColorKeyGradient {
             black.lerp_bounded(white, t)
         }
     }
+
+    /// Keys need a finite `t` and have to be sorted by it.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(key) = self.keys.iter().find(|key| !key.t.is_finite()) {
+            return Err(Error::invalid_parameter(format!("color key t must be finite, got {}", key.t)));
+        }
+        if self.keys.windows(2).any(|pair| pair[0].t > pair[1].t) {
+            return Err(Error::invalid_parameter("color keys must be sorted by t"));
+        }
+        Ok(())
+    }
 }
 
 impl Default for ColorKeyGradient {
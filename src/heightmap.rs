@@ -0,0 +1,690 @@
+use std::io::{self, Write};
+use half::f16;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{chunkgen::ChunkGen2D, error::{Error, Result}, erosion::ThermalErosion, gradient::ColorKeyGradient, noisegen::Noise2D, unity::{Color32, Normal32, Position2D64, Position3D32, Position3D64, Quad, SimpleVertex, Tangent32, TexCoord32, TriplanarWeights32, Vertex}};
+
+/// Height differences a normal map is computed from.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalFilter {
+    /// central differences of the 4 direct neighbours
+    #[default]
+    Central,
+    /// Sobel filter over the 3x3 neighbourhood
+    Sobel,
+}
+
+impl From<u32> for NormalFilter {
+    fn from(filter: u32) -> Self {
+        match filter {
+            1 => NormalFilter::Sobel,
+            _ => NormalFilter::Central,
+        }
+    }
+}
+
+/// Frame the normals of a normal map are written in, both use unity's `n * 0.5 + 0.5` encoding.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalSpace {
+    /// red along +x, green along +z, blue up, for textures imported as normal maps on the terrain plane
+    #[default]
+    Tangent,
+    /// red, green and blue along world x, y and z
+    Object,
+}
+
+impl From<u32> for NormalSpace {
+    fn from(space: u32) -> Self {
+        match space {
+            1 => NormalSpace::Object,
+            _ => NormalSpace::Tangent,
+        }
+    }
+}
+
+/// Settings of a baked normal map.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalMap {
+    /// world height of a normalized height of 1 over the pixel spacing, larger values give stronger bumps
+    pub strength: f64,
+    pub filter: NormalFilter,
+    pub space: NormalSpace,
+}
+
+impl NormalMap {
+    pub fn build(strength: f64) -> Self {
+        Self {
+            strength,
+            filter: NormalFilter::default(),
+            space: NormalSpace::default(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !self.strength.is_finite() {
+            return Err(Error::invalid_parameter(format!("normal map strength must be finite, got {}", self.strength)));
+        }
+        Ok(())
+    }
+}
+
+impl Default for NormalMap {
+    fn default() -> Self {
+        NormalMap::build(50.0)
+    }
+}
+
+/// How chunk meshes get their uvs.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UvMode {
+    /// 0 to 1 across every chunk, for a texture per chunk such as `fill_texture`
+    #[default]
+    Chunk,
+    /// world x and z over `UvMapping::tiling`, tiled textures continue across chunks
+    World,
+    /// world uvs like `World`, with triplanar blend weights from `fill_chunk_triplanar_weights`
+    Triplanar,
+}
+
+impl From<u32> for UvMode {
+    fn from(mode: u32) -> Self {
+        match mode {
+            1 => UvMode::World,
+            2 => UvMode::Triplanar,
+            _ => UvMode::Chunk,
+        }
+    }
+}
+
+/// Uv settings of a chunkgen.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UvMapping {
+    pub mode: UvMode,
+    /// world units covered by one repeat of a texture in world space
+    pub tiling: f64,
+    /// exponent of the triplanar weights, larger values give sharper transitions between projections
+    pub sharpness: f64,
+}
+
+impl UvMapping {
+    pub fn build(mode: UvMode, tiling: f64) -> Self {
+        Self {
+            mode,
+            tiling,
+            sharpness: 4.0,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.tiling.is_finite() && self.tiling > 0.0) {
+            return Err(Error::invalid_parameter(format!("uv tiling must be positive, got {}", self.tiling)));
+        }
+        if !(self.sharpness.is_finite() && self.sharpness >= 1.0) {
+            return Err(Error::invalid_parameter(format!("triplanar sharpness must be at least 1, got {}", self.sharpness)));
+        }
+        Ok(())
+    }
+
+    /// Uv of the vertex at `pos` in a chunk centered on `center`, `chunk_uv` is the uv `fill_mesh` wrote.
+    /// World uvs are offset by whole repeats to the repeat the chunk center is in, so they keep their precision
+    /// far from the origin while textures with repeat wrapping still line up with the neighbouring chunks.
+    pub fn uv(&self, center: Position3D64, pos: Position3D32, chunk_uv: TexCoord32) -> TexCoord32 {
+        if self.mode == UvMode::Chunk {
+            return chunk_uv;
+        }
+        let local = |c: f64, p: f32| (c - (c / self.tiling).floor() * self.tiling + p as f64) / self.tiling;
+        TexCoord32{ u: local(center.x, pos.x) as f32, v: local(center.z, pos.z) as f32 }
+    }
+
+    /// Blend weights of the x, y and z projections of a triplanar material for a surface normal, they sum to 1.
+    pub fn triplanar_weights(&self, norm: Normal32) -> TriplanarWeights32 {
+        let (x, y, z) = (norm.x.abs().powf(self.sharpness as f32), norm.y.abs().powf(self.sharpness as f32), norm.z.abs().powf(self.sharpness as f32));
+        let sum = x + y + z;
+        if sum > 0.0 {
+            TriplanarWeights32{ x: x / sum, y: y / sum, z: z / sum }
+        }
+        else {
+            TriplanarWeights32{ x: 0.0, y: 1.0, z: 0.0 }
+        }
+    }
+}
+
+impl Default for UvMapping {
+    fn default() -> Self {
+        UvMapping::build(UvMode::default(), 16.0)
+    }
+}
+
+/// Normalized heights sampled on a regular grid, `origin` is the world position of sample (0, 0)
+/// and samples are `spacing` world units apart.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeightMap {
+    pub width: usize,
+    pub height: usize,
+    pub origin: Position2D64,
+    pub spacing: f32,
+    pub data: Vec<f32>,
+}
+
+impl HeightMap {
+    pub fn build(width: usize, height: usize, origin: Position2D64, spacing: f32) -> Self {
+        Self {
+            width,
+            height,
+            origin,
+            spacing,
+            data: vec![0.0; width * height],
+        }
+    }
+
+    /// Samples `f` at every grid position.
+    pub fn sample<F>(width: usize, height: usize, origin: Position2D64, spacing: f32, f: F) -> Self where F: Fn(Position2D64) -> f64 + Sync {
+        let mut map = HeightMap::build(width, height, origin, spacing);
+        map.data.par_iter_mut().enumerate().for_each(|(i, h)| {
+            let pos = Position2D64{ x: (i % width) as f64 * spacing as f64, y: (i / width) as f64 * spacing as f64 } + origin;
+            *h = f(pos) as f32;
+        });
+        map
+    }
+
+    /// Samples any `Noise2D`, e.g. to cache heights for collision or gameplay queries.
+    pub fn from_noise<'a, N: Noise2D<'a> + Sync>(noise: &N, width: usize, height: usize, origin: Position2D64, spacing: f32) -> Self {
+        HeightMap::sample(width, height, origin, spacing, |pos| noise.get(pos))
+    }
+
+    /// Samples the vertex grid of a chunk at its lod, extended by `apron` samples on every side.
+    pub fn from_chunkgen<C: ChunkGen2D + Sync>(chunkgen: &C, plane_pos: Position3D64, apron: usize) -> Self {
+        let grid = HeightMap::chunk_grid(chunkgen, plane_pos, apron);
+        HeightMap::sample(grid.width, grid.height, grid.origin, grid.spacing, |pos| chunkgen.get(pos))
+    }
+
+    /// The grid `from_chunkgen` samples, with all heights left at 0.
+    pub fn chunk_grid<C: ChunkGen2D>(chunkgen: &C, plane_pos: Position3D64, apron: usize) -> Self {
+        let n = chunkgen.get_geometry_desc().quads_per_side;
+        let spacing = chunkgen.get_side_len() as f32 / n as f32;
+        let half_side_len = chunkgen.get_side_len() as f64 / 2.0;
+        let side = n + 1 + 2 * apron;
+        let corner = -half_side_len - apron as f64 * spacing as f64;
+        let origin = Position2D64{ x: corner, y: corner } + Position2D64::from(plane_pos);
+        HeightMap::build(side, side, origin, spacing)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.data[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, h: f32) {
+        self.data[y * self.width + x] = h;
+    }
+
+    pub fn world_pos(&self, x: usize, y: usize) -> Position2D64 {
+        Position2D64{ x: x as f64 * self.spacing as f64, y: y as f64 * self.spacing as f64 } + self.origin
+    }
+
+    /// Bilinear height at a world position, `None` outside the map.
+    pub fn height_at(&self, pos: Position2D64) -> Option<f32> {
+        let x = ((pos.x - self.origin.x) / self.spacing as f64) as f32;
+        let y = ((pos.y - self.origin.y) / self.spacing as f64) as f32;
+        if self.width == 0 || self.height == 0 || x < 0.0 || y < 0.0 || x > (self.width - 1) as f32 || y > (self.height - 1) as f32 {
+            return None;
+        }
+        let x0 = (x.floor() as usize).min(self.width.saturating_sub(2));
+        let y0 = (y.floor() as usize).min(self.height.saturating_sub(2));
+        let x1 = (x0 + 1).min(self.width - 1);
+        let y1 = (y0 + 1).min(self.height - 1);
+        let (u, v) = (x - x0 as f32, y - y0 as f32);
+        let top = self.get(x0, y0) * (1.0 - u) + self.get(x1, y0) * u;
+        let bottom = self.get(x0, y1) * (1.0 - u) + self.get(x1, y1) * u;
+        Some(top * (1.0 - v) + bottom * v)
+    }
+
+    /// Surface normal from central differences, one sided at the border of the map.
+    pub fn normal_at(&self, x: usize, y: usize, height_scale: f64) -> Normal32 {
+        let (x0, x1) = (x.saturating_sub(1), (x + 1).min(self.width - 1));
+        let (y0, y1) = (y.saturating_sub(1), (y + 1).min(self.height - 1));
+        let dh_dx = (self.get(x1, y) - self.get(x0, y)) as f64 * height_scale / ((x1 - x0).max(1) as f64 * self.spacing as f64);
+        let dh_dz = (self.get(x, y1) - self.get(x, y0)) as f64 * height_scale / ((y1 - y0).max(1) as f64 * self.spacing as f64);
+        let norm_len = (dh_dx * dh_dx + 1.0 + dh_dz * dh_dz).sqrt();
+        Normal32{
+            x: (-dh_dx / norm_len) as f32,
+            y: (1.0 / norm_len) as f32,
+            z: (-dh_dz / norm_len) as f32
+        }
+    }
+
+    /// Index of sample (0, 0) on the world grid with this spacing, stable for every map sharing the grid.
+    /// Maps whose origin is not a multiple of the spacing get the cell before it, see `lattice_offset`.
+    pub fn world_cell(&self) -> (i64, i64) {
+        let (cell, _) = self.lattice();
+        (cell[0], cell[1])
+    }
+
+    /// Offset of the samples from the world grid with this spacing in cells, each in 0..1.
+    /// Chunks of an odd `side_len` are centered on whole units, so their samples sit half a cell off the grid.
+    pub fn lattice_offset(&self) -> Position2D64 {
+        let (_, offset) = self.lattice();
+        Position2D64{ x: offset[0], y: offset[1] }
+    }
+
+    fn lattice(&self) -> ([i64; 2], [f64; 2]) {
+        let split = |origin: f64| {
+            let pos = origin / self.spacing as f64;
+            // snap positions within rounding error of a cell onto it
+            let cell = (pos + LATTICE_EPSILON).floor();
+            let offset = pos - cell;
+            (cell as i64, if offset.abs() < LATTICE_EPSILON { 0.0 } else { offset })
+        };
+        let (x, y) = (split(self.origin.x), split(self.origin.y));
+        ([x.0, y.0], [x.1, y.1])
+    }
+
+    /// Runs thermal weathering on the map, `height_scale` turns normalized heights into world units.
+    pub fn thermal_erode(&mut self, erosion: &ThermalErosion, height_scale: f32) {
+        erosion.apply(self, height_scale);
+    }
+
+    /// Drops `border` samples from every side, e.g. the apron of `from_chunkgen`.
+    pub fn crop(&self, border: usize) -> Self {
+        let width = self.width.saturating_sub(2 * border);
+        let height = self.height.saturating_sub(2 * border);
+        let mut map = HeightMap::build(width, height, self.world_pos(border, border), self.spacing);
+        for y in 0..height {
+            let src = (y + border) * self.width + border;
+            map.data[y * width..(y + 1) * width].copy_from_slice(&self.data[src..src + width]);
+        }
+        map
+    }
+
+    /// Encodes the map as a 16 bit grayscale PNG, heights are clamped to [0, 1] and row 0 is the first image row.
+    pub fn write_png16<W: Write>(&self, out: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Sixteen);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        let data: Vec<u8> = self.data.iter().flat_map(|h| to_r16(*h).to_be_bytes().to_vec()).collect();
+        writer.write_image_data(&data).map_err(io::Error::other)
+    }
+
+    /// Headerless little endian 16 bit heights, row 0 first, the layout unity's `TerrainData` raw import expects
+    /// with byte order windows. Heights are clamped to [0, 1] like `write_png16`.
+    pub fn write_raw16<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let data: Vec<u8> = self.data.iter().flat_map(|h| to_r16(*h).to_le_bytes().to_vec()).collect();
+        out.write_all(&data)
+    }
+
+    /// Binary 16 bit PGM (P5, maxval 65535), same rows and quantization as `write_png16`.
+    pub fn write_pgm16<W: Write>(&self, mut out: W) -> io::Result<()> {
+        write!(out, "P5\n{} {}\n65535\n", self.width, self.height)?;
+        let data: Vec<u8> = self.data.iter().flat_map(|h| to_r16(*h).to_be_bytes().to_vec()).collect();
+        out.write_all(&data)
+    }
+
+    /// Grayscale little endian PFM of the unquantized heights. PFM stores the bottom row first,
+    /// rows are written last to first so that the image matches `write_png16`.
+    pub fn write_pfm<W: Write>(&self, mut out: W) -> io::Result<()> {
+        write!(out, "Pf\n{} {}\n-1.0\n", self.width, self.height)?;
+        let data: Vec<u8> = self.data.chunks(self.width).rev().flatten().flat_map(|h| h.to_le_bytes().to_vec()).collect();
+        out.write_all(&data)
+    }
+}
+
+/// Quantizes a normalized height to 16 bits, heights outside [0, 1] are clamped.
+pub fn to_r16(h: f32) -> u16 {
+    (h.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+}
+
+/// how far in cells an origin may be off the world grid and still count as on it
+const LATTICE_EPSILON: f64 = 1e-6;
+
+/// Vertex and quad counts of the mesh `fill_mesh` writes for a `width` x `height` grid.
+pub fn mesh_counts(width: usize, height: usize, skirts: bool) -> (usize, usize) {
+    let mut v_count = width * height;
+    let mut quad_count = width.saturating_sub(1) * height.saturating_sub(1);
+    if skirts {
+        v_count += 2 * (width + height);
+        quad_count += 2 * (width.saturating_sub(1) + height.saturating_sub(1));
+    }
+    (v_count, quad_count)
+}
+
+/// Maps a vertex buffer index to its grid column and row, and whether it is a skirt vertex.
+/// Skirt vertices follow the grid, one run per border: south, east, north, west.
+fn grid_coords(i: usize, width: usize, height: usize) -> (usize, usize, bool) {
+    if i < width * height {
+        return (i % width, i / width, false);
+    }
+    let k = i - width * height;
+    if k < width {
+        (k, 0, true)
+    }
+    else if k < width + height {
+        (width - 1, k - width, true)
+    }
+    else if k < 2 * width + height {
+        (k - width - height, height - 1, true)
+    }
+    else {
+        (0, k - 2 * width - height, true)
+    }
+}
+
+impl HeightMap {
+    /// Writes a grid mesh for the map without its outer `border` samples, centered on the middle of the map.
+    /// Vertex heights are `h * height_scale + y_offset`, skirts are added if `skirt_depth` is positive.
+    pub fn fill_mesh(&self, border: usize, height_scale: f64, y_offset: f32, skirt_depth: f64, v_buffer: &mut [SimpleVertex], indx_buffer: &mut [Quad]) {
+        v_buffer.par_iter_mut().enumerate().for_each(|(i, cur_v)| {
+            let (pos, uv, _, _) = self.grid_vertex(i, border, height_scale, y_offset, skirt_depth);
+            cur_v.pos = pos;
+            cur_v.uv = uv;
+        });
+        self.fill_indices(border, indx_buffer);
+    }
+
+    /// Same as `fill_mesh`, with normals and tangents. Border samples still feed the normals
+    /// of the outermost vertices, so a `border` of 1 gives normals that match the neighbouring chunk.
+    pub fn fill_mesh_with_normals(&self, border: usize, height_scale: f64, y_offset: f32, skirt_depth: f64, v_buffer: &mut [Vertex], indx_buffer: &mut [Quad]) {
+        v_buffer.par_iter_mut().enumerate().for_each(|(i, cur_v)| {
+            let (pos, uv, x, y) = self.grid_vertex(i, border, height_scale, y_offset, skirt_depth);
+            cur_v.pos = pos;
+            cur_v.uv = uv;
+            cur_v.norm = self.normal_at(x, y, height_scale);
+            // tangent follows +u (+x), bitangent +v (+z) which is cross(norm, tangent) * -1 in unity
+            let dh_dx = -cur_v.norm.x / cur_v.norm.y;
+            let tan_len = (1.0 + dh_dx * dh_dx).sqrt();
+            cur_v.tangent = Tangent32{
+                w: -1f32,
+                x: 1.0 / tan_len,
+                y: dh_dx / tan_len,
+                z: 0f32
+            };
+        });
+        self.fill_indices(border, indx_buffer);
+    }
+
+    /// Colors every vertex `fill_mesh` writes with the same border, skirts take the color of the vertex above them.
+    pub fn fill_vertex_colors(&self, border: usize, color_gradient: &ColorKeyGradient, tex_buffer: &mut [Color32]) {
+        self.fill_vertex_data(border, tex_buffer, |_, _, h| color_gradient.get_color(h as f64));
+    }
+
+    /// Writes `f(x, y, h)` for every vertex `fill_mesh` writes with the same border, `x` and `y` are
+    /// map coordinates. Skirts get the value of the vertex above them.
+    pub fn fill_vertex_data<T, F>(&self, border: usize, buffer: &mut [T], f: F) where T: Send, F: Fn(usize, usize, f32) -> T + Sync {
+        let (width, height) = (self.width - 2 * border, self.height - 2 * border);
+        buffer.par_iter_mut().enumerate().for_each(|(i, cur)| {
+            let (col, row, _) = grid_coords(i, width, height);
+            let (x, y) = (col + border, row + border);
+            *cur = f(x, y, self.get(x, y));
+        });
+    }
+
+    /// Triplanar blend weights of every vertex `fill_mesh_with_normals` writes with the same border,
+    /// from the same normals. Skirts take the weights of the vertex above them.
+    pub fn fill_triplanar_weights(&self, border: usize, height_scale: f64, uv_mapping: &UvMapping, buffer: &mut [TriplanarWeights32]) {
+        self.fill_vertex_data(border, buffer, |x, y, _| uv_mapping.triplanar_weights(self.normal_at(x, y, height_scale)));
+    }
+
+    /// Colors the map without its outer `border` samples, one pixel per sample.
+    pub fn fill_color_texture(&self, border: usize, color_gradient: &ColorKeyGradient, tex_buffer: &mut [Color32]) {
+        let width = self.width - 2 * border;
+        tex_buffer.par_iter_mut().enumerate().for_each(|(i, cur_pix)| {
+            *cur_pix = color_gradient.get_color(self.get(i % width + border, i / width + border) as f64);
+        });
+    }
+
+    /// 16 bit heights of the map without its outer `border` samples, one value per sample, see `to_r16`.
+    pub fn fill_r16(&self, border: usize, buffer: &mut [u16]) {
+        self.fill_samples(border, buffer, to_r16);
+    }
+
+    /// Heights of the map without its outer `border` samples, one value per sample.
+    pub fn fill_f32(&self, border: usize, buffer: &mut [f32]) {
+        self.fill_samples(border, buffer, |h| h);
+    }
+
+    /// Half precision heights of the map without its outer `border` samples, one value per sample.
+    pub fn fill_f16(&self, border: usize, buffer: &mut [f16]) {
+        self.fill_samples(border, buffer, f16::from_f32);
+    }
+
+    fn fill_samples<T, F>(&self, border: usize, buffer: &mut [T], f: F) where T: Send, F: Fn(f32) -> T + Sync {
+        let width = self.width - 2 * border;
+        buffer.par_iter_mut().enumerate().for_each(|(i, cur)| {
+            *cur = f(self.get(i % width + border, i / width + border));
+        });
+    }
+
+    /// Tangent space normal map of the map without its outer `border` samples, encoded as `n * 0.5 + 0.5`.
+    pub fn fill_normal_map(&self, border: usize, height_scale: f64, tex_buffer: &mut [Color32]) {
+        self.fill_normal_map_with(border, &NormalMap{ strength: height_scale, ..NormalMap::default() }, tex_buffer);
+    }
+
+    /// Normal map of the map without its outer `border` samples, heights are scaled by `normal_map.strength`.
+    pub fn fill_normal_map_with(&self, border: usize, normal_map: &NormalMap, tex_buffer: &mut [Color32]) {
+        let width = self.width - 2 * border;
+        let encode = |n: f32| ((n * 0.5 + 0.5) * 255.0).round().clamp(0.0, 255.0) as u8;
+        tex_buffer.par_iter_mut().enumerate().for_each(|(i, cur_pix)| {
+            let (x, y) = (i % width + border, i / width + border);
+            let norm = match normal_map.filter {
+                NormalFilter::Central => self.normal_at(x, y, normal_map.strength),
+                NormalFilter::Sobel => self.sobel_normal_at(x, y, normal_map.strength),
+            };
+            *cur_pix = match normal_map.space {
+                // the terrain tangent frame is x, z, y
+                NormalSpace::Tangent => Color32{ r: encode(norm.x), g: encode(norm.z), b: encode(norm.y), a: 255 },
+                NormalSpace::Object => Color32{ r: encode(norm.x), g: encode(norm.y), b: encode(norm.z), a: 255 },
+            };
+        });
+    }
+
+    /// Surface normal from a 3x3 Sobel filter, smoother than `normal_at` on noisy heights.
+    pub fn sobel_normal_at(&self, x: usize, y: usize, height_scale: f64) -> Normal32 {
+        let (x0, x1) = (x.saturating_sub(1), (x + 1).min(self.width - 1));
+        let (y0, y1) = (y.saturating_sub(1), (y + 1).min(self.height - 1));
+        let h = |x: usize, y: usize| self.get(x, y) as f64;
+        let span_x = (x1 - x0).max(1) as f64 * self.spacing as f64;
+        let span_y = (y1 - y0).max(1) as f64 * self.spacing as f64;
+        let dh_dx = (h(x1, y0) + 2.0 * h(x1, y) + h(x1, y1) - h(x0, y0) - 2.0 * h(x0, y) - h(x0, y1)) * height_scale / (4.0 * span_x);
+        let dh_dz = (h(x0, y1) + 2.0 * h(x, y1) + h(x1, y1) - h(x0, y0) - 2.0 * h(x, y0) - h(x1, y0)) * height_scale / (4.0 * span_y);
+        let norm_len = (dh_dx * dh_dx + 1.0 + dh_dz * dh_dz).sqrt();
+        Normal32{
+            x: (-dh_dx / norm_len) as f32,
+            y: (1.0 / norm_len) as f32,
+            z: (-dh_dz / norm_len) as f32
+        }
+    }
+
+    /// local position, uv and map coordinates of vertex `i` of the mesh
+    fn grid_vertex(&self, i: usize, border: usize, height_scale: f64, y_offset: f32, skirt_depth: f64) -> (Position3D32, TexCoord32, usize, usize) {
+        let (width, height) = (self.width - 2 * border, self.height - 2 * border);
+        let (col, row, is_skirt) = grid_coords(i, width, height);
+        let (x, y) = (col + border, row + border);
+        let h = self.get(x, y) as f64 * height_scale - if is_skirt { skirt_depth.max(0.0) } else { 0.0 };
+        let pos = Position3D32{
+            x: (col as f32 - (width - 1) as f32 / 2.0) * self.spacing,
+            y: h as f32 + y_offset,
+            z: (row as f32 - (height - 1) as f32 / 2.0) * self.spacing
+        };
+        let uv = TexCoord32{
+            u: col as f32 / (width - 1).max(1) as f32,
+            v: row as f32 / (height - 1).max(1) as f32,
+        };
+        (pos, uv, x, y)
+    }
+
+    fn fill_indices(&self, border: usize, indx_buffer: &mut [Quad]) {
+        let (width, height) = (self.width - 2 * border, self.height - 2 * border);
+        let (quads_x, quads_y) = (width - 1, height - 1);
+        let grid_quads = quads_x * quads_y;
+        let vert_side = width as i32;
+        let skirt_start = (width * height) as i32;
+
+        indx_buffer.par_iter_mut().enumerate().for_each(|(i, cur_tri)| {
+            if i < grid_quads {
+                let z = (i % quads_y) as i32;
+                let x = (i / quads_y) as i32;
+                let s = z * vert_side + x;
+                cur_tri.v1 = s + vert_side;
+                cur_tri.v2 = s + 1;
+                cur_tri.v3 = s;
+                cur_tri.v4 = s + 1;
+                cur_tri.v5 = s + vert_side;
+                cur_tri.v6 = s + vert_side + 1;
+            }
+            else {
+                // skirt quads, p is left of q when looking at the border from outside the grid
+                let mut k = i - grid_quads;
+                let runs = [quads_x, quads_y, quads_x, quads_y];
+                let mut border = 0;
+                while border < 3 && k >= runs[border] {
+                    k -= runs[border];
+                    border += 1;
+                }
+                let j = k as i32;
+                let (p, q) = match border {
+                    0 | 1 => (j, j + 1),
+                    _ => (j + 1, j),
+                };
+                let (w, h) = (width as i32, height as i32);
+                let top = |j: i32| match border {
+                    0 => j,
+                    1 => j * w + w - 1,
+                    2 => (h - 1) * w + j,
+                    _ => j * w,
+                };
+                let bottom = |j: i32| skirt_start + match border {
+                    0 => j,
+                    1 => w + j,
+                    2 => w + h + j,
+                    _ => 2 * w + h + j,
+                };
+                cur_tri.v1 = top(p);
+                cur_tri.v2 = top(q);
+                cur_tri.v3 = bottom(q);
+                cur_tri.v4 = top(p);
+                cur_tri.v5 = bottom(q);
+                cur_tri.v6 = bottom(p);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod heightmap_tests {
+    use crate::{chunkgen::{ChunkGen2D, ChunkGeometryDesc, MountainousTerrainChunkGen}, gradient::ColorKeyGradient, noisegen::{MountainousTerrainNoise, Noise2D}, unity::{Color32, Position2D64, Position3D32, Position3D64, Quad, SimpleVertex}};
+
+    use super::{HeightMap, mesh_counts, to_r16};
+
+    #[test]
+    fn test_from_chunkgen_apron() {
+        let chunkgen = MountainousTerrainChunkGen::build(8, 50.0);
+        let plane_pos = Position3D64{x: 16.0, y: 0.0, z: -8.0};
+        let map = HeightMap::from_chunkgen(&chunkgen, plane_pos, 2);
+        assert_eq!(map.width, 13);
+        assert_eq!(map.origin, Position2D64{x: 10.0, y: -14.0});
+        assert_eq!(map.world_cell(), (10, -14));
+
+        let core = map.crop(2);
+        assert_eq!(core.width, 9);
+        assert_eq!(core.origin, Position2D64{x: 12.0, y: -12.0});
+        assert_eq!(core.get(3, 4), chunkgen.get(Position2D64{x: 15.0, y: -8.0}) as f32);
+    }
+
+    #[test]
+    fn test_from_noise_height_queries() {
+        let noise = MountainousTerrainNoise::default();
+        let map = HeightMap::from_noise(&noise, 5, 4, Position2D64{x: 100.0, y: 50.0}, 2.0);
+        assert_eq!(map.height_at(Position2D64{x: 104.0, y: 52.0}), Some(noise.get(Position2D64{x: 104.0, y: 52.0}) as f32));
+        let mid = map.height_at(Position2D64{x: 105.0, y: 52.0}).unwrap();
+        assert!((mid - (map.get(2, 1) + map.get(3, 1)) / 2.0).abs() < 1e-6);
+        assert_eq!(map.height_at(Position2D64{x: 99.0, y: 52.0}), None);
+        assert_eq!(map.height_at(Position2D64{x: 108.0, y: 56.0}), Some(map.get(4, 3)));
+    }
+
+    #[test]
+    fn test_rectangular_mesh() {
+        let map = HeightMap::sample(4, 3, Position2D64::default(), 1.0, |pos| pos.x / 10.0);
+        let (v_count, quad_count) = mesh_counts(4, 3, true);
+        assert_eq!((v_count, quad_count), (12 + 14, 6 + 10));
+        assert_eq!(mesh_counts(9, 9, true), (ChunkGeometryDesc::build(8, 0, true).v_count, ChunkGeometryDesc::build(8, 0, true).quad_count()));
+
+        let mut v_buffer = vec![SimpleVertex::default(); v_count];
+        let mut indx_buffer = vec![Quad::default(); quad_count];
+        let mut tex_buffer = vec![Color32::default(); v_count];
+        map.fill_mesh(0, 10.0, 0.0, 1.0, &mut v_buffer, &mut indx_buffer);
+        map.fill_vertex_colors(0, &ColorKeyGradient::default(), &mut tex_buffer);
+        assert_eq!(v_buffer[0].pos, Position3D32{x: -1.5, y: 0.0, z: -1.0});
+        assert_eq!(v_buffer[11].pos, Position3D32{x: 1.5, y: 3.0, z: 1.0});
+        assert_eq!(tex_buffer[12 + 4 + 2], tex_buffer[11]);
+        let mut used = vec![false; v_count];
+        for quad in indx_buffer.iter() {
+            for v in [quad.v1, quad.v2, quad.v3, quad.v4, quad.v5, quad.v6].iter() {
+                used[*v as usize] = true;
+            }
+        }
+        assert!(used.iter().all(|u| *u));
+    }
+
+    #[test]
+    fn test_far_samples_stay_smooth() {
+        use noise::{Perlin, Seedable};
+        use crate::noisegraph::{NoiseFnSource, NoiseSource};
+        // raw perlin noise, the mountainous noise is flat in its valleys
+        let noise = NoiseFnSource{noise: Perlin::new().set_seed(5), frequency: 0.05};
+        let steps = |x: f64, z: f64| {
+            let map = HeightMap::sample(256, 1, Position2D64{x, y: z}, 0.125, |pos| noise.get(pos));
+            map.data.windows(2).map(|pair| (pair[1] - pair[0]).abs()).collect::<Vec<f32>>()
+        };
+        let near = steps(0.0, 0.0);
+        let largest_near = near.iter().cloned().fold(0.0, f32::max);
+        for &far in [1e7, -1e7].iter() {
+            let far_steps = steps(far, far);
+            // in f32 eight samples would share a position and then jump a whole unit
+            assert!(far_steps.iter().all(|step| *step > 0.0));
+            assert!(far_steps.iter().cloned().fold(0.0, f32::max) < largest_near * 4.0);
+        }
+    }
+
+    #[test]
+    fn test_height_formats() {
+        use half::f16;
+        let mut map = HeightMap::build(3, 4, Position2D64::default(), 1.0);
+        map.data.iter_mut().enumerate().for_each(|(i, h)| *h = i as f32 / 8.0 - 0.25);
+        let mut r16 = vec![0_u16; 2];
+        map.fill_r16(1, &mut r16);
+        assert_eq!(r16, vec![to_r16(map.get(1, 1)), to_r16(map.get(1, 2))]);
+        let mut all = vec![0_u16; 12];
+        map.fill_r16(0, &mut all);
+        // clamped at both ends
+        assert_eq!((all[0], all[11]), (0, u16::MAX));
+        let mut floats = vec![0.0_f32; 12];
+        map.fill_f32(0, &mut floats);
+        assert_eq!(floats, map.data);
+        let mut halves = vec![f16::from_f32(0.0); 12];
+        map.fill_f16(0, &mut halves);
+        assert!(halves.iter().zip(map.data.iter()).all(|(half, h)| (half.to_f32() - h).abs() < 1e-3));
+
+        let mut raw = Vec::new();
+        map.write_raw16(&mut raw).unwrap();
+        assert_eq!(raw.len(), 24);
+        assert_eq!(u16::from_le_bytes([raw[10], raw[11]]), all[5]);
+        let mut pgm = Vec::new();
+        map.write_pgm16(&mut pgm).unwrap();
+        let header = b"P5\n3 4\n65535\n";
+        assert_eq!(&pgm[..header.len()], header);
+        assert_eq!(u16::from_be_bytes([pgm[header.len() + 10], pgm[header.len() + 11]]), all[5]);
+        let mut pfm = Vec::new();
+        map.write_pfm(&mut pfm).unwrap();
+        let header = b"Pf\n3 4\n-1.0\n";
+        assert_eq!(&pfm[..header.len()], header);
+        assert_eq!(pfm.len(), header.len() + 48);
+        // the first value in the file is the first sample of the last row
+        let first = &pfm[header.len()..header.len() + 4];
+        assert_eq!(f32::from_le_bytes([first[0], first[1], first[2], first[3]]), map.get(0, 3));
+    }
+}
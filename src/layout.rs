@@ -0,0 +1,292 @@
+use half::f16;
+use rayon::prelude::*;
+
+use crate::{biome::BiomeBlend, error::{Error, Result}, unity::{Color32, TriplanarWeights32, Vertex}};
+
+/// unity splits vertex data in at most 4 streams
+pub const MAX_VERTEX_STREAMS: usize = 4;
+
+/// Vertex attributes a `VertexLayout` can hold, numbered like unity's `VertexAttribute`
+/// so that a `VertexAttributeDescriptor` array can be passed as is.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum VertexAttribute {
+    #[default]
+    Position,
+    Normal,
+    /// unity's `x, y, z, w` order
+    Tangent,
+    /// the gradient color of the vertex
+    Color,
+    /// uvs of the `UvMapping` of the chunkgen
+    Uv0,
+    /// triplanar weights of the `UvMapping` of the chunkgen
+    Uv1,
+    /// weights of biomes 0 to 3, in unity's `TexCoord2` slot
+    BiomeWeights,
+}
+
+impl From<u32> for VertexAttribute {
+    fn from(attribute: u32) -> Self {
+        match attribute {
+            1 => VertexAttribute::Normal,
+            2 => VertexAttribute::Tangent,
+            3 => VertexAttribute::Color,
+            4 => VertexAttribute::Uv0,
+            5 => VertexAttribute::Uv1,
+            6 => VertexAttribute::BiomeWeights,
+            _ => VertexAttribute::Position,
+        }
+    }
+}
+
+/// Component formats, numbered like unity's `VertexAttributeFormat`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum VertexFormat {
+    #[default]
+    Float32,
+    Float16,
+    /// [0, 1] in a byte, e.g. colors
+    UNorm8,
+    /// [-1, 1] in a byte, e.g. normals and tangents
+    SNorm8,
+}
+
+impl From<u32> for VertexFormat {
+    fn from(format: u32) -> Self {
+        match format {
+            1 => VertexFormat::Float16,
+            2 => VertexFormat::UNorm8,
+            3 => VertexFormat::SNorm8,
+            _ => VertexFormat::Float32,
+        }
+    }
+}
+
+impl VertexFormat {
+    pub fn size(&self) -> usize {
+        match self {
+            VertexFormat::Float32 => 4,
+            VertexFormat::Float16 => 2,
+            VertexFormat::UNorm8 | VertexFormat::SNorm8 => 1,
+        }
+    }
+
+    fn write(&self, value: f32, out: &mut [u8]) {
+        match self {
+            VertexFormat::Float32 => out[..4].copy_from_slice(&value.to_le_bytes()),
+            VertexFormat::Float16 => out[..2].copy_from_slice(&f16::from_f32(value).to_le_bytes()),
+            VertexFormat::UNorm8 => out[0] = (value.clamp(0.0, 1.0) * 255.0).round() as u8,
+            VertexFormat::SNorm8 => out[0] = (value.clamp(-1.0, 1.0) * 127.0).round() as i8 as u8,
+        }
+    }
+}
+
+/// One attribute of a layout, the same memory layout as unity's `VertexAttributeDescriptor`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct VertexAttributeDesc {
+    /// see `VertexAttribute`
+    pub attribute: u32,
+    /// see `VertexFormat`
+    pub format: u32,
+    /// number of components, 1 to 4
+    pub dimension: u32,
+    pub stream: u32,
+}
+
+impl VertexAttributeDesc {
+    pub fn build(attribute: VertexAttribute, format: VertexFormat, dimension: u32, stream: u32) -> Self {
+        Self { attribute: attribute as u32, format: format as u32, dimension, stream }
+    }
+}
+
+/// An attribute placed in its stream.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AttributeLayout {
+    pub attribute: VertexAttribute,
+    pub format: VertexFormat,
+    pub dimension: usize,
+    pub stream: usize,
+    /// bytes from the start of the vertex in its stream
+    pub offset: usize,
+}
+
+impl AttributeLayout {
+    pub fn size(&self) -> usize {
+        self.format.size() * self.dimension
+    }
+}
+
+/// Everything a layout can write for one vertex.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct VertexSources {
+    pub vertex: Vertex,
+    pub color: Color32,
+    pub triplanar_weights: TriplanarWeights32,
+    pub biome_blend: BiomeBlend,
+}
+
+impl VertexSources {
+    fn components(&self, attribute: VertexAttribute) -> [f32; 4] {
+        let v = &self.vertex;
+        match attribute {
+            VertexAttribute::Position => [v.pos.x, v.pos.y, v.pos.z, 1.0],
+            VertexAttribute::Normal => [v.norm.x, v.norm.y, v.norm.z, 0.0],
+            VertexAttribute::Tangent => [v.tangent.x, v.tangent.y, v.tangent.z, v.tangent.w],
+            VertexAttribute::Color => {
+                let c = &self.color;
+                [c.r as f32 / 255.0, c.g as f32 / 255.0, c.b as f32 / 255.0, c.a as f32 / 255.0]
+            },
+            VertexAttribute::Uv0 => [v.uv.u, v.uv.v, 0.0, 0.0],
+            VertexAttribute::Uv1 => {
+                let w = &self.triplanar_weights;
+                [w.x, w.y, w.z, 0.0]
+            },
+            VertexAttribute::BiomeWeights => {
+                let blend = &self.biome_blend;
+                [blend.weight_of(0), blend.weight_of(1), blend.weight_of(2), blend.weight_of(3)]
+            },
+        }
+    }
+}
+
+/// Where every attribute of a vertex goes in the vertex streams. Attributes are packed in the order of
+/// `VertexAttribute` within their stream, the same way unity's `Mesh.SetVertexBufferParams` lays them out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VertexLayout {
+    pub attributes: Vec<AttributeLayout>,
+    /// bytes per vertex of every stream, 0 for streams without attributes
+    pub strides: Vec<usize>,
+}
+
+impl VertexLayout {
+    /// Checks the descriptors and computes offsets and strides. Like unity, every attribute must be a
+    /// multiple of 4 bytes and appear at most once.
+    pub fn build(descs: &[VertexAttributeDesc]) -> Result<Self> {
+        let mut attributes = Vec::with_capacity(descs.len());
+        for desc in descs.iter() {
+            if desc.attribute > VertexAttribute::BiomeWeights as u32 {
+                return Err(Error::invalid_parameter(format!("unknown vertex attribute {}", desc.attribute)));
+            }
+            if desc.format > VertexFormat::SNorm8 as u32 {
+                return Err(Error::invalid_parameter(format!("unsupported vertex format {}", desc.format)));
+            }
+            if !(1..=4).contains(&desc.dimension) {
+                return Err(Error::invalid_parameter(format!("vertex attribute dimension must be 1 to 4, got {}", desc.dimension)));
+            }
+            if desc.stream as usize >= MAX_VERTEX_STREAMS {
+                return Err(Error::invalid_parameter(format!("vertex stream must be below {}, got {}", MAX_VERTEX_STREAMS, desc.stream)));
+            }
+            let attribute = VertexAttribute::from(desc.attribute);
+            if attributes.iter().any(|other: &AttributeLayout| other.attribute == attribute) {
+                return Err(Error::invalid_parameter(format!("vertex attribute {:?} is listed twice", attribute)));
+            }
+            let layout = AttributeLayout { attribute, format: desc.format.into(), dimension: desc.dimension as usize, stream: desc.stream as usize, offset: 0 };
+            if !layout.size().is_multiple_of(4) {
+                return Err(Error::invalid_parameter(format!("vertex attribute {:?} takes {} bytes, not a multiple of 4", attribute, layout.size())));
+            }
+            attributes.push(layout);
+        }
+        attributes.sort_by_key(|layout| (layout.stream, layout.attribute));
+        let stream_count = attributes.iter().map(|layout| layout.stream + 1).max().unwrap_or(0);
+        let mut strides = vec![0; stream_count];
+        for layout in attributes.iter_mut() {
+            layout.offset = strides[layout.stream];
+            strides[layout.stream] += layout.size();
+        }
+        Ok(Self { attributes, strides })
+    }
+
+    pub fn stream_count(&self) -> usize {
+        self.strides.len()
+    }
+
+    pub fn contains(&self, attribute: VertexAttribute) -> bool {
+        self.attributes.iter().any(|layout| layout.attribute == attribute)
+    }
+
+    /// Checks there is a stream of `v_count * stride` bytes for every stream of the layout.
+    pub fn check_streams(&self, streams: &[&mut [u8]], v_count: usize) -> Result<()> {
+        Error::check_len("streams", streams, self.stream_count())?;
+        for (stream, stride) in streams.iter().zip(self.strides.iter()) {
+            Error::check_len("stream", stream, v_count * stride)?;
+        }
+        Ok(())
+    }
+
+    /// Writes vertex `i` from `sources(i)` for the first `v_count` vertices of every stream.
+    pub fn write<F>(&self, v_count: usize, streams: &mut [&mut [u8]], sources: F) where F: Fn(usize) -> VertexSources + Sync {
+        for (s, (stream, stride)) in streams.iter_mut().zip(self.strides.iter()).enumerate() {
+            if *stride == 0 {
+                continue;
+            }
+            stream[..v_count * stride].par_chunks_mut(*stride).enumerate().for_each(|(i, vert)| {
+                let source = sources(i);
+                for layout in self.attributes.iter().filter(|layout| layout.stream == s) {
+                    let values = source.components(layout.attribute);
+                    let size = layout.format.size();
+                    for (k, value) in values[..layout.dimension].iter().enumerate() {
+                        layout.format.write(*value, &mut vert[layout.offset + k * size..]);
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod layout_tests {
+    use half::f16;
+    use crate::error::Error;
+    use super::{VertexAttribute, VertexAttributeDesc, VertexFormat, VertexLayout, VertexSources};
+
+    #[test]
+    fn test_offsets_follow_unity() {
+        let layout = VertexLayout::build(&[
+            VertexAttributeDesc::build(VertexAttribute::Uv0, VertexFormat::Float16, 2, 0),
+            VertexAttributeDesc::build(VertexAttribute::Position, VertexFormat::Float32, 3, 0),
+            VertexAttributeDesc::build(VertexAttribute::Color, VertexFormat::UNorm8, 4, 1),
+            VertexAttributeDesc::build(VertexAttribute::Normal, VertexFormat::SNorm8, 4, 0),
+        ]).unwrap();
+        assert_eq!(layout.strides, vec![20, 4]);
+        let offsets: Vec<(VertexAttribute, usize)> = layout.attributes.iter().map(|a| (a.attribute, a.offset)).collect();
+        assert_eq!(offsets, vec![(VertexAttribute::Position, 0), (VertexAttribute::Normal, 12), (VertexAttribute::Uv0, 16), (VertexAttribute::Color, 0)]);
+        assert!(layout.contains(VertexAttribute::Color) && !layout.contains(VertexAttribute::Tangent));
+    }
+
+    #[test]
+    fn test_encodes_formats() {
+        let layout = VertexLayout::build(&[
+            VertexAttributeDesc::build(VertexAttribute::Position, VertexFormat::Float32, 3, 0),
+            VertexAttributeDesc::build(VertexAttribute::Normal, VertexFormat::SNorm8, 4, 0),
+            VertexAttributeDesc::build(VertexAttribute::Uv0, VertexFormat::Float16, 2, 0),
+            VertexAttributeDesc::build(VertexAttribute::Color, VertexFormat::UNorm8, 4, 1),
+        ]).unwrap();
+        let mut source = VertexSources::default();
+        source.vertex.pos.y = 2.5;
+        source.vertex.norm.y = -1.0;
+        source.vertex.uv.u = 0.25;
+        source.color.g = 200;
+        let (mut first, mut second) = (vec![0_u8; 40], vec![0_u8; 8]);
+        layout.write(2, &mut [&mut first[..], &mut second[..]], |_| source);
+        assert_eq!(&first[4..8], &2.5_f32.to_le_bytes());
+        assert_eq!(first[13] as i8, -127);
+        assert_eq!(&first[16..18], &f16::from_f32(0.25).to_le_bytes());
+        assert_eq!(&first[20..24], &first[..4]);
+        assert_eq!(second, vec![0, 200, 0, 0, 0, 200, 0, 0]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let reject = |descs: &[VertexAttributeDesc]| matches!(VertexLayout::build(descs), Err(Error::InvalidParameter(_)));
+        let position = VertexAttributeDesc::build(VertexAttribute::Position, VertexFormat::Float32, 3, 0);
+        assert!(reject(&[position, position]));
+        assert!(reject(&[VertexAttributeDesc{ attribute: 7, ..position }]));
+        assert!(reject(&[VertexAttributeDesc{ format: 4, ..position }]));
+        assert!(reject(&[VertexAttributeDesc{ dimension: 5, ..position }]));
+        assert!(reject(&[VertexAttributeDesc{ stream: 4, ..position }]));
+        // 3 bytes can't be aligned
+        assert!(reject(&[VertexAttributeDesc::build(VertexAttribute::Color, VertexFormat::UNorm8, 3, 0)]));
+        assert_eq!(VertexLayout::build(&[]).unwrap().stream_count(), 0);
+    }
+}
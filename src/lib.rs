@@ -1,10 +1,5 @@
 extern crate more_asserts;
-extern crate simplelog;
 
-
-use log::{info, error};
-use simplelog::*;
-use std::fs::File;
 #[path = "noisegen.rs"]
 mod noisegen;
 #[path = "unity.rs"]
@@ -15,20 +10,63 @@ mod chunkgen;
 mod texturegen;
 #[path = "gradient.rs"]
 mod gradient;
+#[path = "noisegraph.rs"]
+pub mod noisegraph;
+#[path = "heightmap.rs"]
+pub mod heightmap;
+#[path = "erosion.rs"]
+pub mod erosion;
+#[path = "gltf.rs"]
+pub mod gltf;
+#[path = "obj.rs"]
+pub mod obj;
+#[path = "ply.rs"]
+pub mod ply;
+#[path = "preset.rs"]
+pub mod preset;
+#[path = "error.rs"]
+pub mod error;
+#[path = "logger.rs"]
+pub mod logger;
+#[path = "streaming.rs"]
+pub mod streaming;
+#[path = "biome.rs"]
+pub mod biome;
+#[path = "rivers.rs"]
+pub mod rivers;
+#[path = "analysis.rs"]
+pub mod analysis;
+#[path = "layout.rs"]
+pub mod layout;
+#[path = "quadtree.rs"]
+pub mod quadtree;
+#[cfg(feature = "ffi")]
+#[path = "ffi.rs"]
+pub mod ffi;
 
 // re-export module ffi
-pub use chunkgen::{get_mountainous_terrain_chunkgen, free_mountainous_terrain_chunkgen, fill_mountainous_terrain_chunk};
-pub use texturegen::{get_mountainous_terrain_texturegen, free_mountainous_terrain_texturegen, fill_mountainous_terrain_texture_2d};
-use noisegen::*;
+#[cfg(feature = "ffi")]
+pub use ffi::{get_mountainous_terrain_chunkgen, free_mountainous_terrain_chunkgen, fill_mountainous_terrain_chunk, fill_mountainous_terrain_chunk_with_normals};
+#[cfg(feature = "ffi")]
+pub use ffi::{get_mountainous_terrain_texturegen, free_mountainous_terrain_texturegen, fill_mountainous_terrain_texture_2d};
+#[cfg(feature = "ffi")]
+pub use ffi::{MeshgenError, meshgen_last_error_message, meshgen_free_error_message};
+#[cfg(feature = "ffi")]
+pub use ffi::{init_logger, init_logger_with_callback, set_logger_module_level, shutdown_logger};
+pub use chunkgen::{ChunkGen2D, NoiseGraphChunkGen, BiomeChunkGen};
+pub use texturegen::{TextureGen2D, NoiseGraphTextureGen, BiomeTextureGen};
 
-#[no_mangle]
-pub extern "C" fn init_logger() {
-    match CombinedLogger::init(
-        vec![
-            WriteLogger::new(LevelFilter::Info, Config::default(), File::create(r"C:\Users\ashtr\meshgen\meshgen.log").unwrap()),
-        ]
-    ) {
-        Ok(_) => info!("logger initialized"),
-        Err(_) => error!("logger already initialized")
-    }
-}
+// generator types for rust users such as the meshgen binary
+pub use noisegen::{MountainousTerrainNoise, MountainousTerrainNoiseParams, Noise2D, FractalType, BaseNoiseType, WorleyDistance, WorleyReturn};
+pub use chunkgen::{MountainousTerrainChunkGen, MountainousTerrainChunkGenBuilder, ChunkBuffers, ChunkGeometryDesc};
+pub use texturegen::{MountainousTerrainTextureGen, MountainousTerrainTextureGenBuilder, write_png};
+pub use gradient::{BlendType, ColorKey, ColorKeyGradient};
+pub use streaming::{ChunkCoord, ChunkStreamer};
+pub use biome::{Biome, BiomeBlend, BiomeMap, ClimateNoiseParams};
+pub use rivers::{River, RiverGen, RiverNetwork, RiverPoint};
+pub use analysis::{TerrainAnalysis, TerrainAttribute};
+pub use layout::{VertexAttribute, VertexAttributeDesc, VertexFormat, VertexLayout};
+pub use quadtree::{NodeId, Quadtree, QuadtreeNode, QuadtreeParams};
+pub use heightmap::{NormalFilter, NormalMap, NormalSpace, UvMapping, UvMode};
+pub use unity::{Color32, Normal16, Normal32, Position2D32, Position2D64, Position3D32, Position3D64, Quad, SimpleVertex, Tangent32, TangentU8, TexCoord32, TriplanarWeights32, Vertex};
+pub use error::{Error, Result};
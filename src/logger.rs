@@ -0,0 +1,203 @@
+use std::{ffi::CString, fs::File, os::raw::c_char, path::Path, sync::{OnceLock, RwLock}};
+use log::{LevelFilter, Log, Metadata, Record};
+use simplelog::{Config, WriteLogger};
+
+use crate::error::{Error, Result};
+
+/// Host function receiving log records, `level` is 1 error, 2 warn, 3 info, 4 debug or 5 trace.
+/// `target` is the module the record comes from, e.g. `meshgen::erosion`. Both strings are only
+/// valid for the duration of the call. The callback can be called from any thread and must not
+/// call back into meshgen.
+pub type LogCallback = extern "C" fn(level: u32, target: *const c_char, message: *const c_char);
+
+enum Sink {
+    File(Box<WriteLogger<File>>),
+    Callback(LogCallback),
+}
+
+struct LoggerConfig {
+    level: LevelFilter,
+    /// overrides of `level` for a module and everything below it
+    module_levels: Vec<(String, LevelFilter)>,
+    sink: Option<Sink>,
+}
+
+impl LoggerConfig {
+    fn level_for(&self, target: &str) -> LevelFilter {
+        // the longest matching module wins
+        self.module_levels.iter()
+            .filter(|(module, _)| target == module || target.strip_prefix(module.as_str()).is_some_and(|rest| rest.starts_with("::")))
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.module_levels.iter().map(|(_, level)| *level).fold(self.level, Ord::max)
+    }
+}
+
+/// The `log` logger of the library, installed by the first `init_*` call and reconfigured by later ones.
+struct HostLogger {
+    config: RwLock<LoggerConfig>,
+}
+
+static LOGGER: HostLogger = HostLogger {
+    config: RwLock::new(LoggerConfig { level: LevelFilter::Info, module_levels: Vec::new(), sink: None }),
+};
+
+/// whether `LOGGER` is the logger of the process, false if the host installed another one first
+static INSTALLED: OnceLock<bool> = OnceLock::new();
+
+impl Log for HostLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        match self.config.read() {
+            Ok(config) => config.sink.is_some() && metadata.level() <= config.level_for(metadata.target()),
+            Err(_) => false,
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if let Ok(config) = self.config.read() {
+            match &config.sink {
+                Some(Sink::File(logger)) => logger.log(record),
+                Some(Sink::Callback(callback)) => {
+                    let target = CString::new(record.target().replace('\0', " ")).unwrap_or_default();
+                    let message = CString::new(record.args().to_string().replace('\0', " ")).unwrap_or_default();
+                    callback(record.level() as u32, target.as_ptr(), message.as_ptr());
+                },
+                None => (),
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(config) = self.config.read() {
+            if let Some(Sink::File(logger)) = &config.sink {
+                logger.flush();
+            }
+        }
+    }
+}
+
+fn configure<F: FnOnce(&mut LoggerConfig)>(f: F) -> Result<()> {
+    let installed = *INSTALLED.get_or_init(|| log::set_logger(&LOGGER).is_ok());
+    if !installed {
+        return Err(Error::Logger("another logger is already installed in this process".to_string()));
+    }
+    let mut config = LOGGER.config.write().map_err(|_| Error::Logger("logger configuration is poisoned".to_string()))?;
+    f(&mut config);
+    log::set_max_level(match config.sink {
+        Some(_) => config.max_level(),
+        None => LevelFilter::Off,
+    });
+    Ok(())
+}
+
+/// Appends the log to `path`, records more verbose than `level` are dropped unless a module level allows them.
+pub fn init_file<P: AsRef<Path>>(path: P, level: LevelFilter) -> Result<()> {
+    let file = File::options().create(true).append(true).open(path.as_ref())
+        .map_err(|err| Error::Logger(format!("failed to open log file {}: {}", path.as_ref().display(), err)))?;
+    configure(|config| {
+        config.level = level;
+        config.sink = Some(Sink::File(WriteLogger::new(LevelFilter::Trace, Config::default(), file)));
+    })
+}
+
+/// Hands every record up to `level` to `callback` instead of a file.
+pub fn init_callback(callback: LogCallback, level: LevelFilter) -> Result<()> {
+    configure(|config| {
+        config.level = level;
+        config.sink = Some(Sink::Callback(callback));
+    })
+}
+
+/// Overrides the level for `module` and its submodules, e.g. `meshgen::erosion`.
+pub fn set_module_level(module: &str, level: LevelFilter) -> Result<()> {
+    configure(|config| {
+        config.module_levels.retain(|(other, _)| other != module);
+        config.module_levels.push((module.to_string(), level));
+    })
+}
+
+/// Drops the sink, nothing is logged until the next `init_*` call.
+pub fn shutdown() -> Result<()> {
+    configure(|config| {
+        if let Some(Sink::File(logger)) = &config.sink {
+            logger.flush();
+        }
+        config.sink = None;
+    })
+}
+
+#[cfg(test)]
+mod logger_tests {
+    use std::{ffi::CStr, fs, os::raw::c_char, sync::Mutex};
+    use log::LevelFilter;
+    use crate::error::Error;
+    use super::{init_callback, init_file, set_module_level, shutdown, LoggerConfig};
+
+    static RECORDS: Mutex<Vec<(u32, String, String)>> = Mutex::new(Vec::new());
+
+    extern "C" fn record(level: u32, target: *const c_char, message: *const c_char) {
+        let (target, message) = unsafe { (CStr::from_ptr(target), CStr::from_ptr(message)) };
+        let message = message.to_str().unwrap().to_string();
+        // other tests log from their own threads while the callback is installed
+        if message.starts_with("logger test") {
+            RECORDS.lock().unwrap().push((level, target.to_str().unwrap().to_string(), message));
+        }
+    }
+
+    #[test]
+    fn test_module_levels() {
+        let config = LoggerConfig {
+            level: LevelFilter::Warn,
+            module_levels: vec![("meshgen::erosion".to_string(), LevelFilter::Trace), ("meshgen::erosion::thermal".to_string(), LevelFilter::Off)],
+            sink: None,
+        };
+        assert_eq!(config.level_for("meshgen"), LevelFilter::Warn);
+        assert_eq!(config.level_for("meshgen::erosion"), LevelFilter::Trace);
+        assert_eq!(config.level_for("meshgen::erosion::hydraulic"), LevelFilter::Trace);
+        assert_eq!(config.level_for("meshgen::erosion::thermal"), LevelFilter::Off);
+        assert_eq!(config.level_for("meshgen::erosionx"), LevelFilter::Warn);
+        assert_eq!(config.max_level(), LevelFilter::Trace);
+    }
+
+    // the logger is global to the process, so the sinks are tested one after the other
+    #[test]
+    fn test_file_and_callback_sinks() {
+        let dir = std::env::temp_dir().join(format!("meshgen_logger_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("meshgen.log");
+        assert!(matches!(init_file(dir.join("missing").join("meshgen.log"), LevelFilter::Info), Err(Error::Logger(_))));
+
+        init_file(&path, LevelFilter::Info).unwrap();
+        log::info!(target: "meshgen::chunkgen", "logger test file info");
+        log::debug!(target: "meshgen::chunkgen", "logger test file debug");
+        log::logger().flush();
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.contains("logger test file info"));
+        assert!(!written.contains("logger test file debug"));
+
+        init_callback(record, LevelFilter::Warn).unwrap();
+        set_module_level("meshgen::erosion", LevelFilter::Debug).unwrap();
+        log::info!(target: "meshgen::chunkgen", "logger test dropped");
+        log::warn!(target: "meshgen::chunkgen", "logger test warn");
+        log::debug!(target: "meshgen::erosion", "logger test erosion debug");
+        log::trace!(target: "meshgen::erosion", "logger test erosion trace");
+        shutdown().unwrap();
+        log::error!(target: "meshgen::chunkgen", "logger test after shutdown");
+
+        let records = RECORDS.lock().unwrap().clone();
+        assert_eq!(records, vec![
+            (2, "meshgen::chunkgen".to_string(), "logger test warn".to_string()),
+            (4, "meshgen::erosion".to_string(), "logger test erosion debug".to_string()),
+        ]);
+        // the file sink was replaced by the callback
+        assert!(!fs::read_to_string(&path).unwrap().contains("logger test warn"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,17 +1,233 @@
 use log::info;
 use noise::{NoiseFn, Seedable};
-use crate::unity::{Position2D32, Lerp};
+use crate::{error::{Error, Result}, unity::{Position2D32, Position2D64, Lerp}};
 use rand::{Rng, SeedableRng};
 use rand::rngs::{SmallRng};
 use lyon_geom::CubicBezierSegment;
+use serde::{Deserialize, Serialize};
 
+#[allow(dead_code)]
 struct Flat {}
 impl<T> NoiseFn<T> for Flat {
     fn get(&self, _point: T) -> f64 {0f64}
 }
 
+/// How the octaves of a `Noise2D` are combined, every type is normalized to [0, 1] before the bias and gain curves
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FractalType {
+    /// sum of (n + 1) / 2
+    #[default]
+    Fbm,
+    /// sum of (1 - |n|)^2, each octave weighted by the previous one
+    Ridged,
+    /// sum of |n|
+    Billow,
+    /// sum of (n + 1) / 2, each octave weighted by the product of the previous ones
+    HybridMulti,
+}
+
+impl From<u32> for FractalType {
+    fn from(fractal_type: u32) -> Self {
+        match fractal_type {
+            1 => FractalType::Ridged,
+            2 => FractalType::Billow,
+            3 => FractalType::HybridMulti,
+            _ => FractalType::Fbm,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorleyDistance {
+    #[default]
+    Euclidean,
+    EuclideanSquared,
+    Manhattan,
+    Chebyshev,
+    Quadratic,
+}
+
+impl From<u32> for WorleyDistance {
+    fn from(distance: u32) -> Self {
+        match distance {
+            1 => WorleyDistance::EuclideanSquared,
+            2 => WorleyDistance::Manhattan,
+            3 => WorleyDistance::Chebyshev,
+            4 => WorleyDistance::Quadratic,
+            _ => WorleyDistance::Euclidean,
+        }
+    }
+}
+
+impl WorleyDistance {
+    /// Distance to the closest seed point that 99% of samples stay under, in cells. Distances are
+    /// divided by it so every distance function spans the same -1..1 as perlin noise.
+    fn range_bound(&self) -> f64 {
+        match self {
+            WorleyDistance::Euclidean => 0.8,
+            WorleyDistance::EuclideanSquared => 0.64,
+            WorleyDistance::Manhattan => 1.05,
+            WorleyDistance::Chebyshev => 0.72,
+            WorleyDistance::Quadratic => 0.48,
+        }
+    }
+}
+
+impl From<WorleyDistance> for noise::RangeFunction {
+    fn from(distance: WorleyDistance) -> Self {
+        match distance {
+            WorleyDistance::Euclidean => noise::RangeFunction::Euclidean,
+            WorleyDistance::EuclideanSquared => noise::RangeFunction::EuclideanSquared,
+            WorleyDistance::Manhattan => noise::RangeFunction::Manhattan,
+            WorleyDistance::Chebyshev => noise::RangeFunction::Chebyshev,
+            WorleyDistance::Quadratic => noise::RangeFunction::Quadratic,
+        }
+    }
+}
+
+/// What a worley sample returns, the random value of the closest cell or the distance to its seed point
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorleyReturn {
+    #[default]
+    CellValue,
+    Distance,
+}
+
+impl From<u32> for WorleyReturn {
+    fn from(return_type: u32) -> Self {
+        match return_type {
+            1 => WorleyReturn::Distance,
+            _ => WorleyReturn::CellValue,
+        }
+    }
+}
+
+/// The coherent noise every octave of a `MountainousTerrainNoise` samples
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(from = "BaseNoiseDesc", into = "BaseNoiseDesc")]
+pub enum BaseNoiseType {
+    #[default]
+    Perlin,
+    OpenSimplex,
+    SuperSimplex,
+    Value,
+    Worley(WorleyDistance, WorleyReturn),
+}
+
+impl BaseNoiseType {
+    /// decodes the ffi arguments, the worley arguments are ignored for the other types
+    pub fn build(base_noise: u32, worley_distance: u32, worley_return: u32) -> Self {
+        match base_noise {
+            1 => BaseNoiseType::OpenSimplex,
+            2 => BaseNoiseType::SuperSimplex,
+            3 => BaseNoiseType::Value,
+            4 => BaseNoiseType::Worley(worley_distance.into(), worley_return.into()),
+            _ => BaseNoiseType::Perlin,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum BaseNoiseKind {
+    #[default]
+    Perlin,
+    OpenSimplex,
+    SuperSimplex,
+    Value,
+    Worley,
+}
+
+/// Flat preset form of `BaseNoiseType`, the worley fields are ignored for the other types
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct BaseNoiseDesc {
+    noise: BaseNoiseKind,
+    worley_distance: WorleyDistance,
+    worley_return: WorleyReturn,
+}
+
+impl From<BaseNoiseDesc> for BaseNoiseType {
+    fn from(desc: BaseNoiseDesc) -> Self {
+        match desc.noise {
+            BaseNoiseKind::Perlin => BaseNoiseType::Perlin,
+            BaseNoiseKind::OpenSimplex => BaseNoiseType::OpenSimplex,
+            BaseNoiseKind::SuperSimplex => BaseNoiseType::SuperSimplex,
+            BaseNoiseKind::Value => BaseNoiseType::Value,
+            BaseNoiseKind::Worley => BaseNoiseType::Worley(desc.worley_distance, desc.worley_return),
+        }
+    }
+}
+
+impl From<BaseNoiseType> for BaseNoiseDesc {
+    fn from(noise_type: BaseNoiseType) -> Self {
+        let noise = match noise_type {
+            BaseNoiseType::Perlin => BaseNoiseKind::Perlin,
+            BaseNoiseType::OpenSimplex => BaseNoiseKind::OpenSimplex,
+            BaseNoiseType::SuperSimplex => BaseNoiseKind::SuperSimplex,
+            BaseNoiseType::Value => BaseNoiseKind::Value,
+            BaseNoiseType::Worley(..) => BaseNoiseKind::Worley,
+        };
+        match noise_type {
+            BaseNoiseType::Worley(worley_distance, worley_return) => BaseNoiseDesc{ noise, worley_distance, worley_return },
+            _ => BaseNoiseDesc{ noise, ..Default::default() },
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum BaseNoise {
+    Perlin(noise::Perlin),
+    OpenSimplex(noise::OpenSimplex),
+    SuperSimplex(noise::SuperSimplex),
+    Value(noise::Value),
+    Worley(noise::Worley, WorleyDistance, WorleyReturn),
+}
+
+impl BaseNoise {
+    pub fn build(noise_type: BaseNoiseType, seed: u32) -> Self {
+        match noise_type {
+            BaseNoiseType::Perlin => BaseNoise::Perlin(noise::Perlin::new().set_seed(seed)),
+            BaseNoiseType::OpenSimplex => BaseNoise::OpenSimplex(noise::OpenSimplex::new().set_seed(seed)),
+            BaseNoiseType::SuperSimplex => BaseNoise::SuperSimplex(noise::SuperSimplex::new().set_seed(seed)),
+            BaseNoiseType::Value => BaseNoise::Value(noise::Value::new().set_seed(seed)),
+            BaseNoiseType::Worley(distance, return_type) => BaseNoise::Worley(
+                noise::Worley::new()
+                    .set_seed(seed)
+                    .set_range_function(distance.into())
+                    .enable_range(return_type == WorleyReturn::Distance),
+                distance,
+                return_type,
+            ),
+        }
+    }
+}
+
+impl NoiseFn<[f64; 2]> for BaseNoise {
+    fn get(&self, pos: [f64; 2]) -> f64 {
+        match self {
+            BaseNoise::Perlin(noise) => noise.get(pos),
+            BaseNoise::OpenSimplex(noise) => noise.get(pos),
+            BaseNoise::SuperSimplex(noise) => noise.get(pos),
+            BaseNoise::Value(noise) => noise.get(pos),
+            BaseNoise::Worley(noise, distance, WorleyReturn::Distance) => {
+                // the worley module returns 2 * distance - 1
+                let range = (noise.get(pos) + 1.0) / 2.0;
+                (range / distance.range_bound()).min(1.0) * 2.0 - 1.0
+            },
+            BaseNoise::Worley(noise, _, WorleyReturn::CellValue) => noise.get(pos),
+        }
+    }
+}
+
+/// how strongly a ridged octave feeds its weight into the next one
+const RIDGED_GAIN: f64 = 2.0;
+
 pub trait Noise2D<'a> {
-    fn get(&self, pos: Position2D32) -> f64 {
+    fn get(&self, pos: Position2D64) -> f64 {
         let scale = self.get_scale();
         let frequency = self.get_frequency();
         let octaves = self.get_octaves();
@@ -19,23 +235,54 @@ pub trait Noise2D<'a> {
         let displacement = self.get_displacement();
         let amplitude = self.get_amplitude();
 
-        let my_sum: f64 = (0..octaves)
-            .map(|i: u32| -> f64 {
-                let mut sample_x: f64 = pos.x as f64 / scale * frequency[i as usize] + offsets[i as usize][0];
-                let mut sample_y: f64 = pos.y as f64 / scale * frequency[i as usize] + offsets[i  as usize][1];
-                if displacement > 0.0 {
-                    sample_x += displacement * self.get_noise([offsets[octaves as usize][0] + sample_x, offsets[octaves as usize][1] + sample_y]);
-                    sample_y += displacement * self.get_noise([offsets[(octaves + 1) as usize][0] + sample_x, offsets[(octaves + 1) as usize][1] + sample_y]);
-                }
-                ( ( self.get_noise([sample_x, sample_y]) + 1.0 ) / 2.0 )  * amplitude[i as usize]
-            })
-            .sum();
-            let h_pre = my_sum / self.get_max_noise_sum();
-            let h = self.get_gain(self.get_bezier_bias(h_pre));
-            // info!("{:?}", (h_pre,h));
-            h
+        let sample = |i: u32| -> f64 {
+            let mut sample_x: f64 = pos.x / scale * frequency[i as usize] + offsets[i as usize][0];
+            let mut sample_y: f64 = pos.y / scale * frequency[i as usize] + offsets[i  as usize][1];
+            if displacement > 0.0 {
+                sample_x += displacement * self.get_noise([offsets[octaves as usize][0] + sample_x, offsets[octaves as usize][1] + sample_y]);
+                sample_y += displacement * self.get_noise([offsets[(octaves + 1) as usize][0] + sample_x, offsets[(octaves + 1) as usize][1] + sample_y]);
+            }
+            self.get_noise([sample_x, sample_y])
+        };
+
+        // every octave contributes at most amplitude[i], so the max noise sum normalizes all types
+        let my_sum: f64 = match self.get_fractal_type() {
+            FractalType::Fbm => (0..octaves)
+                .map(|i| ( ( sample(i) + 1.0 ) / 2.0 ) * amplitude[i as usize])
+                .sum(),
+            FractalType::Billow => (0..octaves)
+                .map(|i| sample(i).abs().min(1.0) * amplitude[i as usize])
+                .sum(),
+            FractalType::Ridged => {
+                let mut weight = 1.0;
+                (0..octaves)
+                    .map(|i| {
+                        let signal = (1.0 - sample(i).abs()).max(0.0).powi(2) * weight;
+                        weight = (signal * RIDGED_GAIN).clamp(0.0, 1.0);
+                        signal * amplitude[i as usize]
+                    })
+                    .sum()
+            },
+            FractalType::HybridMulti => {
+                let mut weight = 1.0;
+                (0..octaves)
+                    .map(|i| {
+                        let signal = ( ( sample(i) + 1.0 ) / 2.0 ).clamp(0.0, 1.0);
+                        let h = weight * signal * amplitude[i as usize];
+                        weight = (weight * signal).min(1.0);
+                        h
+                    })
+                    .sum()
+            },
+        };
+        let h_pre = my_sum / self.get_max_noise_sum();
+        self.get_gain(self.get_bezier_bias(h_pre))
+    }
+    fn get_fractal_type(&self) -> FractalType {
+        FractalType::Fbm
     }
     fn get_noise(&self, pos: [f64; 2]) -> f64;
+    #[allow(dead_code)]
     fn get_displacement_noise(&self, pos: [f64; 2]) -> f64;
     fn get_scale(&self) -> f64;
     fn get_offsets(&self) -> &Vec<[f64; 2]>;
@@ -47,12 +294,126 @@ pub trait Noise2D<'a> {
     fn get_bezier_bias(&self, h:f64) -> f64;
     fn get_max_noise_sum(&self) -> f64;
     fn get_octaves(&self) -> u32;
+    #[allow(dead_code)]
     fn get_fast_bias_gain_control_param(&self) -> f64;
 }
 
-#[derive(Clone, Debug)]
+/// The arguments of `MountainousTerrainNoise::build`, the form the noise takes in presets.
+/// Missing fields take the values of `MountainousTerrainNoise::default`.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MountainousTerrainNoiseParams {
+    pub seed: u32,
+    pub scale: f64,
+    pub persistance: f64,
+    pub lacunarity: f64,
+    pub octaves: u32,
+    pub displacement: f64,
+    /// control parameter of the bias and gain curves
+    pub bias_gain: f64,
+    pub bezier_bias_from: Position2D32,
+    pub bezier_bias_to: Position2D32,
+    pub bezier_bias_corner_curvature: f64,
+    pub fractal_type: FractalType,
+    pub base_noise_type: BaseNoiseType,
+}
+
+impl MountainousTerrainNoiseParams {
+    pub fn build(&self) -> MountainousTerrainNoise {
+        MountainousTerrainNoise::build(
+            self.seed,
+            self.scale,
+            self.persistance,
+            self.lacunarity,
+            self.octaves,
+            self.displacement,
+            self.bias_gain,
+            self.bezier_bias_from,
+            self.bezier_bias_to,
+            self.bezier_bias_corner_curvature,
+            self.fractal_type,
+            self.base_noise_type,
+        )
+    }
+
+    /// `build` after `validate`
+    pub fn try_build(&self) -> Result<MountainousTerrainNoise> {
+        self.validate()?;
+        Ok(self.build())
+    }
+
+    /// Checks for parameters `build` would turn into NaN or infinite heights.
+    pub fn validate(&self) -> Result<()> {
+        let finite = [
+            ("scale", self.scale),
+            ("persistance", self.persistance),
+            ("lacunarity", self.lacunarity),
+            ("displacement", self.displacement),
+            ("bias_gain", self.bias_gain),
+            ("bezier_bias_corner_curvature", self.bezier_bias_corner_curvature),
+            ("bezier_bias_from.x", self.bezier_bias_from.x as f64),
+            ("bezier_bias_from.y", self.bezier_bias_from.y as f64),
+            ("bezier_bias_to.x", self.bezier_bias_to.x as f64),
+            ("bezier_bias_to.y", self.bezier_bias_to.y as f64),
+        ];
+        if let Some((name, value)) = finite.iter().find(|(_, value)| !value.is_finite()) {
+            return Err(Error::invalid_parameter(format!("{} must be finite, got {}", name, value)));
+        }
+        if self.scale == 0.0 {
+            return Err(Error::invalid_parameter("scale must not be zero"));
+        }
+        if self.octaves < 1 {
+            return Err(Error::invalid_parameter("octaves must be at least 1"));
+        }
+        if self.persistance < 0.0 {
+            return Err(Error::invalid_parameter(format!("persistance must not be negative, got {}", self.persistance)));
+        }
+        // get_bias divides by the control parameter and its complement
+        if self.bias_gain <= 0.0 || self.bias_gain >= 1.0 {
+            return Err(Error::invalid_parameter(format!("bias_gain must be in (0, 1), got {}", self.bias_gain)));
+        }
+        // the bezier corner is where the line through the origin and `from` meets the line through `to` and (1, 1)
+        let (from, to) = (self.bezier_bias_from, self.bezier_bias_to);
+        if !(from.x > 0.0 && from.x < to.x && to.x < 1.0) {
+            return Err(Error::invalid_parameter(format!("bezier bias points need 0 < from.x < to.x < 1, got from.x {} and to.x {}", from.x, to.x)));
+        }
+        let m_from = from.y / from.x;
+        let m_to = (1.0 - to.y) / (1.0 - to.x);
+        let c_x = (-m_to * to.x + to.y) / (m_from - m_to);
+        if !(c_x * m_from).is_finite() {
+            return Err(Error::invalid_parameter(format!("bezier bias points {:?} and {:?} give parallel slopes", from, to)));
+        }
+        Ok(())
+    }
+}
+
+impl Default for MountainousTerrainNoiseParams {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            scale: 50.0,
+            persistance: 0.5,
+            lacunarity: 0.2,
+            octaves: 3,
+            displacement: -1.0,
+            bias_gain: 0.3,
+            bezier_bias_from: Position2D32{x: 0.4, y: 0.0},
+            bezier_bias_to: Position2D32{x: 0.5, y: 0.1},
+            bezier_bias_corner_curvature: 0.5,
+            fractal_type: FractalType::Fbm,
+            base_noise_type: BaseNoiseType::Perlin,
+        }
+    }
+}
+
+/// Serialized through `MountainousTerrainNoiseParams`, the derived tables are rebuilt on load.
+#[allow(dead_code)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "MountainousTerrainNoiseParams", into = "MountainousTerrainNoiseParams")]
 pub struct MountainousTerrainNoise {
-    pub(crate) noise: noise::Perlin,
+    pub(crate) noise: BaseNoise,
+    pub(crate) base_noise_type: BaseNoiseType,
+    noise_seed: u32,
     amplitude: Vec<f64>,
     frequency: Vec<f64>,
     offsets: Vec<[f64; 2]>,
@@ -65,15 +426,26 @@ pub struct MountainousTerrainNoise {
     pub(crate) lacunarity: f64,
     pub(crate) fast_bias_gain_control_param: f64,
     pub(crate) bezier_bias: CubicBezierSegment<f32>,
+    pub(crate) bezier_bias_corner_curvature: f64,
+    pub(crate) fractal_type: FractalType,
 }
 
 
+/// Perlin noise keeps its default seed, older versions dropped the drawn one and existing terrain depends on it.
+fn base_noise_seed(base_noise_type: BaseNoiseType, noise_seed: u32) -> u32 {
+    match base_noise_type {
+        BaseNoiseType::Perlin => noise::Perlin::DEFAULT_SEED,
+        _ => noise_seed,
+    }
+}
+
 impl MountainousTerrainNoise {
-    pub fn build(seed: u32, scale: f64, persistance: f64, lacunarity: f64, octaves: u32, displacement: f64, a: f64, bezier_bias_from: Position2D32, bezier_bias_to: Position2D32, bezier_bias_corner_curvature: f64) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn build(seed: u32, scale: f64, persistance: f64, lacunarity: f64, octaves: u32, displacement: f64, a: f64, bezier_bias_from: Position2D32, bezier_bias_to: Position2D32, bezier_bias_corner_curvature: f64, fractal_type: FractalType, base_noise_type: BaseNoiseType) -> Self {
         let mut small_rng = SmallRng::seed_from_u64(seed as u64);
 
-        let noise = noise::Perlin::new();
-        noise.set_seed(small_rng.gen());
+        let noise_seed: u32 = small_rng.gen();
+        let noise = BaseNoise::build(base_noise_type, base_noise_seed(base_noise_type, noise_seed));
         
         
         let m_from = bezier_bias_from.y / bezier_bias_from.x;
@@ -89,6 +461,8 @@ impl MountainousTerrainNoise {
         info!("{:?}", CubicBezierSegment{from: bezier_bias_from.into(), to: bezier_bias_to.into(), ctrl1: ctrl1.into(), ctrl2: ctrl2.into()});
         Self {
             noise,
+            base_noise_type,
+            noise_seed,
             seed,
             scale,
             octaves,
@@ -104,29 +478,59 @@ impl MountainousTerrainNoise {
                 ]}).collect(),
             displacement,
             fast_bias_gain_control_param: a,
-            bezier_bias: CubicBezierSegment{from: bezier_bias_from.into(), to: bezier_bias_to.into(), ctrl1: ctrl1.into(), ctrl2: ctrl2.into()}
+            bezier_bias: CubicBezierSegment{from: bezier_bias_from.into(), to: bezier_bias_to.into(), ctrl1: ctrl1.into(), ctrl2: ctrl2.into()},
+            bezier_bias_corner_curvature,
+            fractal_type,
+        }
+    }
+
+    pub fn params(&self) -> MountainousTerrainNoiseParams {
+        MountainousTerrainNoiseParams {
+            seed: self.seed,
+            scale: self.scale,
+            persistance: self.persistance,
+            lacunarity: self.lacunarity,
+            octaves: self.octaves,
+            displacement: self.displacement,
+            bias_gain: self.fast_bias_gain_control_param,
+            bezier_bias_from: Position2D32{x: self.bezier_bias.from.x, y: self.bezier_bias.from.y},
+            bezier_bias_to: Position2D32{x: self.bezier_bias.to.x, y: self.bezier_bias.to.y},
+            bezier_bias_corner_curvature: self.bezier_bias_corner_curvature,
+            fractal_type: self.fractal_type,
+            base_noise_type: self.base_noise_type,
         }
     }
+
+    /// Swaps the base noise, the generator seed is the one `build` drew so offsets stay the same.
+    pub fn set_base_noise(&mut self, base_noise_type: BaseNoiseType) {
+        self.base_noise_type = base_noise_type;
+        self.noise = BaseNoise::build(base_noise_type, base_noise_seed(base_noise_type, self.noise_seed));
+    }
 }
 
 impl Default for MountainousTerrainNoise {
     fn default() -> Self {
-        MountainousTerrainNoise::build(
-            0,
-            50.0,
-            0.5,
-            0.2,
-            3,
-            -1.0,
-            0.3,
-            Position2D32{x: 0.4, y: 0.0},
-            Position2D32{x: 0.5, y:0.1},
-            0.5
-        )
+        MountainousTerrainNoiseParams::default().build()
+    }
+}
+
+impl From<MountainousTerrainNoiseParams> for MountainousTerrainNoise {
+    fn from(params: MountainousTerrainNoiseParams) -> Self {
+        params.build()
     }
 }
 
-impl<'a> Noise2D<'_> for MountainousTerrainNoise {
+impl From<MountainousTerrainNoise> for MountainousTerrainNoiseParams {
+    fn from(noise: MountainousTerrainNoise) -> Self {
+        noise.params()
+    }
+}
+
+impl Noise2D<'_> for MountainousTerrainNoise {
+    fn get_fractal_type(&self) -> FractalType {
+        self.fractal_type
+    }
+
     fn get_bias(&self, h: f64, a: f64) -> f64 {
         h / ( (1.0 / a - 2.0) * (1.0 - h) + 1.0 )
     }
@@ -196,11 +600,12 @@ impl<'a> Noise2D<'_> for MountainousTerrainNoise {
 #[cfg(test)]
 mod noise_tests {
     use more_asserts::{assert_ge, assert_le};
-    use noise::NoiseFn;
 
-    use crate::unity::Position2D32;
+    use crate::unity::{Position2D32, Position2D64};
+
+    use noise::NoiseFn;
 
-    use super::{MountainousTerrainNoise, Noise2D};
+    use super::{BaseNoise, BaseNoiseType, FractalType, MountainousTerrainNoise, MountainousTerrainNoiseParams, Noise2D, WorleyDistance, WorleyReturn};
 
     #[test]
     fn it_works() {
@@ -209,22 +614,18 @@ mod noise_tests {
 
     #[test]
     fn test_perlin_magnitude() {
-        let scale = 10.0;
         let height = 1000;
         let width = 100;
-        let noise = noise::Perlin::new();
         let octaves = 1;
         let persistance = 0.1;
-        let amplitude: Vec<f64> = (0..octaves).map(|i| { f64::powf(persistance, i.into()) }).collect();
         let projected_max: f64 = (0..octaves).map(|i| { f64::powf(persistance, i.into()) }).sum();
-        let a = 0.9;
         let my_noise = MountainousTerrainNoise::default();
         let mut max = 0_f64;
         let mut min = 1_f64;
         let mut sum = 0_f64;
         let mut count_low_5 = 0;
         for p in 0..(width * height) {
-            let h = my_noise.get(Position2D32{x: (p % width) as f32, y: (p / width) as f32});           
+            let h = my_noise.get(Position2D64{x: (p % width) as f64, y: (p / width) as f64});           
             // let h = noise.get([(i % width) as f64 / scale as f64, (i / height) as f64 / scale as f64]);
             max = f64::max(h, max);
             min = f64::min(h, min);
@@ -248,4 +649,93 @@ mod noise_tests {
         let bias_out: Vec<(f64, f64)> = my_range.map(|i| {(i as f64 / 20.0, my_noise.get_bezier_bias(i as f64 / 20.0))}).collect();
         println!("{:?}", bias_out);
     }
+
+    #[test]
+    fn test_fractal_type_range() {
+        for fractal_type in [FractalType::Fbm, FractalType::Ridged, FractalType::Billow, FractalType::HybridMulti].iter() {
+            let my_noise = MountainousTerrainNoise::build(7, 20.0, 0.5, 2.0, 5, 0.5, 0.5, Position2D32{x: 0.4, y: 0.1}, Position2D32{x: 0.6, y: 0.5}, 0.5, *fractal_type, BaseNoiseType::Perlin);
+            for p in 0..10000 {
+                let h = my_noise.get(Position2D64{x: (p % 100) as f64 * 0.7, y: (p / 100) as f64 * 0.7});
+                assert_ge!(h, 0.0);
+                assert_le!(h, 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_base_noise_types() {
+        let base_noise_types = [
+            BaseNoiseType::Perlin,
+            BaseNoiseType::OpenSimplex,
+            BaseNoiseType::SuperSimplex,
+            BaseNoiseType::Value,
+            BaseNoiseType::Worley(WorleyDistance::Euclidean, WorleyReturn::CellValue),
+            BaseNoiseType::Worley(WorleyDistance::Manhattan, WorleyReturn::Distance),
+        ];
+        let perlin = MountainousTerrainNoise::build(3, 20.0, 0.5, 2.0, 4, -1.0, 0.5, Position2D32{x: 0.4, y: 0.1}, Position2D32{x: 0.6, y: 0.5}, 0.5, FractalType::Fbm, BaseNoiseType::Perlin);
+        for base_noise_type in base_noise_types.iter() {
+            let mut my_noise = perlin.clone();
+            my_noise.set_base_noise(*base_noise_type);
+            assert_eq!(my_noise.get_offsets(), perlin.get_offsets());
+            let mut differs = false;
+            for p in 0..2500 {
+                let pos = Position2D64{x: (p % 50) as f64 * 1.3, y: (p / 50) as f64 * 1.3};
+                let h = my_noise.get(pos);
+                assert!(h.is_finite());
+                differs |= h != perlin.get(pos);
+            }
+            assert_eq!(differs, *base_noise_type != BaseNoiseType::Perlin);
+        }
+    }
+
+    #[test]
+    fn test_perlin_keeps_default_seed() {
+        // the drawn seed never reached perlin before base noise types existed, terrain must not change
+        let perlin = noise::Perlin::new();
+        for seed in [0, 7, 4242].iter() {
+            let my_noise = MountainousTerrainNoise::build(*seed, 20.0, 0.5, 2.0, 4, -1.0, 0.5, Position2D32{x: 0.4, y: 0.1}, Position2D32{x: 0.6, y: 0.5}, 0.5, FractalType::Fbm, BaseNoiseType::Perlin);
+            for p in 0..100 {
+                let pos = [p as f64 * 0.37, p as f64 * -0.21];
+                assert_eq!(my_noise.noise.get(pos), perlin.get(pos));
+            }
+        }
+    }
+
+    #[test]
+    fn test_worley_distance_spread() {
+        let distances = [WorleyDistance::Euclidean, WorleyDistance::EuclideanSquared, WorleyDistance::Manhattan, WorleyDistance::Chebyshev, WorleyDistance::Quadratic];
+        for distance in distances.iter() {
+            let noise = BaseNoise::build(BaseNoiseType::Worley(*distance, WorleyReturn::Distance), 11);
+            let (mut min, mut max) = (f64::MAX, f64::MIN);
+            for p in 0..40000 {
+                let v = noise.get([(p % 200) as f64 * 0.173, (p / 200) as f64 * 0.131]);
+                min = min.min(v);
+                max = max.max(v);
+            }
+            // the same -1..1 as perlin
+            assert_ge!(min, -1.0, "{:?}", distance);
+            assert_le!(max, 1.0, "{:?}", distance);
+            assert_le!(min, -0.95, "{:?}", distance);
+            assert_ge!(max, 0.95, "{:?}", distance);
+        }
+    }
+
+    #[test]
+    fn test_params_validate() {
+        let defaults = MountainousTerrainNoiseParams::default();
+        assert!(defaults.validate().is_ok());
+        let invalid = [
+            MountainousTerrainNoiseParams{scale: 0.0, ..defaults},
+            MountainousTerrainNoiseParams{scale: f64::INFINITY, ..defaults},
+            MountainousTerrainNoiseParams{octaves: 0, ..defaults},
+            MountainousTerrainNoiseParams{bias_gain: 1.0, ..defaults},
+            MountainousTerrainNoiseParams{bezier_bias_from: Position2D32{x: 0.0, y: 0.1}, ..defaults},
+            MountainousTerrainNoiseParams{bezier_bias_to: Position2D32{x: 1.0, y: 0.1}, ..defaults},
+            // both points on the diagonal, the corner lines never meet
+            MountainousTerrainNoiseParams{bezier_bias_from: Position2D32{x: 0.2, y: 0.2}, bezier_bias_to: Position2D32{x: 0.6, y: 0.6}, ..defaults},
+        ];
+        for params in invalid.iter() {
+            assert!(params.validate().is_err(), "{:?}", params);
+        }
+    }
 }
@@ -0,0 +1,294 @@
+use std::sync::Arc;
+use noise::{NoiseFn, Seedable};
+use serde::{Deserialize, Serialize};
+use crate::{noisegen::{MountainousTerrainNoise, Noise2D}, unity::Position2D64};
+
+/// Anything that maps a world position to a height, the building block of a noise graph.
+pub trait NoiseSource: Send + Sync {
+    fn get(&self, pos: Position2D64) -> f64;
+}
+
+pub type BoxedNoiseSource = Box<dyn NoiseSource>;
+
+impl NoiseSource for MountainousTerrainNoise {
+    fn get(&self, pos: Position2D64) -> f64 {
+        Noise2D::get(self, pos)
+    }
+}
+
+impl<T: NoiseSource + ?Sized> NoiseSource for Box<T> {
+    fn get(&self, pos: Position2D64) -> f64 {
+        (**self).get(pos)
+    }
+}
+
+impl<T: NoiseSource + ?Sized> NoiseSource for Arc<T> {
+    fn get(&self, pos: Position2D64) -> f64 {
+        (**self).get(pos)
+    }
+}
+
+/// Samples a `NoiseFn` at `pos * frequency`, the output keeps the range of the wrapped function.
+pub struct NoiseFnSource<F> {
+    pub noise: F,
+    pub frequency: f64,
+}
+
+impl<F: NoiseFn<[f64; 2]> + Send + Sync> NoiseSource for NoiseFnSource<F> {
+    fn get(&self, pos: Position2D64) -> f64 {
+        self.noise.get([pos.x * self.frequency, pos.y * self.frequency])
+    }
+}
+
+pub struct Constant(pub f64);
+
+impl NoiseSource for Constant {
+    fn get(&self, _pos: Position2D64) -> f64 {
+        self.0
+    }
+}
+
+pub struct Add(pub BoxedNoiseSource, pub BoxedNoiseSource);
+
+impl NoiseSource for Add {
+    fn get(&self, pos: Position2D64) -> f64 {
+        self.0.get(pos) + self.1.get(pos)
+    }
+}
+
+pub struct Multiply(pub BoxedNoiseSource, pub BoxedNoiseSource);
+
+impl NoiseSource for Multiply {
+    fn get(&self, pos: Position2D64) -> f64 {
+        self.0.get(pos) * self.1.get(pos)
+    }
+}
+
+pub struct Min(pub BoxedNoiseSource, pub BoxedNoiseSource);
+
+impl NoiseSource for Min {
+    fn get(&self, pos: Position2D64) -> f64 {
+        self.0.get(pos).min(self.1.get(pos))
+    }
+}
+
+pub struct Max(pub BoxedNoiseSource, pub BoxedNoiseSource);
+
+impl NoiseSource for Max {
+    fn get(&self, pos: Position2D64) -> f64 {
+        self.0.get(pos).max(self.1.get(pos))
+    }
+}
+
+/// Picks `low` where `mask` is below `threshold` and `high` above it,
+/// blending with a smoothstep over `threshold - falloff..threshold + falloff`.
+pub struct Select {
+    pub low: BoxedNoiseSource,
+    pub high: BoxedNoiseSource,
+    pub mask: BoxedNoiseSource,
+    pub threshold: f64,
+    pub falloff: f64,
+}
+
+impl NoiseSource for Select {
+    fn get(&self, pos: Position2D64) -> f64 {
+        let m = self.mask.get(pos);
+        if self.falloff <= 0.0 {
+            return if m < self.threshold { self.low.get(pos) } else { self.high.get(pos) };
+        }
+        let t = ((m - self.threshold + self.falloff) / (2.0 * self.falloff)).clamp(0.0, 1.0);
+        if t <= 0.0 {
+            self.low.get(pos)
+        }
+        else if t >= 1.0 {
+            self.high.get(pos)
+        }
+        else {
+            let t = t * t * (3.0 - 2.0 * t);
+            self.low.get(pos) * (1.0 - t) + self.high.get(pos) * t
+        }
+    }
+}
+
+pub struct ScaleBias {
+    pub source: BoxedNoiseSource,
+    pub scale: f64,
+    pub bias: f64,
+}
+
+impl NoiseSource for ScaleBias {
+    fn get(&self, pos: Position2D64) -> f64 {
+        self.source.get(pos) * self.scale + self.bias
+    }
+}
+
+pub struct Clamp {
+    pub source: BoxedNoiseSource,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl NoiseSource for Clamp {
+    fn get(&self, pos: Position2D64) -> f64 {
+        self.source.get(pos).max(self.min).min(self.max)
+    }
+}
+
+/// Remaps the source through a piecewise linear curve, `points` are `[input, output]` pairs.
+/// Inputs outside the curve take the value of the nearest end point.
+pub struct Curve {
+    pub source: BoxedNoiseSource,
+    points: Vec<[f64; 2]>,
+}
+
+impl Curve {
+    pub fn build(source: BoxedNoiseSource, mut points: Vec<[f64; 2]>) -> Self {
+        points.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap_or(std::cmp::Ordering::Equal));
+        Self { source, points }
+    }
+}
+
+impl NoiseSource for Curve {
+    fn get(&self, pos: Position2D64) -> f64 {
+        let h = self.source.get(pos);
+        match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) => {
+                if h <= first[0] {
+                    return first[1];
+                }
+                if h >= last[0] {
+                    return last[1];
+                }
+                let r = self.points.iter().position(|p| p[0] > h).unwrap_or(self.points.len() - 1);
+                let (l, r) = (self.points[r - 1], self.points[r]);
+                l[1] + (r[1] - l[1]) * (h - l[0]) / (r[0] - l[0])
+            },
+            _ => h,
+        }
+    }
+}
+
+/// Moves the sample position before reading the source: scale, then rotate (radians), then translate.
+pub struct Transform {
+    pub source: BoxedNoiseSource,
+    pub translate: Position2D64,
+    pub rotation: f64,
+    pub scale: f64,
+}
+
+impl NoiseSource for Transform {
+    fn get(&self, pos: Position2D64) -> f64 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let x = pos.x * self.scale;
+        let y = pos.y * self.scale;
+        let transformed = Position2D64{
+            x: x * cos - y * sin,
+            y: x * sin + y * cos,
+        } + self.translate;
+        self.source.get(transformed)
+    }
+}
+
+/// Data description of a noise graph, `build` turns it into the combinator tree.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoiseNode {
+    Constant(f64),
+    Mountainous(Box<MountainousTerrainNoise>),
+    /// raw perlin noise in [-1, 1]
+    Perlin { seed: u32, frequency: f64 },
+    Add(Box<NoiseNode>, Box<NoiseNode>),
+    Multiply(Box<NoiseNode>, Box<NoiseNode>),
+    Min(Box<NoiseNode>, Box<NoiseNode>),
+    Max(Box<NoiseNode>, Box<NoiseNode>),
+    Select { low: Box<NoiseNode>, high: Box<NoiseNode>, mask: Box<NoiseNode>, threshold: f64, falloff: f64 },
+    ScaleBias { source: Box<NoiseNode>, scale: f64, bias: f64 },
+    Clamp { source: Box<NoiseNode>, min: f64, max: f64 },
+    Curve { source: Box<NoiseNode>, points: Vec<[f64; 2]> },
+    Transform { source: Box<NoiseNode>, translate: Position2D64, rotation: f64, scale: f64 },
+}
+
+impl NoiseNode {
+    pub fn build(&self) -> BoxedNoiseSource {
+        match self {
+            NoiseNode::Constant(c) => Box::new(Constant(*c)),
+            NoiseNode::Mountainous(noise) => noise.clone(),
+            NoiseNode::Perlin { seed, frequency } => Box::new(NoiseFnSource{ noise: noise::Perlin::new().set_seed(*seed), frequency: *frequency }),
+            NoiseNode::Add(a, b) => Box::new(Add(a.build(), b.build())),
+            NoiseNode::Multiply(a, b) => Box::new(Multiply(a.build(), b.build())),
+            NoiseNode::Min(a, b) => Box::new(Min(a.build(), b.build())),
+            NoiseNode::Max(a, b) => Box::new(Max(a.build(), b.build())),
+            NoiseNode::Select { low, high, mask, threshold, falloff } => Box::new(Select{
+                low: low.build(),
+                high: high.build(),
+                mask: mask.build(),
+                threshold: *threshold,
+                falloff: *falloff,
+            }),
+            NoiseNode::ScaleBias { source, scale, bias } => Box::new(ScaleBias{ source: source.build(), scale: *scale, bias: *bias }),
+            NoiseNode::Clamp { source, min, max } => Box::new(Clamp{ source: source.build(), min: *min, max: *max }),
+            NoiseNode::Curve { source, points } => Box::new(Curve::build(source.build(), points.clone())),
+            NoiseNode::Transform { source, translate, rotation, scale } => Box::new(Transform{
+                source: source.build(),
+                translate: *translate,
+                rotation: *rotation,
+                scale: *scale,
+            }),
+        }
+    }
+}
+
+impl Default for NoiseNode {
+    fn default() -> Self {
+        NoiseNode::Mountainous(Box::default())
+    }
+}
+
+#[cfg(test)]
+mod noisegraph_tests {
+    use crate::unity::Position2D64;
+
+    use super::{NoiseNode, NoiseSource};
+
+    fn constant(c: f64) -> Box<NoiseNode> {
+        Box::new(NoiseNode::Constant(c))
+    }
+
+    #[test]
+    fn test_arithmetic_nodes() {
+        let pos = Position2D64{x: 3.0, y: -2.0};
+        assert_eq!(NoiseNode::Add(constant(0.25), constant(0.5)).build().get(pos), 0.75);
+        assert_eq!(NoiseNode::Multiply(constant(0.25), constant(0.5)).build().get(pos), 0.125);
+        assert_eq!(NoiseNode::Min(constant(0.25), constant(0.5)).build().get(pos), 0.25);
+        assert_eq!(NoiseNode::Max(constant(0.25), constant(0.5)).build().get(pos), 0.5);
+        assert_eq!(NoiseNode::ScaleBias{source: constant(0.5), scale: 2.0, bias: -1.0}.build().get(pos), 0.0);
+        assert_eq!(NoiseNode::Clamp{source: constant(2.0), min: 0.0, max: 1.0}.build().get(pos), 1.0);
+    }
+
+    #[test]
+    fn test_select_falloff() {
+        let pos = Position2D64::default();
+        let select = |mask: f64| NoiseNode::Select{low: constant(0.0), high: constant(1.0), mask: constant(mask), threshold: 0.5, falloff: 0.1}.build().get(pos);
+        assert_eq!(select(0.3), 0.0);
+        assert_eq!(select(0.7), 1.0);
+        assert!((select(0.5) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_curve_remap() {
+        let pos = Position2D64::default();
+        let curve = |h: f64| NoiseNode::Curve{source: constant(h), points: vec![[1.0, 0.0], [0.0, 1.0], [0.5, 0.0]]}.build().get(pos);
+        assert_eq!(curve(-1.0), 1.0);
+        assert_eq!(curve(0.25), 0.5);
+        assert_eq!(curve(0.75), 0.0);
+        assert_eq!(curve(2.0), 0.0);
+    }
+
+    #[test]
+    fn test_transform_matches_source() {
+        let node = NoiseNode::default();
+        let source = node.build();
+        let transformed = NoiseNode::Transform{source: Box::new(node), translate: Position2D64{x: 10.0, y: 5.0}, rotation: 0.0, scale: 1.0}.build();
+        assert_eq!(transformed.get(Position2D64{x: 1.0, y: 2.0}), source.get(Position2D64{x: 11.0, y: 7.0}));
+    }
+}
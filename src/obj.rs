@@ -0,0 +1,105 @@
+use std::{fs::File, io::{self, BufWriter, Write}, path::Path};
+
+use crate::{chunkgen::{ChunkBuffers, ChunkGen2D}, texturegen::write_png, unity::{Color32, Faces, Position3D64, VertexAttributes}};
+
+const MATERIAL_NAME: &str = "terrain";
+
+/// Writes a chunk as Wavefront OBJ, flipped to the right handed, counter clockwise convention of most tools.
+/// Vertex colors are written as the `v x y z r g b` extension when `colors` holds one color per vertex.
+/// With `mtllib` set the faces use the `terrain` material of that library, see `write_mtl`.
+pub fn write_obj<W: Write, V: VertexAttributes, F: Faces>(out: W, v_buffer: &[V], indx_buffer: &[F], colors: &[Color32], mtllib: Option<&str>) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+    let with_colors = colors.len() >= v_buffer.len();
+    let with_normals = v_buffer.iter().all(|v| v.normal().is_some());
+
+    writeln!(out, "# meshgen chunk")?;
+    if let Some(mtllib) = mtllib {
+        writeln!(out, "mtllib {}", mtllib)?;
+    }
+    for (i, v) in v_buffer.iter().enumerate() {
+        let p = v.position();
+        if with_colors {
+            let c = colors[i];
+            writeln!(out, "v {} {} {} {} {} {}", p.x, p.y, -p.z, c.r as f32 / 255.0, c.g as f32 / 255.0, c.b as f32 / 255.0)?;
+        }
+        else {
+            writeln!(out, "v {} {} {}", p.x, p.y, -p.z)?;
+        }
+    }
+    for v in v_buffer.iter() {
+        // obj puts the texture origin at the bottom left, the png stores the first texture row at the top
+        let uv = v.uv();
+        writeln!(out, "vt {} {}", uv.u, 1.0 - uv.v)?;
+    }
+    if with_normals {
+        for n in v_buffer.iter().filter_map(|v| v.normal()) {
+            writeln!(out, "vn {} {} {}", n.x, n.y, -n.z)?;
+        }
+    }
+    if mtllib.is_some() {
+        writeln!(out, "usemtl {}", MATERIAL_NAME)?;
+    }
+    for face in indx_buffer.iter() {
+        for k in 0..F::TRIANGLES {
+            let tri = face.triangle(k);
+            // obj indices are one based
+            let (a, b, c) = (tri.v1 + 1, tri.v3 + 1, tri.v2 + 1);
+            if with_normals {
+                writeln!(out, "f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}", a, b, c)?;
+            }
+            else {
+                writeln!(out, "f {0}/{0} {1}/{1} {2}/{2}", a, b, c)?;
+            }
+        }
+    }
+    out.flush()
+}
+
+/// Material library for `write_obj`, an unlit white material showing `texture`.
+pub fn write_mtl<W: Write>(mut out: W, texture: &str) -> io::Result<()> {
+    writeln!(out, "newmtl {}", MATERIAL_NAME)?;
+    writeln!(out, "Ka 1 1 1")?;
+    writeln!(out, "Kd 1 1 1")?;
+    writeln!(out, "Ks 0 0 0")?;
+    writeln!(out, "d 1")?;
+    writeln!(out, "illum 1")?;
+    writeln!(out, "map_Kd {}", texture)?;
+    out.flush()
+}
+
+/// Generates the chunk at `plane_pos` and saves it as `path` with a `.mtl` and `.png` of the same stem next to it.
+pub fn save_chunk_obj<C: ChunkGen2D + Sync, P: AsRef<Path>>(chunkgen: &C, plane_pos: Position3D64, path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    let (mtl_path, png_path) = (path.with_extension("mtl"), path.with_extension("png"));
+    let file_name = |p: &Path| p.file_name().and_then(|name| name.to_str()).unwrap_or_default().to_string();
+
+    let chunk = ChunkBuffers::fill(chunkgen, plane_pos);
+    let tex_side = chunk.tex_side();
+    write_png(BufWriter::new(File::create(&png_path)?), tex_side, tex_side, &chunk.tex_buffer)?;
+    write_mtl(BufWriter::new(File::create(&mtl_path)?), &file_name(&png_path))?;
+    write_obj(File::create(path)?, &chunk.v_buffer, &chunk.indx_buffer, &chunk.tex_buffer, Some(&file_name(&mtl_path)))
+}
+
+#[cfg(test)]
+mod obj_tests {
+    use crate::chunkgen::export_test_chunks;
+    use super::write_obj;
+
+    #[test]
+    fn test_counts_match_geometry_desc() {
+        for (desc, chunk) in export_test_chunks() {
+            let (v_count, f_count) = (desc.v_count as i32, desc.f_count as i32);
+            let mut obj = Vec::new();
+            write_obj(&mut obj, &chunk.v_buffer, &chunk.indx_buffer, &chunk.tex_buffer, Some("chunk.mtl")).unwrap();
+            let obj = String::from_utf8(obj).unwrap();
+            let count = |prefix: &str| obj.lines().filter(|line| line.starts_with(prefix)).count() as i32;
+
+            assert_eq!(count("v "), v_count);
+            assert_eq!(count("vt "), v_count);
+            assert_eq!(count("f "), f_count);
+            assert_eq!(count("usemtl "), 1);
+            // every vertex line carries a color
+            assert!(obj.lines().filter(|line| line.starts_with("v ")).all(|line| line.split_whitespace().count() == 7));
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::{fs::File, io::{self, BufWriter, Write}, path::Path};
+
+use crate::{chunkgen::{ChunkBuffers, ChunkGen2D}, unity::{Color32, Faces, Position3D64, VertexAttributes}};
+
+/// Writes a chunk as binary little endian PLY, flipped to the right handed, counter clockwise convention of most tools.
+/// Every vertex has a position, uv (`s`, `t`), normal when the vertex type has one and an RGBA color
+/// taken from `colors`, vertices past the end of `colors` are white.
+pub fn write_ply<W: Write, V: VertexAttributes, F: Faces>(out: W, v_buffer: &[V], indx_buffer: &[F], colors: &[Color32]) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+    let with_normals = v_buffer.iter().all(|v| v.normal().is_some());
+
+    writeln!(out, "ply")?;
+    writeln!(out, "format binary_little_endian 1.0")?;
+    writeln!(out, "comment meshgen chunk")?;
+    writeln!(out, "element vertex {}", v_buffer.len())?;
+    for property in ["x", "y", "z"].iter() {
+        writeln!(out, "property float {}", property)?;
+    }
+    if with_normals {
+        for property in ["nx", "ny", "nz"].iter() {
+            writeln!(out, "property float {}", property)?;
+        }
+    }
+    for property in ["s", "t"].iter() {
+        writeln!(out, "property float {}", property)?;
+    }
+    for property in ["red", "green", "blue", "alpha"].iter() {
+        writeln!(out, "property uchar {}", property)?;
+    }
+    writeln!(out, "element face {}", indx_buffer.len() * F::TRIANGLES)?;
+    writeln!(out, "property list uchar int vertex_indices")?;
+    writeln!(out, "end_header")?;
+
+    for (i, v) in v_buffer.iter().enumerate() {
+        let p = v.position();
+        for x in [p.x, p.y, -p.z].iter() {
+            out.write_all(&x.to_le_bytes())?;
+        }
+        if let Some(n) = v.normal() {
+            for x in [n.x, n.y, -n.z].iter() {
+                out.write_all(&x.to_le_bytes())?;
+            }
+        }
+        let uv = v.uv();
+        out.write_all(&uv.u.to_le_bytes())?;
+        // same bottom left texture origin as the obj export
+        out.write_all(&(1.0 - uv.v).to_le_bytes())?;
+        let c = colors.get(i).copied().unwrap_or(Color32{r: 255, g: 255, b: 255, a: 255});
+        out.write_all(&[c.r, c.g, c.b, c.a])?;
+    }
+    for face in indx_buffer.iter() {
+        for k in 0..F::TRIANGLES {
+            let tri = face.triangle(k);
+            out.write_all(&[3])?;
+            for index in [tri.v1, tri.v3, tri.v2].iter() {
+                out.write_all(&index.to_le_bytes())?;
+            }
+        }
+    }
+    out.flush()
+}
+
+/// Generates the chunk at `plane_pos` and saves it as `path`.
+pub fn save_chunk_ply<C: ChunkGen2D + Sync, P: AsRef<Path>>(chunkgen: &C, plane_pos: Position3D64, path: P) -> io::Result<()> {
+    let chunk = ChunkBuffers::fill(chunkgen, plane_pos);
+    write_ply(File::create(path)?, &chunk.v_buffer, &chunk.indx_buffer, &chunk.tex_buffer)
+}
+
+#[cfg(test)]
+mod ply_tests {
+    use crate::chunkgen::export_test_chunks;
+    use super::write_ply;
+
+    #[test]
+    fn test_counts_match_geometry_desc() {
+        for (desc, chunk) in export_test_chunks() {
+            let (v_count, f_count) = (desc.v_count as i32, desc.f_count as i32);
+            let mut ply = Vec::new();
+            write_ply(&mut ply, &chunk.v_buffer, &chunk.indx_buffer, &chunk.tex_buffer).unwrap();
+
+            let header_end = ply.windows(11).position(|w| w == b"end_header\n").unwrap() + 11;
+            let header = std::str::from_utf8(&ply[..header_end]).unwrap();
+            let element = |name: &str| header.lines()
+                .find_map(|line| line.strip_prefix(&format!("element {} ", name)))
+                .and_then(|n| n.parse::<i32>().ok())
+                .unwrap();
+            assert_eq!(element("vertex"), v_count);
+            assert_eq!(element("face"), f_count);
+            // 5 floats and 4 color bytes per vertex, a count byte and 3 indices per face
+            assert_eq!(ply.len() - header_end, (v_count * 24 + f_count * 13) as usize);
+
+            let first_color = &ply[header_end + 20..header_end + 24];
+            let c = chunk.tex_buffer[0];
+            assert_eq!(first_color, &[c.r, c.g, c.b, c.a]);
+        }
+    }
+}
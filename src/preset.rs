@@ -0,0 +1,164 @@
+use std::{error::Error, fmt, fs, io, path::Path};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Text formats presets can be written in.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresetFormat {
+    #[default]
+    Json,
+    Ron,
+    Toml,
+}
+
+impl From<u32> for PresetFormat {
+    fn from(format: u32) -> Self {
+        match format {
+            1 => PresetFormat::Ron,
+            2 => PresetFormat::Toml,
+            _ => PresetFormat::Json,
+        }
+    }
+}
+
+impl PresetFormat {
+    /// Picks the format from the file extension, `.json`, `.ron` or `.toml`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(PresetFormat::Json),
+            "ron" => Some(PresetFormat::Ron),
+            "toml" => Some(PresetFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PresetError {
+    Io(io::Error),
+    Utf8(std::str::Utf8Error),
+    Json(serde_json::Error),
+    Ron(ron::Error),
+    TomlDe(toml::de::Error),
+    TomlSer(toml::ser::Error),
+    UnknownFormat,
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresetError::Io(err) => write!(f, "failed to access preset: {}", err),
+            PresetError::Utf8(err) => write!(f, "preset is not valid utf-8: {}", err),
+            PresetError::Json(err) => write!(f, "invalid json preset: {}", err),
+            PresetError::Ron(err) => write!(f, "invalid ron preset: {}", err),
+            PresetError::TomlDe(err) => write!(f, "invalid toml preset: {}", err),
+            PresetError::TomlSer(err) => write!(f, "failed to write toml preset: {}", err),
+            PresetError::UnknownFormat => write!(f, "unknown preset format, expected .json, .ron or .toml"),
+        }
+    }
+}
+
+impl Error for PresetError {}
+
+impl From<io::Error> for PresetError {
+    fn from(err: io::Error) -> Self {
+        PresetError::Io(err)
+    }
+}
+
+impl From<std::str::Utf8Error> for PresetError {
+    fn from(err: std::str::Utf8Error) -> Self {
+        PresetError::Utf8(err)
+    }
+}
+
+impl From<serde_json::Error> for PresetError {
+    fn from(err: serde_json::Error) -> Self {
+        PresetError::Json(err)
+    }
+}
+
+impl From<ron::Error> for PresetError {
+    fn from(err: ron::Error) -> Self {
+        PresetError::Ron(err)
+    }
+}
+
+impl From<toml::de::Error> for PresetError {
+    fn from(err: toml::de::Error) -> Self {
+        PresetError::TomlDe(err)
+    }
+}
+
+impl From<toml::ser::Error> for PresetError {
+    fn from(err: toml::ser::Error) -> Self {
+        PresetError::TomlSer(err)
+    }
+}
+
+pub fn from_str<T: DeserializeOwned>(preset: &str, format: PresetFormat) -> Result<T, PresetError> {
+    Ok(match format {
+        PresetFormat::Json => serde_json::from_str(preset)?,
+        PresetFormat::Ron => ron::de::from_str(preset)?,
+        PresetFormat::Toml => toml::from_str(preset)?,
+    })
+}
+
+pub fn to_string<T: Serialize>(value: &T, format: PresetFormat) -> Result<String, PresetError> {
+    Ok(match format {
+        PresetFormat::Json => serde_json::to_string_pretty(value)?,
+        PresetFormat::Ron => ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?,
+        // going through a toml::Value puts plain values before tables, whatever the field order
+        PresetFormat::Toml => toml::to_string_pretty(&toml::Value::try_from(value)?)?,
+    })
+}
+
+/// Reads a preset, the format is picked from the file extension.
+pub fn load<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, PresetError> {
+    let format = PresetFormat::from_path(&path).ok_or(PresetError::UnknownFormat)?;
+    from_str(&fs::read_to_string(path)?, format)
+}
+
+pub fn save<T: Serialize, P: AsRef<Path>>(value: &T, path: P) -> Result<(), PresetError> {
+    let format = PresetFormat::from_path(&path).ok_or(PresetError::UnknownFormat)?;
+    Ok(fs::write(path, to_string(value, format)?)?)
+}
+
+#[cfg(test)]
+mod preset_tests {
+    use crate::{chunkgen::MountainousTerrainChunkGen, erosion::ThermalErosion, gradient::{BlendType, ColorKey}, noisegen::{BaseNoiseType, FractalType, Noise2D, WorleyDistance, WorleyReturn}, unity::{Color32, Position2D32, Position2D64}};
+    use super::{from_str, to_string, PresetFormat};
+
+    #[test]
+    fn test_chunkgen_round_trip() {
+        let mut chunkgen = MountainousTerrainChunkGen::build(32, 80.0);
+        chunkgen.noise = crate::noisegen::MountainousTerrainNoise::build(7, 20.0, 0.4, 2.0, 4, 0.5, 0.4, Position2D32{x: 0.3, y: 0.1}, Position2D32{x: 0.6, y: 0.3}, 0.25, FractalType::Ridged, BaseNoiseType::Worley(WorleyDistance::Manhattan, WorleyReturn::Distance));
+        chunkgen.color_gradient.blend_type = BlendType::Linear;
+        chunkgen.color_gradient.keys = vec![ColorKey{color: Color32{r: 30, g: 80, b: 200, a: 255}, t: 0.3}, ColorKey{color: Color32{r: 255, g: 255, b: 255, a: 255}, t: 1.0}];
+        chunkgen.lod = 1;
+        chunkgen.thermal_erosion = Some(ThermalErosion::build(3, 35.0));
+
+        for format in [PresetFormat::Json, PresetFormat::Ron, PresetFormat::Toml].iter() {
+            let preset = to_string(&chunkgen, *format).unwrap();
+            let loaded: MountainousTerrainChunkGen = from_str(&preset, *format).unwrap();
+            assert_eq!(to_string(&loaded, *format).unwrap(), preset);
+            assert_eq!(loaded.noise.params(), chunkgen.noise.params());
+            assert_eq!(loaded.color_gradient, chunkgen.color_gradient);
+            assert_eq!(loaded.thermal_erosion, chunkgen.thermal_erosion);
+            let pos = Position2D64{x: 12.5, y: -3.0};
+            assert_eq!(loaded.noise.get(pos), chunkgen.noise.get(pos));
+        }
+    }
+
+    #[test]
+    fn test_partial_preset_uses_defaults() {
+        let chunkgen: MountainousTerrainChunkGen = from_str("side_len = 16\n[noise]\nseed = 3\nfractal_type = \"billow\"\n", PresetFormat::Toml).unwrap();
+        let defaults = MountainousTerrainChunkGen::default();
+        assert_eq!(chunkgen.side_len, 16);
+        assert_eq!(chunkgen.height, defaults.height);
+        assert_eq!(chunkgen.noise.params().seed, 3);
+        assert_eq!(chunkgen.noise.params().fractal_type, FractalType::Billow);
+        assert_eq!(chunkgen.noise.params().scale, defaults.noise.params().scale);
+        assert!(from_str::<MountainousTerrainChunkGen>("{\"side_len\": \"wide\"}", PresetFormat::Json).is_err());
+    }
+}
@@ -0,0 +1,540 @@
+use std::{collections::{HashMap, HashSet, VecDeque}, sync::Arc};
+use log::info;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{biome::BiomeBlend, chunkgen::{ChunkGen2D, ChunkGeometryDesc}, error::{Error, Result}, heightmap::HeightMap, unity::{Color32, Position2D64, Position3D64, Quad, Vertex}};
+
+/// A node of the quadtree, level 0 is the root and level `l` splits the root into `2^l x 2^l` nodes,
+/// node (x, z) being the x-th from the -x side and the z-th from the -z side.
+#[repr(C)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct NodeId {
+    pub level: u32,
+    pub x: i64,
+    pub z: i64,
+}
+
+impl NodeId {
+    pub fn root() -> Self {
+        NodeId::default()
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        match self.level {
+            0 => None,
+            _ => Some(NodeId{ level: self.level - 1, x: self.x >> 1, z: self.z >> 1 }),
+        }
+    }
+
+    pub fn children(&self) -> [NodeId; 4] {
+        let (level, x, z) = (self.level + 1, self.x * 2, self.z * 2);
+        [NodeId{ level, x, z }, NodeId{ level, x: x + 1, z }, NodeId{ level, x, z: z + 1 }, NodeId{ level, x: x + 1, z: z + 1 }]
+    }
+}
+
+/// Settings of a `Quadtree`.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuadtreeParams {
+    /// world units along the side of the root node
+    pub root_size: f64,
+    /// world position of the center of the root node
+    pub root_center: Position2D64,
+    /// nodes are never split below this level
+    pub max_depth: u32,
+    /// quads along the side of every node mesh, must be even
+    pub resolution: usize,
+    /// nodes whose geometric error covers more pixels than this are split
+    pub error_threshold: f64,
+    /// vertical field of view of the camera in degrees
+    pub fov_y: f64,
+    /// height of the viewport in pixels
+    pub viewport_height: f64,
+    /// fraction of `error_threshold` over which nodes morph into their parent before they are merged
+    pub morph_range: f64,
+}
+
+impl QuadtreeParams {
+    pub fn build(root_size: f64, max_depth: u32) -> Self {
+        Self {
+            root_size,
+            root_center: Position2D64::default(),
+            max_depth,
+            resolution: 32,
+            error_threshold: 2.0,
+            fov_y: 60.0,
+            viewport_height: 1080.0,
+            morph_range: 0.5,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.root_size.is_finite() && self.root_size > 0.0) {
+            return Err(Error::invalid_parameter(format!("root_size must be positive, got {}", self.root_size)));
+        }
+        if !(self.root_center.x.is_finite() && self.root_center.y.is_finite()) {
+            return Err(Error::invalid_parameter(format!("root_center must be finite, got {:?}", self.root_center)));
+        }
+        // node coordinates are i64, deeper levels would overflow them
+        if self.max_depth > 60 {
+            return Err(Error::invalid_parameter(format!("max_depth must be at most 60, got {}", self.max_depth)));
+        }
+        if self.resolution < 2 || !self.resolution.is_multiple_of(2) {
+            return Err(Error::invalid_parameter(format!("resolution must be even and at least 2, got {}", self.resolution)));
+        }
+        if !(self.error_threshold.is_finite() && self.error_threshold > 0.0) {
+            return Err(Error::invalid_parameter(format!("error_threshold must be positive, got {}", self.error_threshold)));
+        }
+        if !(self.fov_y > 0.0 && self.fov_y < 180.0) {
+            return Err(Error::invalid_parameter(format!("fov_y must be between 0 and 180 degrees, got {}", self.fov_y)));
+        }
+        if !(self.viewport_height.is_finite() && self.viewport_height > 0.0) {
+            return Err(Error::invalid_parameter(format!("viewport_height must be positive, got {}", self.viewport_height)));
+        }
+        if !(self.morph_range.is_finite() && self.morph_range > 0.0) {
+            return Err(Error::invalid_parameter(format!("morph_range must be positive, got {}", self.morph_range)));
+        }
+        Ok(())
+    }
+
+    /// world units along the side of a node of `level`
+    pub fn node_size(&self, level: u32) -> f64 {
+        self.root_size / (1_u64 << level) as f64
+    }
+
+    /// corner of the node on the -x and -z side
+    pub fn node_min(&self, id: NodeId) -> Position2D64 {
+        let size = self.node_size(id.level);
+        let root_min = self.root_center - Position2D64{ x: self.root_size / 2.0, y: self.root_size / 2.0 };
+        root_min + Position2D64{ x: id.x as f64 * size, y: id.z as f64 * size }
+    }
+
+    /// center of the node, the position its vertices are relative to
+    pub fn node_center(&self, id: NodeId) -> Position3D64 {
+        let half = self.node_size(id.level) / 2.0;
+        let min = self.node_min(id);
+        Position3D64{ x: min.x + half, y: 0.0, z: min.y + half }
+    }
+
+    /// Buffer sizes of every node mesh.
+    pub fn geometry_desc(&self, skirts: bool) -> ChunkGeometryDesc {
+        ChunkGeometryDesc::build(self.resolution, 0, skirts)
+    }
+
+    /// Pixels covered by a world space `error` at `distance` from the camera.
+    pub fn screen_error(&self, error: f64, distance: f64) -> f64 {
+        let projection = self.viewport_height / (2.0 * (self.fov_y.to_radians() / 2.0).tan());
+        match distance > 0.0 {
+            true => error * projection / distance,
+            _ if error > 0.0 => f64::INFINITY,
+            _ => 0.0,
+        }
+    }
+}
+
+impl Default for QuadtreeParams {
+    fn default() -> Self {
+        QuadtreeParams::build(65536.0, 10)
+    }
+}
+
+/// Mesh of a visible node, vertices are relative to `center` like the ones of a chunk.
+#[derive(Clone, Debug, Default)]
+pub struct QuadtreeNode {
+    pub id: NodeId,
+    pub center: Position3D64,
+    pub size: f64,
+    pub v_buffer: Vec<Vertex>,
+    pub indx_buffer: Vec<Quad>,
+    pub tex_buffer: Vec<Color32>,
+    /// biome blend of every vertex, see `ChunkGen2D::fill_vertex_biomes`
+    pub biome_buffer: Vec<BiomeBlend>,
+    /// per vertex height offset to the surface of the parent node, the vertex is drawn at
+    /// `y + morph_buffer[i] * morph_weight` so that it matches the parent at a weight of 1
+    pub morph_buffer: Vec<f32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct NodeInfo {
+    /// largest height difference in world units between the node mesh and the mesh of its children
+    error: f64,
+    min_height: f64,
+    max_height: f64,
+}
+
+/// Height of the vertex at (x, y) of a grid with twice the resolution of its parent on the parent mesh,
+/// vertices between parent vertices lie on the parent edges, quad centers on the diagonal `fill_mesh` splits along.
+fn parent_height(map: &HeightMap, border: usize, x: usize, y: usize) -> f32 {
+    let (i, j) = (x - border, y - border);
+    match (i % 2, j % 2) {
+        (0, 0) => map.get(x, y),
+        (1, 0) => (map.get(x - 1, y) + map.get(x + 1, y)) / 2.0,
+        (0, _) => (map.get(x, y - 1) + map.get(x, y + 1)) / 2.0,
+        _ => (map.get(x - 1, y + 1) + map.get(x + 1, y - 1)) / 2.0,
+    }
+}
+
+/// Continuous level of detail terrain over a `ChunkGen2D`. Every `update` selects the nodes whose screen space
+/// geometric error stays under `error_threshold`, generates the new ones on the rayon pool and reports the
+/// changes through `take_added` and `take_removed`.
+///
+/// Neighbouring nodes can be more than one level apart, cracks between them are covered by the skirts of
+/// the chunkgen. Heights come from `sample_height_grid` at the spacing of the node, so they go through the
+/// filters of the chunkgen, vertex colors and biome blends from `fill_vertex_colors` and `fill_vertex_biomes`,
+/// uvs from `get_uv_mapping`. A node as large as a chunk with as many quads matches `fill_chunk_2d_with_normals`.
+pub struct Quadtree<C> {
+    chunkgen: C,
+    params: QuadtreeParams,
+    /// errors of the nodes the last update visited
+    infos: HashMap<NodeId, NodeInfo>,
+    visible: HashMap<NodeId, Arc<QuadtreeNode>>,
+    morph_weights: HashMap<NodeId, f32>,
+    added: VecDeque<NodeId>,
+    removed: VecDeque<NodeId>,
+}
+
+impl<C: ChunkGen2D + Sync> Quadtree<C> {
+    pub fn build(chunkgen: C, params: QuadtreeParams) -> Self {
+        Self {
+            chunkgen,
+            params,
+            infos: HashMap::new(),
+            visible: HashMap::new(),
+            morph_weights: HashMap::new(),
+            added: VecDeque::new(),
+            removed: VecDeque::new(),
+        }
+    }
+
+    pub fn chunkgen(&self) -> &C {
+        &self.chunkgen
+    }
+
+    pub fn params(&self) -> &QuadtreeParams {
+        &self.params
+    }
+
+    /// Buffer sizes of every node mesh.
+    pub fn geometry_desc(&self) -> ChunkGeometryDesc {
+        self.params.geometry_desc(self.chunkgen.get_skirt_depth() > 0.0)
+    }
+
+    /// Swaps the settings, every visible node is reported for removal and the next `update` starts over.
+    pub fn set_params(&mut self, params: QuadtreeParams) {
+        info!("replacing quadtree settings, dropping {} nodes", self.visible.len());
+        self.params = params;
+        self.infos.clear();
+        self.morph_weights.clear();
+        self.added.clear();
+        let mut visible: Vec<NodeId> = self.visible.drain().map(|(id, _)| id).collect();
+        visible.sort();
+        self.removed.extend(visible);
+    }
+
+    /// Same as `set_params` for a new chunkgen.
+    pub fn set_chunkgen(&mut self, chunkgen: C) {
+        self.chunkgen = chunkgen;
+        self.set_params(self.params);
+    }
+
+    /// Heights of `id` with `quads` quads per side, `border` extra samples per side.
+    fn sample(&self, id: NodeId, quads: usize, border: usize) -> HeightMap {
+        let spacing = self.params.node_size(id.level) / quads as f64;
+        self.chunkgen.sample_height_grid(self.params.node_min(id), quads + 1, spacing as f32, border)
+    }
+
+    fn node_info(&self, id: NodeId) -> NodeInfo {
+        let map = self.sample(id, 2 * self.params.resolution, 0);
+        let height_scale = self.chunkgen.get_height();
+        let mut info = NodeInfo{ error: 0.0, min_height: f64::INFINITY, max_height: f64::NEG_INFINITY };
+        for y in 0..map.height {
+            for x in 0..map.width {
+                let h = map.get(x, y);
+                info.error = info.error.max(((h - parent_height(&map, 0, x, y)) as f64 * height_scale).abs());
+                info.min_height = info.min_height.min(h as f64 * height_scale);
+                info.max_height = info.max_height.max(h as f64 * height_scale);
+            }
+        }
+        info
+    }
+
+    /// Screen space error of the node seen from `camera`, from the distance to its bounding box.
+    fn screen_error(&self, id: NodeId, info: &NodeInfo, camera: Position3D64) -> f64 {
+        let min = self.params.node_min(id);
+        let size = self.params.node_size(id.level);
+        let outside = |c: f64, lo: f64, hi: f64| (lo - c).max(c - hi).max(0.0);
+        let dx = outside(camera.x, min.x, min.x + size);
+        let dy = outside(camera.y, info.min_height, info.max_height);
+        let dz = outside(camera.z, min.y, min.y + size);
+        self.params.screen_error(info.error, (dx * dx + dy * dy + dz * dz).sqrt())
+    }
+
+    fn node_mesh(&self, id: NodeId) -> QuadtreeNode {
+        let n = self.params.resolution;
+        let center = self.params.node_center(id);
+        let desc = self.geometry_desc();
+        let mut node = QuadtreeNode {
+            id,
+            center,
+            size: self.params.node_size(id.level),
+            v_buffer: vec![Vertex::default(); desc.v_count],
+            indx_buffer: vec![Quad::default(); desc.quad_count()],
+            tex_buffer: vec![Color32::default(); desc.v_count],
+            biome_buffer: vec![BiomeBlend::default(); desc.v_count],
+            morph_buffer: vec![0.0; desc.v_count],
+        };
+        let heights = self.sample(id, n, 1);
+        let height_scale = self.chunkgen.get_height();
+        heights.fill_mesh_with_normals(1, height_scale, 0.0, self.chunkgen.get_skirt_depth(), &mut node.v_buffer, &mut node.indx_buffer);
+        let uv_mapping = self.chunkgen.get_uv_mapping();
+        node.v_buffer.par_iter_mut().for_each(|v| v.uv = uv_mapping.uv(center, v.pos, v.uv));
+        self.chunkgen.fill_vertex_colors(&heights, 1, &mut node.tex_buffer);
+        self.chunkgen.fill_vertex_biomes(&heights, 1, &mut node.biome_buffer);
+        heights.fill_vertex_data(1, &mut node.morph_buffer, |x, y, h| ((parent_height(&heights, 1, x, y) - h) as f64 * height_scale) as f32);
+        node
+    }
+
+    /// Selects the nodes for `camera`, generates the ones that became visible and updates the morph weights.
+    pub fn update(&mut self, camera: Position3D64) {
+        let mut visited = HashMap::new();
+        let mut errors = HashMap::new();
+        let mut leaves = Vec::new();
+        let mut frontier = vec![NodeId::root()];
+        while !frontier.is_empty() {
+            let missing: Vec<NodeId> = frontier.iter().copied().filter(|id| !self.infos.contains_key(id)).collect();
+            let computed: Vec<(NodeId, NodeInfo)> = missing.par_iter().map(|&id| (id, self.node_info(id))).collect();
+            self.infos.extend(computed);
+            let mut next = Vec::new();
+            for id in frontier {
+                let info = self.infos[&id];
+                let error = self.screen_error(id, &info, camera);
+                visited.insert(id, info);
+                errors.insert(id, error);
+                if id.level < self.params.max_depth && error > self.params.error_threshold {
+                    next.extend_from_slice(&id.children());
+                }
+                else {
+                    leaves.push(id);
+                }
+            }
+            frontier = next;
+        }
+        self.infos = visited;
+
+        // a node is fully morphed into its parent when the parent error drops to the threshold and it is merged
+        let (threshold, range) = (self.params.error_threshold, self.params.error_threshold * self.params.morph_range);
+        self.morph_weights = leaves.iter()
+            .map(|id| (*id, id.parent().map_or(0.0, |parent| (1.0 - (errors[&parent] - threshold) / range).clamp(0.0, 1.0) as f32)))
+            .collect();
+
+        let selected: HashSet<NodeId> = leaves.iter().copied().collect();
+        let mut removed: Vec<NodeId> = self.visible.keys().copied().filter(|id| !selected.contains(id)).collect();
+        removed.sort();
+        for id in removed.iter() {
+            self.visible.remove(id);
+            self.added.retain(|added| added != id);
+        }
+        self.removed.extend(removed);
+
+        let mut added: Vec<NodeId> = leaves.into_iter().filter(|id| !self.visible.contains_key(id)).collect();
+        added.sort();
+        let meshes: Vec<QuadtreeNode> = added.par_iter().map(|&id| self.node_mesh(id)).collect();
+        for node in meshes {
+            self.visible.insert(node.id, Arc::new(node));
+        }
+        self.added.extend(added);
+    }
+
+    /// Nodes that became visible since the last call, the host should show them before it drops the removed ones.
+    pub fn take_added(&mut self) -> Vec<NodeId> {
+        self.added.drain(..).collect()
+    }
+
+    /// At most `max` of the nodes `take_added` would return.
+    pub fn take_added_up_to(&mut self, max: usize) -> Vec<NodeId> {
+        let n = max.min(self.added.len());
+        self.added.drain(..n).collect()
+    }
+
+    /// Nodes the host should drop since the last call.
+    pub fn take_removed(&mut self) -> Vec<NodeId> {
+        self.removed.drain(..).collect()
+    }
+
+    pub fn take_removed_up_to(&mut self, max: usize) -> Vec<NodeId> {
+        let n = max.min(self.removed.len());
+        self.removed.drain(..n).collect()
+    }
+
+    /// Mesh of a visible node.
+    pub fn get(&self, id: NodeId) -> Option<Arc<QuadtreeNode>> {
+        self.visible.get(&id).map(Arc::clone)
+    }
+
+    /// Geomorph weight of a visible node for the last update, 0 draws the node as is and 1 as its parent.
+    pub fn morph_weight(&self, id: NodeId) -> Option<f32> {
+        self.morph_weights.get(&id).copied()
+    }
+
+    /// Every visible node in order.
+    pub fn visible_nodes(&self) -> Vec<NodeId> {
+        let mut nodes: Vec<NodeId> = self.visible.keys().copied().collect();
+        nodes.sort();
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod quadtree_tests {
+    use crate::{biome::{BiomeBlend, BiomeMap}, chunkgen::{BiomeChunkGen, ChunkGen2D, MountainousTerrainChunkGen}, erosion::ThermalErosion, error::Error, unity::{Color32, Position2D64, Position3D64, Quad, Vertex}};
+    use super::{NodeId, Quadtree, QuadtreeParams};
+
+    fn at(x: f64, y: f64, z: f64) -> Position3D64 {
+        Position3D64{ x, y, z }
+    }
+
+    fn tree(skirt_depth: f64, error_threshold: f64) -> Quadtree<MountainousTerrainChunkGen> {
+        let mut chunkgen = MountainousTerrainChunkGen::build(8, 80.0);
+        chunkgen.skirt_depth = skirt_depth;
+        let params = QuadtreeParams{ resolution: 8, error_threshold, ..QuadtreeParams::build(1024.0, 5) };
+        Quadtree::build(chunkgen, params)
+    }
+
+    #[test]
+    fn test_node_ids() {
+        let params = QuadtreeParams::build(1024.0, 5);
+        let id = NodeId{ level: 2, x: 3, z: 1 };
+        assert_eq!(id.parent(), Some(NodeId{ level: 1, x: 1, z: 0 }));
+        assert!(id.children().iter().all(|child| child.parent() == Some(id)));
+        assert_eq!(NodeId::root().parent(), None);
+        assert_eq!(params.node_size(2), 256.0);
+        assert_eq!(params.node_center(id), at(384.0, 0.0, -128.0));
+        assert_eq!(params.node_center(NodeId::root()), at(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_nodes_cover_the_root() {
+        let mut tree = tree(0.0, 2.0);
+        tree.update(at(10.0, 100.0, -30.0));
+        let nodes = tree.visible_nodes();
+        let area: f64 = nodes.iter().map(|id| tree.params().node_size(id.level).powi(2)).sum();
+        assert_eq!(area, 1024.0 * 1024.0);
+        // finer near the camera than far away
+        let near = nodes.iter().find(|id| {
+            let size = tree.params().node_size(id.level);
+            let min = tree.params().node_min(**id);
+            (min.x..min.x + size).contains(&10.0) && (min.y..min.y + size).contains(&-30.0)
+        }).unwrap();
+        assert!(nodes.iter().all(|id| id.level <= near.level));
+        assert!(near.level > 1);
+        let mut added = tree.take_added();
+        added.sort();
+        assert_eq!(added, nodes);
+        assert!(tree.take_removed().is_empty());
+
+        // the node mesh is the chunkgen surface sampled at the node center
+        let node = tree.get(*near).unwrap();
+        assert_eq!(node.v_buffer.len(), tree.geometry_desc().v_count);
+        let corner = node.v_buffer[0].pos;
+        let expected = tree.chunkgen().get([node.center.x + corner.x as f64, node.center.z + corner.z as f64].into()) * 80.0;
+        assert!((corner.y as f64 - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_leaves_meet_the_threshold() {
+        let mut tree = tree(0.0, 8.0);
+        let camera = at(-200.0, 120.0, 150.0);
+        tree.update(camera);
+        let nodes = tree.visible_nodes();
+        assert!(nodes.iter().any(|id| id.level < 5));
+        for id in nodes {
+            let error = tree.screen_error(id, &tree.node_info(id), camera);
+            assert!(error <= 8.0 || id.level == 5, "node {:?} has {} pixels of error", id, error);
+            // and the parent was split because it didn't
+            let parent = id.parent().unwrap();
+            assert!(tree.screen_error(parent, &tree.node_info(parent), camera) > 8.0);
+        }
+    }
+
+    #[test]
+    fn test_reports_changes() {
+        // coarse enough that the nodes away from the camera are merged
+        let mut tree = tree(2.0, 16.0);
+        tree.update(at(0.0, 100.0, 0.0));
+        let before = tree.visible_nodes();
+        tree.take_added();
+        // nothing changes for the same camera
+        tree.update(at(0.0, 100.0, 0.0));
+        assert!(tree.take_added().is_empty() && tree.take_removed().is_empty());
+
+        tree.update(at(400.0, 100.0, 400.0));
+        let after = tree.visible_nodes();
+        let mut removed = tree.take_removed();
+        let mut added = tree.take_added();
+        removed.sort();
+        added.sort();
+        assert_eq!(removed, before.iter().copied().filter(|id| !after.contains(id)).collect::<Vec<NodeId>>());
+        assert_eq!(added, after.iter().copied().filter(|id| !before.contains(id)).collect::<Vec<NodeId>>());
+        assert!(!added.is_empty());
+        assert!(tree.get(removed[0]).is_none());
+        assert_eq!(tree.take_added_up_to(3).len(), 0);
+    }
+
+    #[test]
+    fn test_morph_weights() {
+        let mut tree = tree(0.0, 2.0);
+        tree.update(at(0.0, 100.0, 0.0));
+        for id in tree.visible_nodes() {
+            let weight = tree.morph_weight(id).unwrap();
+            assert!((0.0..=1.0).contains(&weight));
+            let node = tree.get(id).unwrap();
+            // vertices on the parent grid don't move
+            assert_eq!(node.morph_buffer[0], 0.0);
+            assert_eq!(node.morph_buffer[2 * 9 + 4], 0.0);
+            // the others morph onto the parent edges
+            let (left, right) = (node.v_buffer[0].pos.y, node.v_buffer[2].pos.y);
+            assert!((node.v_buffer[1].pos.y + node.morph_buffer[1] - (left + right) / 2.0).abs() < 1e-3);
+        }
+        // the camera hovers right above the terrain, the nodes below it reach max_depth
+        let under = tree.visible_nodes().into_iter().max_by_key(|id| id.level).unwrap();
+        assert_eq!(under.level, 5);
+    }
+
+    #[test]
+    fn test_node_matches_chunk() {
+        let mut chunkgen = BiomeChunkGen::build(8, 80.0, BiomeMap::default());
+        chunkgen.thermal_erosion = Some(ThermalErosion::build(3, 35.0));
+        chunkgen.skirt_depth = 2.0;
+        // level 3 nodes are as large as the chunks and have as many quads, the root sits between plains and dunes
+        let params = QuadtreeParams{ resolution: 8, root_center: Position2D64{ x: 896.0, y: 0.0 }, ..QuadtreeParams::build(64.0, 3) };
+        let tree = Quadtree::build(chunkgen, params);
+        let node = tree.node_mesh(NodeId{ level: 3, x: 2, z: 5 });
+
+        let desc = tree.chunkgen().get_geometry_desc();
+        assert_eq!(desc, tree.geometry_desc());
+        let mut v_buffer = vec![Vertex::default(); desc.v_count];
+        let mut indx_buffer = vec![Quad::default(); desc.quad_count()];
+        let mut tex_buffer = vec![Color32::default(); desc.v_count];
+        let mut biome_buffer = vec![BiomeBlend::default(); desc.v_count];
+        tree.chunkgen().fill_chunk_with_normals(&mut v_buffer, &mut indx_buffer, &mut tex_buffer, node.center).unwrap();
+        tree.chunkgen().fill_chunk_biomes(&mut biome_buffer, node.center).unwrap();
+        assert_eq!(node.v_buffer, v_buffer);
+        assert_eq!(node.indx_buffer, indx_buffer);
+        assert_eq!(node.tex_buffer, tex_buffer);
+        assert_eq!(node.biome_buffer, biome_buffer);
+        // not the single biome 0 of the default hooks
+        assert!(node.biome_buffer.iter().any(|blend| blend.id() != 0 || blend.weights[1] > 0.0));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(QuadtreeParams::default().validate().is_ok());
+        let reject = |params: QuadtreeParams| matches!(params.validate(), Err(Error::InvalidParameter(_)));
+        assert!(reject(QuadtreeParams{ resolution: 7, ..QuadtreeParams::default() }));
+        assert!(reject(QuadtreeParams{ root_size: 0.0, ..QuadtreeParams::default() }));
+        assert!(reject(QuadtreeParams{ error_threshold: f64::NAN, ..QuadtreeParams::default() }));
+        assert!(reject(QuadtreeParams{ fov_y: 180.0, ..QuadtreeParams::default() }));
+        assert!(reject(QuadtreeParams{ max_depth: 61, ..QuadtreeParams::default() }));
+    }
+}
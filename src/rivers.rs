@@ -0,0 +1,428 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+use log::info;
+use lyon_geom::{euclid::{default::Point2D, point2}, CubicBezierSegment};
+use serde::{Deserialize, Serialize};
+
+use crate::{chunkgen::ChunkGen2D, error::{Error, Result}, heightmap::HeightMap, unity::{Position2D64, Position3D64}};
+
+/// D8 neighbour offsets, the index is the flow direction code. East first, then counter clockwise.
+pub const D8: [(i64, i64); 8] = [(1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1), (0, 1), (1, 1)];
+
+/// Direction code of samples that drain nowhere, the border of the region and flats the fill left behind.
+pub const NO_FLOW: u8 = u8::MAX;
+
+/// Settings of the river network of a region, rivers start where `threshold` samples drain through one sample.
+/// Width and depth grow with the flow as `width * (flow / threshold)^width_exponent`, both in world units.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RiverGen {
+    pub threshold: f32,
+    pub width: f32,
+    pub width_exponent: f32,
+    pub depth: f32,
+    pub depth_exponent: f32,
+    /// rise per sample the depression fill adds across flats so that every sample drains, in normalized height
+    pub fill_epsilon: f32,
+}
+
+impl RiverGen {
+    pub fn build(threshold: f32) -> Self {
+        Self {
+            threshold,
+            width: 2.0,
+            width_exponent: 0.5,
+            depth: 1.0,
+            depth_exponent: 0.4,
+            fill_epsilon: 1e-5,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !self.threshold.is_finite() || self.threshold < 1.0 {
+            return Err(Error::invalid_parameter(format!("river threshold must be at least 1 sample, got {}", self.threshold)));
+        }
+        // river samples are the ones with a width
+        if !self.width.is_finite() || self.width <= 0.0 {
+            return Err(Error::invalid_parameter(format!("river width must be positive, got {}", self.width)));
+        }
+        for (name, value) in [("width_exponent", self.width_exponent), ("depth", self.depth), ("depth_exponent", self.depth_exponent)].iter() {
+            if !value.is_finite() || *value < 0.0 {
+                return Err(Error::invalid_parameter(format!("river {} must be finite and not negative, got {}", name, value)));
+            }
+        }
+        if !self.fill_epsilon.is_finite() || self.fill_epsilon < 0.0 {
+            return Err(Error::invalid_parameter(format!("fill_epsilon must be finite and not negative, got {}", self.fill_epsilon)));
+        }
+        Ok(())
+    }
+
+    /// Rivers of `map`, `height_scale` turns normalized heights into world units.
+    /// Water leaves the region at its border, so rivers near the border only see part of their catchment.
+    pub fn generate(&self, map: &HeightMap, height_scale: f32) -> RiverNetwork {
+        info!("generating rivers for a {}x{} region", map.width, map.height);
+        let filled = priority_flood(map, self.fill_epsilon);
+        let directions = flow_directions(&filled);
+        let accumulation = flow_accumulation(&filled, &directions);
+
+        let mut network = RiverNetwork {
+            heights: map.clone(),
+            width: HeightMap::build(map.width, map.height, map.origin, map.spacing),
+            depth: HeightMap::build(map.width, map.height, map.origin, map.spacing),
+            rivers: Vec::new(),
+            filled,
+            directions,
+            accumulation,
+        };
+        for i in 0..map.data.len() {
+            let flow = network.accumulation.data[i];
+            if flow >= self.threshold {
+                network.width.data[i] = self.width * (flow / self.threshold).powf(self.width_exponent);
+                network.depth.data[i] = self.depth * (flow / self.threshold).powf(self.depth_exponent);
+            }
+        }
+        network.carve(height_scale);
+        network.rivers = network.trace(height_scale);
+        network
+    }
+
+    /// Rivers of the region of `chunkgen` with `width` x `height` samples `spacing` apart from `origin`.
+    pub fn generate_region<C: ChunkGen2D + Sync>(&self, chunkgen: &C, origin: Position2D64, width: usize, height: usize, spacing: f32) -> RiverNetwork {
+        let map = HeightMap::sample(width, height, origin, spacing, |pos| chunkgen.get(pos));
+        self.generate(&map, chunkgen.get_height() as f32)
+    }
+}
+
+impl Default for RiverGen {
+    fn default() -> Self {
+        RiverGen::build(200.0)
+    }
+}
+
+/// One sample along a river, `pos.y` is the water surface.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct RiverPoint {
+    pub pos: Position3D64,
+    /// samples draining through this one
+    pub flow: f32,
+    pub width: f32,
+    pub depth: f32,
+}
+
+/// A river from its source to its mouth. The last point of a tributary is on the river it joins.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct River {
+    pub points: Vec<RiverPoint>,
+    /// index of the river this one flows into, `None` if it leaves the region
+    pub joins: Option<usize>,
+}
+
+impl River {
+    pub fn polyline(&self) -> Vec<Position3D64> {
+        self.points.iter().map(|point| point.pos).collect()
+    }
+
+    /// Catmull-Rom curves through the points in the xz plane, one segment per pair of points.
+    pub fn curves(&self) -> Vec<CubicBezierSegment<f64>> {
+        let xz: Vec<Point2D<f64>> = self.points.iter().map(|p| point2(p.pos.x, p.pos.z)).collect();
+        (0..xz.len().saturating_sub(1)).map(|i| {
+            let (from, to) = (xz[i], xz[i + 1]);
+            let before = xz[i.saturating_sub(1)];
+            let after = xz[(i + 2).min(xz.len() - 1)];
+            CubicBezierSegment {
+                from,
+                ctrl1: from + (to - before) / 6.0,
+                ctrl2: to - (after - from) / 6.0,
+                to,
+            }
+        }).collect()
+    }
+}
+
+/// Everything `RiverGen::generate` works out for a region, the maps share the grid of the input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RiverNetwork {
+    /// input heights with the river beds carved in
+    pub heights: HeightMap,
+    /// input heights with every depression filled up to its spill point
+    pub filled: HeightMap,
+    /// D8 code of the neighbour each sample drains to, see `D8` and `NO_FLOW`
+    pub directions: Vec<u8>,
+    /// samples draining through each sample, itself included
+    pub accumulation: HeightMap,
+    /// river width in world units, 0 away from rivers
+    pub width: HeightMap,
+    /// river depth in world units, 0 away from rivers
+    pub depth: HeightMap,
+    pub rivers: Vec<River>,
+}
+
+impl RiverNetwork {
+    /// index of the sample `i` drains to
+    pub fn downstream(&self, i: usize) -> Option<usize> {
+        neighbour(&self.filled, i, self.directions[i])
+    }
+
+    pub fn is_river(&self, i: usize) -> bool {
+        self.width.data[i] > 0.0
+    }
+
+    pub fn polylines(&self) -> Vec<Vec<Position3D64>> {
+        self.rivers.iter().map(River::polyline).collect()
+    }
+
+    /// Lowers the heights around every river sample to a parabolic bed below the water surface.
+    fn carve(&mut self, height_scale: f32) {
+        if height_scale <= 0.0 {
+            return;
+        }
+        let (width, height, spacing) = (self.heights.width as i64, self.heights.height as i64, self.heights.spacing);
+        for i in 0..self.heights.data.len() {
+            if !self.is_river(i) {
+                continue;
+            }
+            let half_width = (self.width.data[i] / 2.0).max(spacing / 2.0);
+            let water = self.filled.data[i] * height_scale;
+            let depth = self.depth.data[i];
+            let reach = (half_width / spacing).ceil() as i64;
+            let (x, y) = (i as i64 % width, i as i64 / width);
+            for ny in (y - reach).max(0)..=(y + reach).min(height - 1) {
+                for nx in (x - reach).max(0)..=(x + reach).min(width - 1) {
+                    let d = (((nx - x).pow(2) + (ny - y).pow(2)) as f32).sqrt() * spacing;
+                    if d > half_width {
+                        continue;
+                    }
+                    let bed = (water - depth * (1.0 - (d / half_width).powi(2))) / height_scale;
+                    let h = &mut self.heights.data[(ny * width + nx) as usize];
+                    *h = h.min(bed);
+                }
+            }
+        }
+    }
+
+    /// Walks up from every mouth, the branch with the most flow continues the river and the others become tributaries.
+    fn trace(&self, height_scale: f32) -> Vec<River> {
+        let count = self.filled.data.len();
+        let mut upstream: Vec<Vec<usize>> = vec![Vec::new(); count];
+        for i in (0..count).filter(|i| self.is_river(*i)) {
+            if let Some(d) = self.downstream(i) {
+                upstream[d].push(i);
+            }
+        }
+        for ups in upstream.iter_mut() {
+            ups.sort_by(|a, b| self.accumulation.data[*b].total_cmp(&self.accumulation.data[*a]).then(a.cmp(b)));
+        }
+
+        let mut stack: Vec<(usize, Option<usize>)> = (0..count).rev()
+            .filter(|i| self.is_river(*i) && self.downstream(*i).is_none_or(|d| !self.is_river(d)))
+            .map(|i| (i, None))
+            .collect();
+        let mut rivers = Vec::new();
+        while let Some((mouth, joins)) = stack.pop() {
+            let id = rivers.len();
+            let mut cells = vec![mouth];
+            let mut cur = mouth;
+            while let Some((&main, tributaries)) = upstream[cur].split_first() {
+                stack.extend(tributaries.iter().rev().map(|t| (*t, Some(id))));
+                cells.push(main);
+                cur = main;
+            }
+            cells.reverse();
+            if joins.is_some() {
+                cells.extend(self.downstream(mouth));
+            }
+            let points = cells.iter().map(|&i| self.river_point(i, height_scale)).collect();
+            rivers.push(River { points, joins });
+        }
+        rivers
+    }
+
+    fn river_point(&self, i: usize, height_scale: f32) -> RiverPoint {
+        let world = self.filled.world_pos(i % self.filled.width, i / self.filled.width);
+        RiverPoint {
+            pos: Position3D64{ x: world.x, y: (self.filled.data[i] * height_scale) as f64, z: world.y },
+            flow: self.accumulation.data[i],
+            width: self.width.data[i],
+            depth: self.depth.data[i],
+        }
+    }
+}
+
+fn neighbour(map: &HeightMap, i: usize, direction: u8) -> Option<usize> {
+    let (dx, dy) = D8.get(direction as usize)?;
+    let (x, y) = ((i % map.width) as i64 + dx, (i / map.width) as i64 + dy);
+    if x < 0 || y < 0 || x >= map.width as i64 || y >= map.height as i64 {
+        return None;
+    }
+    Some(y as usize * map.width + x as usize)
+}
+
+/// min heap entry of the priority flood, ties go to the lower index so the fill is deterministic
+#[derive(PartialEq)]
+struct FloodCell {
+    h: f32,
+    i: usize,
+}
+
+impl Eq for FloodCell {}
+
+impl Ord for FloodCell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.h.total_cmp(&self.h).then(other.i.cmp(&self.i))
+    }
+}
+
+impl PartialOrd for FloodCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Priority-flood depression filling, water enters from the border and every sample ends up
+/// at least `epsilon` above the neighbour it was reached from.
+fn priority_flood(map: &HeightMap, epsilon: f32) -> HeightMap {
+    let mut filled = map.clone();
+    let mut visited = vec![false; map.data.len()];
+    let mut open = BinaryHeap::new();
+    for (i, seen) in visited.iter_mut().enumerate() {
+        let (x, y) = (i % map.width, i / map.width);
+        if x == 0 || y == 0 || x == map.width - 1 || y == map.height - 1 {
+            *seen = true;
+            open.push(FloodCell{ h: filled.data[i], i });
+        }
+    }
+    while let Some(FloodCell{ h, i }) = open.pop() {
+        for direction in 0..D8.len() as u8 {
+            if let Some(n) = neighbour(map, i, direction) {
+                if !visited[n] {
+                    visited[n] = true;
+                    // the next float up if epsilon is too small to change the height
+                    let lowest = (h + epsilon).max(h.next_up());
+                    filled.data[n] = filled.data[n].max(lowest);
+                    open.push(FloodCell{ h: filled.data[n], i: n });
+                }
+            }
+        }
+    }
+    filled
+}
+
+/// Steepest descent on the filled heights, border samples drain out of the region.
+fn flow_directions(filled: &HeightMap) -> Vec<u8> {
+    (0..filled.data.len()).map(|i| {
+        let (x, y) = (i % filled.width, i / filled.width);
+        if x == 0 || y == 0 || x == filled.width - 1 || y == filled.height - 1 {
+            return NO_FLOW;
+        }
+        let mut best = (NO_FLOW, 0.0);
+        for (direction, (dx, dy)) in D8.iter().enumerate() {
+            if let Some(n) = neighbour(filled, i, direction as u8) {
+                let dist = if *dx != 0 && *dy != 0 { std::f32::consts::SQRT_2 } else { 1.0 };
+                let slope = (filled.data[i] - filled.data[n]) / dist;
+                if slope > best.1 {
+                    best = (direction as u8, slope);
+                }
+            }
+        }
+        best.0
+    }).collect()
+}
+
+/// Every sample passes its own area and everything above it on to the sample below, highest first.
+fn flow_accumulation(filled: &HeightMap, directions: &[u8]) -> HeightMap {
+    let mut accumulation = HeightMap::build(filled.width, filled.height, filled.origin, filled.spacing);
+    accumulation.data.iter_mut().for_each(|a| *a = 1.0);
+    let mut order: Vec<usize> = (0..filled.data.len()).collect();
+    order.sort_by(|a, b| filled.data[*b].total_cmp(&filled.data[*a]).then(a.cmp(b)));
+    for i in order {
+        if let Some(d) = neighbour(filled, i, directions[i]) {
+            accumulation.data[d] += accumulation.data[i];
+        }
+    }
+    accumulation
+}
+
+#[cfg(test)]
+mod river_tests {
+    use crate::{chunkgen::MountainousTerrainChunkGen, error::Error, heightmap::HeightMap, unity::Position2D64};
+    use super::{flow_directions, priority_flood, RiverGen, NO_FLOW};
+
+    /// a valley sloping down to the south border with a pit in the middle
+    fn valley() -> HeightMap {
+        let mut map = HeightMap::sample(21, 41, Position2D64::default(), 1.0, |pos| 0.02 * (pos.x - 10.0).abs() + 0.01 * pos.y);
+        map.set(10, 20, 0.0);
+        map
+    }
+
+    #[test]
+    fn test_priority_flood_fills_pits() {
+        let map = valley();
+        let filled = priority_flood(&map, 1e-5);
+        assert!(filled.get(10, 20) > map.get(10, 19));
+        assert!(filled.data.iter().zip(map.data.iter()).all(|(f, h)| f >= h));
+        // nothing away from the pit moves
+        assert_eq!(filled.get(3, 30), map.get(3, 30));
+
+        // below zero and without epsilon the pit still drains, noise graphs can go down to -1
+        let mut map = valley();
+        map.data.iter_mut().for_each(|h| *h -= 2.0);
+        let filled = priority_flood(&map, 0.0);
+        assert!(filled.get(10, 20) > map.get(10, 19));
+        let directions = flow_directions(&filled);
+        for (i, direction) in directions.iter().enumerate() {
+            let (x, y) = (i % 21, i / 21);
+            assert_eq!(*direction == NO_FLOW, x == 0 || y == 0 || x == 20 || y == 40);
+        }
+    }
+
+    #[test]
+    fn test_valley_river() {
+        let rivers = RiverGen { threshold: 30.0, ..RiverGen::default() };
+        let network = rivers.generate(&valley(), 10.0);
+        // every interior sample drains, so all the water reaches the border
+        for i in 0..network.directions.len() {
+            let (x, y) = (i % 21, i / 21);
+            let border = x == 0 || y == 0 || x == 20 || y == 40;
+            assert_eq!(network.directions[i] == NO_FLOW, border);
+        }
+        let total: f32 = (0..network.directions.len()).filter(|i| network.directions[*i] == NO_FLOW).map(|i| network.accumulation.data[i]).sum();
+        assert_eq!(total, (21 * 41) as f32);
+
+        let main = &network.rivers[0];
+        assert_eq!(main.joins, None);
+        // the river runs down the valley floor, through the filled pit, and grows on the way
+        assert!(main.points.iter().skip(1).all(|p| p.pos.x == 10.0));
+        assert!(main.points.windows(2).all(|pair| pair[1].pos.y < pair[0].pos.y && pair[1].width >= pair[0].width));
+        assert_eq!(main.points.last().unwrap().pos.z, 0.0);
+        assert!(network.heights.get(10, 30) < valley().get(10, 30));
+        assert!(network.depth.get(10, 30) > 0.0);
+        assert_eq!(network.width.get(2, 30), 0.0);
+
+        let curves = main.curves();
+        assert_eq!(curves.len(), main.points.len() - 1);
+        assert_eq!(curves[0].from.x, main.points[0].pos.x);
+        assert_eq!(curves.last().unwrap().to.y, 0.0);
+    }
+
+    #[test]
+    fn test_tributaries_join() {
+        let chunkgen = MountainousTerrainChunkGen::build(64, 50.0);
+        let rivers = RiverGen::build(40.0);
+        rivers.validate().unwrap();
+        let network = rivers.generate_region(&chunkgen, Position2D64{x: -64.0, y: -64.0}, 128, 128, 1.0);
+        assert!(network.rivers.iter().any(|river| river.joins.is_some()));
+        for river in network.rivers.iter() {
+            assert!(river.points.len() >= 2 || river.joins.is_none());
+            if let Some(joins) = river.joins {
+                let mouth = river.points.last().unwrap().pos;
+                assert!(network.rivers[joins].polyline().contains(&mouth));
+            }
+        }
+        assert_eq!(network, rivers.generate_region(&chunkgen, Position2D64{x: -64.0, y: -64.0}, 128, 128, 1.0));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(matches!(RiverGen::build(0.5).validate(), Err(Error::InvalidParameter(_))));
+        assert!(matches!(RiverGen { depth: f32::NAN, ..RiverGen::default() }.validate(), Err(Error::InvalidParameter(_))));
+    }
+}
@@ -0,0 +1,339 @@
+use std::{collections::{HashMap, HashSet, VecDeque}, sync::{mpsc::{self, Receiver, Sender}, Arc}};
+use log::info;
+
+use crate::{chunkgen::{ChunkBuffers, ChunkGen2D}, unity::Position3D64};
+
+/// Integer coordinates of a chunk on the xz plane, chunk (x, z) is centered on (x * side_len, z * side_len).
+#[repr(C)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct ChunkCoord {
+    pub x: i64,
+    pub z: i64,
+}
+
+impl ChunkCoord {
+    /// The chunk whose square contains `pos`.
+    pub fn containing(pos: Position3D64, side_len: usize) -> Self {
+        let side_len = side_len.max(1) as f64;
+        Self {
+            x: (pos.x / side_len).round() as i64,
+            z: (pos.z / side_len).round() as i64,
+        }
+    }
+
+    /// center of the chunk, the `plane_pos` handed to the chunkgen
+    pub fn plane_pos(&self, side_len: usize) -> Position3D64 {
+        Position3D64{ x: self.x as f64 * side_len as f64, y: 0.0, z: self.z as f64 * side_len as f64 }
+    }
+
+    fn distance_sq(&self, other: ChunkCoord) -> i64 {
+        let (dx, dz) = (self.x.saturating_sub(other.x), self.z.saturating_sub(other.z));
+        dx.saturating_mul(dx).saturating_add(dz.saturating_mul(dz))
+    }
+}
+
+struct CachedChunk {
+    buffers: Arc<ChunkBuffers>,
+    last_used: u64,
+}
+
+/// Keeps the chunks around a viewer generated. Missing chunks are generated on the rayon pool,
+/// finished ones are kept in a cache that evicts the least recently used chunk once it is full.
+///
+/// The host learns about changes through `take_ready`, chunks that can be shown now, and
+/// `take_unload`, chunks that were ready before and left the view radius or the cache.
+pub struct ChunkStreamer<C> {
+    chunkgen: Arc<C>,
+    /// in chunks, every chunk whose center is at most this far from the viewer chunk is generated
+    view_radius: u32,
+    cache_capacity: usize,
+    cache: HashMap<ChunkCoord, CachedChunk>,
+    pending: HashSet<ChunkCoord>,
+    /// chunks handed to the host with `take_ready` or waiting in `ready`
+    visible: HashSet<ChunkCoord>,
+    ready: VecDeque<ChunkCoord>,
+    unload: VecDeque<ChunkCoord>,
+    viewer: Option<ChunkCoord>,
+    tick: u64,
+    /// bumped by `set_chunkgen` so results of the previous chunkgen are dropped
+    generation: u64,
+    sender: Sender<(u64, ChunkCoord, ChunkBuffers)>,
+    receiver: Receiver<(u64, ChunkCoord, ChunkBuffers)>,
+}
+
+impl<C: ChunkGen2D + Send + Sync + 'static> ChunkStreamer<C> {
+    /// `cache_capacity` is raised to the number of chunks in the view radius so visible chunks are never evicted.
+    pub fn build(chunkgen: C, view_radius: u32, cache_capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            chunkgen: Arc::new(chunkgen),
+            view_radius,
+            cache_capacity: cache_capacity.max(Self::chunks_in_radius(view_radius)),
+            cache: HashMap::new(),
+            pending: HashSet::new(),
+            visible: HashSet::new(),
+            ready: VecDeque::new(),
+            unload: VecDeque::new(),
+            viewer: None,
+            tick: 0,
+            generation: 0,
+            sender,
+            receiver,
+        }
+    }
+
+    fn chunks_in_radius(view_radius: u32) -> usize {
+        let r = view_radius as i64;
+        let origin = ChunkCoord::default();
+        (-r..=r).flat_map(|x| (-r..=r).map(move |z| ChunkCoord{ x, z }))
+            .filter(|coord| coord.distance_sq(origin) <= r * r)
+            .count()
+    }
+
+    pub fn chunkgen(&self) -> &C {
+        &self.chunkgen
+    }
+
+    pub fn view_radius(&self) -> u32 {
+        self.view_radius
+    }
+
+    pub fn cache_capacity(&self) -> usize {
+        self.cache_capacity
+    }
+
+    /// Swaps the chunkgen, every cached chunk is dropped and visible chunks are reported for unloading.
+    pub fn set_chunkgen(&mut self, chunkgen: C) {
+        info!("replacing chunk streamer chunkgen, dropping {} cached chunks", self.cache.len());
+        self.chunkgen = Arc::new(chunkgen);
+        self.generation += 1;
+        self.cache.clear();
+        self.pending.clear();
+        self.ready.clear();
+        let mut visible: Vec<ChunkCoord> = self.visible.drain().collect();
+        visible.sort();
+        self.unload.extend(visible);
+        if let Some(viewer) = self.viewer {
+            self.schedule(viewer);
+        }
+    }
+
+    pub fn set_view_radius(&mut self, view_radius: u32) {
+        self.view_radius = view_radius;
+        self.cache_capacity = self.cache_capacity.max(Self::chunks_in_radius(view_radius));
+        if let Some(viewer) = self.viewer {
+            self.schedule(viewer);
+        }
+    }
+
+    fn in_view(&self, coord: ChunkCoord, viewer: ChunkCoord) -> bool {
+        coord.distance_sq(viewer) <= self.view_radius as i64 * self.view_radius as i64
+    }
+
+    /// Moves the viewer, schedules the chunks that came into view and collects the finished ones.
+    pub fn update(&mut self, viewer_pos: Position3D64) {
+        let viewer = ChunkCoord::containing(viewer_pos, self.chunkgen.get_side_len());
+        if self.viewer != Some(viewer) {
+            self.viewer = Some(viewer);
+            let mut left: Vec<ChunkCoord> = self.visible.iter().copied().filter(|&coord| !self.in_view(coord, viewer)).collect();
+            left.sort();
+            for coord in left {
+                self.visible.remove(&coord);
+                self.ready.retain(|&ready| ready != coord);
+                self.unload.push_back(coord);
+            }
+            self.schedule(viewer);
+        }
+        self.poll();
+    }
+
+    /// Requests every chunk in view, nearest first.
+    fn schedule(&mut self, viewer: ChunkCoord) {
+        self.tick += 1;
+        let r = self.view_radius as i64;
+        let mut wanted: Vec<ChunkCoord> = (-r..=r)
+            .flat_map(|x| (-r..=r).map(move |z| ChunkCoord{ x: viewer.x.saturating_add(x), z: viewer.z.saturating_add(z) }))
+            .filter(|&coord| self.in_view(coord, viewer))
+            .collect();
+        wanted.sort_by_key(|&coord| (coord.distance_sq(viewer), coord));
+        wanted.dedup();
+        for coord in wanted {
+            if let Some(cached) = self.cache.get_mut(&coord) {
+                cached.last_used = self.tick;
+                if self.visible.insert(coord) {
+                    self.ready.push_back(coord);
+                }
+            }
+            else if self.pending.insert(coord) {
+                let chunkgen = Arc::clone(&self.chunkgen);
+                let sender = self.sender.clone();
+                let generation = self.generation;
+                let plane_pos = coord.plane_pos(chunkgen.get_side_len());
+                rayon::spawn(move || {
+                    let buffers = chunkgen.generate_chunk(plane_pos);
+                    // the streamer may have been dropped in the meantime
+                    let _ = sender.send((generation, coord, buffers));
+                });
+            }
+        }
+    }
+
+    /// Collects the chunks finished since the last call without blocking.
+    pub fn poll(&mut self) {
+        while let Ok((generation, coord, buffers)) = self.receiver.try_recv() {
+            self.insert(generation, coord, buffers);
+        }
+    }
+
+    /// Blocks until every scheduled chunk is generated.
+    pub fn wait(&mut self) {
+        while !self.pending.is_empty() {
+            match self.receiver.recv() {
+                Ok((generation, coord, buffers)) => self.insert(generation, coord, buffers),
+                Err(_) => break,
+            }
+        }
+    }
+
+    fn insert(&mut self, generation: u64, coord: ChunkCoord, buffers: ChunkBuffers) {
+        if generation != self.generation || !self.pending.remove(&coord) {
+            return;
+        }
+        self.cache.insert(coord, CachedChunk{ buffers: Arc::new(buffers), last_used: self.tick });
+        if self.viewer.is_some_and(|viewer| self.in_view(coord, viewer)) && self.visible.insert(coord) {
+            self.ready.push_back(coord);
+        }
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.cache.len() > self.cache_capacity {
+            let oldest = self.cache.iter()
+                .filter(|(coord, _)| !self.visible.contains(coord))
+                .min_by_key(|(coord, cached)| (cached.last_used, **coord))
+                .map(|(coord, _)| *coord);
+            match oldest {
+                Some(coord) => {
+                    self.cache.remove(&coord);
+                },
+                None => break,
+            }
+        }
+    }
+
+    /// Chunks that can be shown since the last call, nearest to the viewer first.
+    pub fn take_ready(&mut self) -> Vec<ChunkCoord> {
+        self.ready.drain(..).collect()
+    }
+
+    /// At most `max` of the chunks `take_ready` would return.
+    pub fn take_ready_up_to(&mut self, max: usize) -> Vec<ChunkCoord> {
+        let n = max.min(self.ready.len());
+        self.ready.drain(..n).collect()
+    }
+
+    /// Chunks the host should drop since the last call.
+    pub fn take_unload(&mut self) -> Vec<ChunkCoord> {
+        self.unload.drain(..).collect()
+    }
+
+    pub fn take_unload_up_to(&mut self, max: usize) -> Vec<ChunkCoord> {
+        let n = max.min(self.unload.len());
+        self.unload.drain(..n).collect()
+    }
+
+    /// Buffers of a generated chunk, marking it as recently used.
+    pub fn get(&mut self, coord: ChunkCoord) -> Option<Arc<ChunkBuffers>> {
+        let tick = self.tick;
+        self.cache.get_mut(&coord).map(|cached| {
+            cached.last_used = tick;
+            Arc::clone(&cached.buffers)
+        })
+    }
+
+    pub fn is_cached(&self, coord: ChunkCoord) -> bool {
+        self.cache.contains_key(&coord)
+    }
+
+    pub fn cached_count(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod streaming_tests {
+    use crate::{chunkgen::{ChunkGen2D, MountainousTerrainChunkGen}, unity::Position3D64};
+    use super::{ChunkCoord, ChunkStreamer};
+
+    fn at(x: f64, z: f64) -> Position3D64 {
+        Position3D64{ x, y: 0.0, z }
+    }
+
+    #[test]
+    fn test_chunk_coords() {
+        assert_eq!(ChunkCoord::containing(at(0.0, 0.0), 8), ChunkCoord{ x: 0, z: 0 });
+        assert_eq!(ChunkCoord::containing(at(3.9, -4.1), 8), ChunkCoord{ x: 0, z: -1 });
+        assert_eq!(ChunkCoord::containing(at(12.1, 20.0), 8), ChunkCoord{ x: 2, z: 3 });
+        assert_eq!(ChunkCoord{ x: -2, z: 1 }.plane_pos(8), at(-16.0, 8.0));
+    }
+
+    #[test]
+    fn test_streams_chunks_around_viewer() {
+        let chunkgen = MountainousTerrainChunkGen::build(4, 10.0);
+        let mut streamer = ChunkStreamer::build(chunkgen.clone(), 1, 0);
+        assert_eq!(streamer.cache_capacity(), 5);
+
+        streamer.update(at(0.0, 0.0));
+        assert_eq!(streamer.pending_count(), 5);
+        streamer.wait();
+        let mut ready = streamer.take_ready();
+        assert_eq!(ready.len(), 5);
+        assert!(streamer.take_ready().is_empty());
+        assert!(streamer.take_unload().is_empty());
+        ready.sort();
+        assert_eq!(ready, vec![ChunkCoord{ x: -1, z: 0 }, ChunkCoord{ x: 0, z: -1 }, ChunkCoord{ x: 0, z: 0 }, ChunkCoord{ x: 0, z: 1 }, ChunkCoord{ x: 1, z: 0 }]);
+
+        // the cached chunk is the one the chunkgen generates for that position
+        let coord = ChunkCoord{ x: 1, z: 0 };
+        let expected = chunkgen.generate_chunk(coord.plane_pos(4));
+        let cached = streamer.get(coord).unwrap();
+        assert_eq!(cached.v_buffer, expected.v_buffer);
+        assert_eq!(cached.tex_buffer, expected.tex_buffer);
+
+        // one chunk to the right, the left column leaves the view
+        streamer.update(at(4.0, 0.0));
+        assert_eq!(streamer.take_unload(), vec![ChunkCoord{ x: -1, z: 0 }, ChunkCoord{ x: 0, z: -1 }, ChunkCoord{ x: 0, z: 1 }]);
+        streamer.wait();
+        let mut ready = streamer.take_ready();
+        ready.sort();
+        assert_eq!(ready, vec![ChunkCoord{ x: 1, z: -1 }, ChunkCoord{ x: 1, z: 1 }, ChunkCoord{ x: 2, z: 0 }]);
+        // only invisible chunks are evicted, the least recently used first
+        assert_eq!(streamer.cached_count(), 5);
+        assert!(!streamer.is_cached(ChunkCoord{ x: -1, z: 0 }));
+
+        // walking back reuses the cached chunks that survived
+        streamer.update(at(0.0, 0.0));
+        assert_eq!(streamer.pending_count(), 3);
+        streamer.wait();
+        assert_eq!(streamer.take_ready().len(), 3);
+    }
+
+    #[test]
+    fn test_set_chunkgen_drops_cache() {
+        let mut streamer = ChunkStreamer::build(MountainousTerrainChunkGen::build(4, 10.0), 0, 4);
+        streamer.update(at(0.0, 0.0));
+        streamer.wait();
+        assert_eq!(streamer.take_ready(), vec![ChunkCoord::default()]);
+
+        streamer.set_chunkgen(MountainousTerrainChunkGen::build(4, 20.0));
+        assert_eq!(streamer.take_unload(), vec![ChunkCoord::default()]);
+        assert_eq!(streamer.cached_count(), 0);
+        streamer.wait();
+        assert_eq!(streamer.take_ready(), vec![ChunkCoord::default()]);
+        assert_eq!(streamer.get(ChunkCoord::default()).unwrap().v_buffer.len(), 25);
+    }
+}
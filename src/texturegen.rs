@@ -1,38 +1,151 @@
 extern crate more_asserts;
 
-use crate::{gradient::{BlendType, ColorKey, ColorKeyGradient}, noisegen::{MountainousTerrainNoise, Noise2D}, unity::{Position2D32, Color32}};
-use std::{panic};
-use std::ptr;
-use log::info;
-use rayon::{prelude::*};
+use crate::{analysis::{TerrainAnalysis, TerrainAttribute}, biome::{BiomeBlend, BiomeMap}, error::{Error, Result}, heightmap::{HeightMap, NormalMap}, gradient::ColorKeyGradient, noisegen::{MountainousTerrainNoise, MountainousTerrainNoiseParams, Noise2D}, noisegraph::BoxedNoiseSource, unity::{Position2D64, Color32}};
+use std::io::{self, Write};
+use half::f16;
+use serde::{Deserialize, Serialize};
+
+/// One `get` per pixel centered on `pos`, heights as the noise returns them.
+fn raw_height_map<T: TextureGen2D + Sync + ?Sized>(texturegen: &T, pos: Position2D64) -> HeightMap {
+    let (width, height) = (texturegen.get_width(), texturegen.get_height());
+    let origin = Position2D64{x: -(width as f64 / 2.0), y: -(height as f64 / 2.0)} + pos;
+    HeightMap::sample(width, height, origin, 1.0, |cur_pos| texturegen.get(cur_pos))
+}
 
 pub trait TextureGen2D {
-    fn get(&self, pos: Position2D32) -> f64;
+    fn get(&self, pos: Position2D64) -> f64;
     fn get_width(&self) -> usize;
     fn get_height(&self) -> usize;
     fn get_color_gradient(&self) -> ColorKeyGradient;
-    fn fill_texture_2d(&self, tex_buffer: &mut [Color32], pos: Position2D32) where Self: Sync { 
-        // TODO: idk why this would fail, but if it can, it should match the Result of the possible failure and return a good error message
+    fn fill_texture_2d(&self, tex_buffer: &mut [Color32], pos: Position2D64) where Self: Sync { 
+        let heights = self.sample_height_map(pos);
+        heights.fill_color_texture(0, &self.get_color_gradient(), tex_buffer);
+    }
+
+    /// `fill_texture_2d` after checking `tex_buffer` holds `get_width() * get_height()` pixels.
+    fn fill_texture(&self, tex_buffer: &mut [Color32], pos: Position2D64) -> Result<()> where Self: Sync {
+        let pix_cnt = self.get_width() * self.get_height();
+        Error::check_len("tex_buffer", tex_buffer, pix_cnt)?;
+        self.fill_texture_2d(&mut tex_buffer[..pix_cnt], pos);
+        Ok(())
+    }
+
+    /// Row major RGBA texture centered on `pos`.
+    fn generate_texture(&self, pos: Position2D64) -> Vec<Color32> where Self: Sync {
+        let mut tex_buffer = vec![Color32::default(); self.get_width() * self.get_height()];
+        self.fill_texture_2d(&mut tex_buffer, pos);
+        tex_buffer
+    }
+
+    /// One sample per pixel centered on `pos`, mapped to the [0, 1] range of the color gradient.
+    fn sample_height_map(&self, pos: Position2D64) -> HeightMap where Self: Sync {
         let width = self.get_width();
         let height = self.get_height();
-        let color_gradient = self.get_color_gradient();
-        let half_width = width as f32 / 2.0;
-        let half_height = height as f32 / 2.0;
+        let origin = Position2D64{x: -(width as f64 / 2.0), y: -(height as f64 / 2.0)} + pos;
+        HeightMap::sample(width, height, origin, 1.0, |cur_pos| self.get(cur_pos) / 2_f64 + 0.5_f64)
+    }
+
+    /// 16 bit heights of the texture centered on `pos`, `get` one pixel apart without the color remap of
+    /// `sample_height_map`, the same heights `HeightMap::from_noise` samples. Checks `buffer` holds
+    /// `get_width() * get_height()` values.
+    fn fill_heights_r16(&self, buffer: &mut [u16], pos: Position2D64) -> Result<()> where Self: Sync {
+        let pix_cnt = self.get_width() * self.get_height();
+        Error::check_len("buffer", buffer, pix_cnt)?;
+        raw_height_map(self, pos).fill_r16(0, &mut buffer[..pix_cnt]);
+        Ok(())
+    }
+
+    /// Same as `fill_heights_r16` in single precision, heights are not clamped.
+    fn fill_heights_f32(&self, buffer: &mut [f32], pos: Position2D64) -> Result<()> where Self: Sync {
+        let pix_cnt = self.get_width() * self.get_height();
+        Error::check_len("buffer", buffer, pix_cnt)?;
+        raw_height_map(self, pos).fill_f32(0, &mut buffer[..pix_cnt]);
+        Ok(())
+    }
+
+    /// Same as `fill_heights_r16` in half precision, heights are not clamped.
+    fn fill_heights_f16(&self, buffer: &mut [f16], pos: Position2D64) -> Result<()> where Self: Sync {
+        let pix_cnt = self.get_width() * self.get_height();
+        Error::check_len("buffer", buffer, pix_cnt)?;
+        raw_height_map(self, pos).fill_f16(0, &mut buffer[..pix_cnt]);
+        Ok(())
+    }
+
+    /// settings of the normal map `fill_normal_map` bakes from `get`
+    fn get_normal_map(&self) -> NormalMap {
+        NormalMap::default()
+    }
+
+    /// Normal map of the texture centered on `pos`, heights are `get` one pixel apart and sampled
+    /// on a one pixel apron so that neighbouring textures match.
+    fn fill_normal_map_2d(&self, tex_buffer: &mut [Color32], pos: Position2D64) where Self: Sync {
+        let (width, height) = (self.get_width() + 2, self.get_height() + 2);
+        let origin = Position2D64{x: -(self.get_width() as f64 / 2.0) - 1.0, y: -(self.get_height() as f64 / 2.0) - 1.0} + pos;
+        let heights = HeightMap::sample(width, height, origin, 1.0, |cur_pos| self.get(cur_pos));
+        heights.fill_normal_map_with(1, &self.get_normal_map(), tex_buffer);
+    }
+
+    /// Row major normal map centered on `pos`.
+    fn generate_normal_map(&self, pos: Position2D64) -> Vec<Color32> where Self: Sync {
+        let mut tex_buffer = vec![Color32::default(); self.get_width() * self.get_height()];
+        self.fill_normal_map_2d(&mut tex_buffer, pos);
+        tex_buffer
+    }
+
+    /// `fill_normal_map_2d` after checking `tex_buffer` holds `get_width() * get_height()` pixels.
+    fn fill_normal_map(&self, tex_buffer: &mut [Color32], pos: Position2D64) -> Result<()> where Self: Sync {
+        let pix_cnt = self.get_width() * self.get_height();
+        Error::check_len("tex_buffer", tex_buffer, pix_cnt)?;
+        self.fill_normal_map_2d(&mut tex_buffer[..pix_cnt], pos);
+        Ok(())
+    }
+
+    /// settings of the terrain attributes `fill_analysis` derives from `get`
+    fn get_analysis(&self) -> TerrainAnalysis {
+        TerrainAnalysis::default()
+    }
+
+    /// `attribute` of every pixel of the texture centered on `pos`, the heights are `get` one pixel apart.
+    /// Heights are sampled on an apron around the texture so that neighbouring textures match.
+    fn fill_analysis_2d(&self, attribute: TerrainAttribute, buffer: &mut [f32], pos: Position2D64) where Self: Sync {
+        let analysis = self.get_analysis();
+        let apron = analysis.get_reach(attribute);
+        let (width, height) = (self.get_width() + 2 * apron, self.get_height() + 2 * apron);
+        let origin = Position2D64{x: -(self.get_width() as f64 / 2.0) - apron as f64, y: -(self.get_height() as f64 / 2.0) - apron as f64} + pos;
+        let heights = HeightMap::sample(width, height, origin, 1.0, |cur_pos| self.get(cur_pos));
+        analysis.fill(&heights, attribute, apron, buffer);
+    }
 
-        tex_buffer.par_iter_mut().enumerate().for_each(|(i, cur_pix)| {
-            let cur_pos = Position2D32{x: (i % width) as f32 - half_width, y: (i / height) as f32 -  half_height} + pos;
-            let h = color_gradient.get_color(self.get(cur_pos) / 2_f64 + 0.5_f64);
-            *cur_pix = h;
-        });
+    /// `fill_analysis_2d` after checking `buffer` holds `get_width() * get_height()` values.
+    fn fill_analysis(&self, attribute: TerrainAttribute, buffer: &mut [f32], pos: Position2D64) -> Result<()> where Self: Sync {
+        let pix_cnt = self.get_width() * self.get_height();
+        Error::check_len("buffer", buffer, pix_cnt)?;
+        self.fill_analysis_2d(attribute, &mut buffer[..pix_cnt], pos);
+        Ok(())
+    }
+
+    /// `fill_analysis` encoded as grayscale pixels, see `TerrainAnalysis::encode`.
+    fn fill_analysis_texture(&self, attribute: TerrainAttribute, tex_buffer: &mut [Color32], pos: Position2D64) -> Result<()> where Self: Sync {
+        let pix_cnt = self.get_width() * self.get_height();
+        Error::check_len("tex_buffer", tex_buffer, pix_cnt)?;
+        let mut values = vec![0.0; pix_cnt];
+        self.fill_analysis_2d(attribute, &mut values, pos);
+        let analysis = self.get_analysis();
+        tex_buffer.iter_mut().zip(values.iter()).for_each(|(pix, value)| *pix = analysis.encode(attribute, *value));
+        Ok(())
     }
 }
 
-#[derive(Clone, Debug)]
+/// Every field is optional in a preset, missing ones take the values of `MountainousTerrainTextureGen::default`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MountainousTerrainTextureGen {
     pub width: usize,
     pub height: usize,
     pub noise: MountainousTerrainNoise,
     pub color_gradient: ColorKeyGradient,
+    pub normal_map: NormalMap,
+    pub analysis: TerrainAnalysis,
 }
 
 impl MountainousTerrainTextureGen {
@@ -42,18 +155,90 @@ impl MountainousTerrainTextureGen {
             height,
             noise: MountainousTerrainNoise::default(),
             color_gradient: ColorKeyGradient::default(),
+            normal_map: NormalMap::default(),
+            analysis: TerrainAnalysis::default(),
         }
     }
 
-    fn to_ptr(self) -> *mut MountainousTerrainTextureGen {
-        Box::into_raw(Box::new(self))
+    pub fn builder() -> MountainousTerrainTextureGenBuilder {
+        MountainousTerrainTextureGenBuilder::default()
     }
 
-    fn free(ptr: *mut MountainousTerrainTextureGen) {
-        if !ptr.is_null() {
-            // SHOULD BE DROPPED AUTOMATICALLY
-            let _mynoise: Box<MountainousTerrainTextureGen> = unsafe { Box::from_raw(ptr) };
+    /// Checks for settings that would generate NaN pixels or no texture at all.
+    pub fn validate(&self) -> Result<()> {
+        if self.width < 1 || self.height < 1 {
+            return Err(Error::invalid_parameter(format!("texture must be at least 1x1, got {}x{}", self.width, self.height)));
         }
+        self.color_gradient.validate()?;
+        self.normal_map.validate()?;
+        self.analysis.validate()?;
+        self.noise.params().validate()
+    }
+}
+
+/// Settings of a `MountainousTerrainTextureGen`, checked once by `build`.
+#[derive(Clone, Debug)]
+pub struct MountainousTerrainTextureGenBuilder {
+    width: usize,
+    height: usize,
+    noise: MountainousTerrainNoiseParams,
+    color_gradient: ColorKeyGradient,
+    normal_map: NormalMap,
+    analysis: TerrainAnalysis,
+}
+
+impl Default for MountainousTerrainTextureGenBuilder {
+    fn default() -> Self {
+        let defaults = MountainousTerrainTextureGen::default();
+        Self {
+            width: defaults.width,
+            height: defaults.height,
+            noise: MountainousTerrainNoiseParams::default(),
+            color_gradient: defaults.color_gradient,
+            normal_map: defaults.normal_map,
+            analysis: defaults.analysis,
+        }
+    }
+}
+
+impl MountainousTerrainTextureGenBuilder {
+    pub fn size(mut self, width: usize, height: usize) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn noise(mut self, noise: MountainousTerrainNoiseParams) -> Self {
+        self.noise = noise;
+        self
+    }
+
+    pub fn color_gradient(mut self, color_gradient: ColorKeyGradient) -> Self {
+        self.color_gradient = color_gradient;
+        self
+    }
+
+    pub fn normal_map(mut self, normal_map: NormalMap) -> Self {
+        self.normal_map = normal_map;
+        self
+    }
+
+    pub fn analysis(mut self, analysis: TerrainAnalysis) -> Self {
+        self.analysis = analysis;
+        self
+    }
+
+    pub fn build(self) -> Result<MountainousTerrainTextureGen> {
+        let texturegen = MountainousTerrainTextureGen {
+            width: self.width,
+            height: self.height,
+            noise: self.noise.try_build()?,
+            color_gradient: self.color_gradient,
+            normal_map: self.normal_map,
+            analysis: self.analysis,
+        };
+        texturegen.validate()?;
+        Ok(texturegen)
     }
 }
 
@@ -64,7 +249,7 @@ impl Default for MountainousTerrainTextureGen {
 }
 
 impl TextureGen2D for MountainousTerrainTextureGen {
-    fn get(&self, pos: Position2D32) -> f64 {
+    fn get(&self, pos: Position2D64) -> f64 {
         self.noise.get(pos)
     }
     fn get_width(&self) -> usize {
@@ -77,105 +262,265 @@ impl TextureGen2D for MountainousTerrainTextureGen {
     fn get_color_gradient(&self) -> ColorKeyGradient {
         self.color_gradient.clone()
     }
+
+    fn get_normal_map(&self) -> NormalMap {
+        self.normal_map
+    }
+
+    fn get_analysis(&self) -> TerrainAnalysis {
+        self.analysis
+    }
 }
 
-#[no_mangle]
-pub extern "C" fn free_mountainous_terrain_texturegen(mut _ptr: *mut MountainousTerrainTextureGen) {
-    if !_ptr.is_null() {
-        MountainousTerrainTextureGen::free(_ptr);
-    } 
-    _ptr = ptr::null_mut() as *mut MountainousTerrainTextureGen;
+/// Texture generator over any noise graph, see `noisegraph::NoiseNode`.
+pub struct NoiseGraphTextureGen {
+    pub width: usize,
+    pub height: usize,
+    pub noise: BoxedNoiseSource,
+    pub color_gradient: ColorKeyGradient,
 }
 
-#[no_mangle]
-pub extern "C" fn set_mountainous_terrain_texturegen_dim(texturegen: *mut MountainousTerrainTextureGen, width: usize, height: usize) {
-    if !texturegen.is_null() {
-        let mut texturegen = unsafe { Box::from_raw(texturegen) };
-        texturegen.width = width;
-        texturegen.height = height;
-        Box::leak(texturegen);
-    } 
+impl NoiseGraphTextureGen {
+    pub fn build(width: usize, height: usize, noise: BoxedNoiseSource) -> Self {
+        Self {
+            width,
+            height,
+            noise,
+            color_gradient: ColorKeyGradient::default(),
+        }
+    }
+}
+
+impl TextureGen2D for NoiseGraphTextureGen {
+    fn get(&self, pos: Position2D64) -> f64 {
+        self.noise.get(pos)
+    }
+    fn get_width(&self) -> usize {
+        self.width
+    }
+    fn get_height(&self) -> usize {
+        self.height
+    }
+
+    fn get_color_gradient(&self) -> ColorKeyGradient {
+        self.color_gradient.clone()
+    }
 }
 
-#[no_mangle]
-pub extern "C" fn set_mountainous_terrain_texturegen_noise(texturegen: *mut MountainousTerrainTextureGen, seed: u32, octaves: u32, scale: f64, persistance: f64, lacunarity: f64, displacement: f64, a: f64, bezier_bias_from: Position2D32, bezier_bias_to: Position2D32, bezier_bias_corner_curvature: f64) {
-    if !texturegen.is_null() {
-        let mut texturegen = unsafe { Box::from_raw(texturegen) };
-        texturegen.noise = MountainousTerrainNoise::build(seed, scale, persistance, lacunarity, octaves, displacement, a, bezier_bias_from, bezier_bias_to, bezier_bias_corner_curvature);
-        Box::leak(texturegen);
-    } 
+/// Texture generator blending the biomes of a `BiomeMap`, pixels blend the biome gradients.
+/// Every field is optional in a preset, missing ones take the values of `BiomeTextureGen::default`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BiomeTextureGen {
+    pub width: usize,
+    pub height: usize,
+    pub biome_map: BiomeMap,
 }
 
-#[no_mangle]
-pub extern "C" fn set_mountainous_terrain_texturegen_color_gradient(texturegen: *mut MountainousTerrainTextureGen, color_key_arr: *mut ColorKey, key_cnt: usize, blend_mode: bool) {
-    if !texturegen.is_null() {
-        let mut texturegen = unsafe { Box::from_raw(texturegen) };
-        let blend_type: BlendType = match blend_mode {
-            true => BlendType::Linear,
-            _ => BlendType::Discrete,
-        };
-        if key_cnt < 1 {
-            texturegen.color_gradient = ColorKeyGradient{blend_type, keys: Vec::new()};
-        }
-        else {
-            let res = panic::catch_unwind(|| {
-                unsafe {
-                    let keys: &mut [ColorKey] = std::slice::from_raw_parts_mut(color_key_arr, key_cnt);
-                    info!("{:?}", keys);
-                    keys
-                }
-            });
-            texturegen.color_gradient = match res {
-                Ok(keys) => ColorKeyGradient{blend_type, keys: (Vec::from(keys))},
-                Err(_) => ColorKeyGradient{blend_type, keys: Vec::new()}, // TODO set error message here
-            }
+impl BiomeTextureGen {
+    pub fn build(width: usize, height: usize, biome_map: BiomeMap) -> Self {
+        Self {
+            width,
+            height,
+            biome_map,
         }
-        Box::leak(texturegen);
-    } 
-}
-
-#[no_mangle]
-pub extern "C" fn get_mountainous_terrain_texturegen() -> *mut  MountainousTerrainTextureGen {
-    info!("getting MountainousTerrainTextureGen...");
-    MountainousTerrainTextureGen::default().to_ptr()
-}
-
-#[no_mangle]
-pub extern "C" fn fill_mountainous_terrain_texture_2d(texturegen: *mut MountainousTerrainTextureGen, bufptr: *mut Color32, pos: *mut Position2D32) -> *const u8 {
-    // info!("filling texture buffer...");
-    if texturegen.is_null() {
-        "ERROR: pointer to texturegen is null\0".as_ptr()
-    }
-    else if bufptr.is_null() {
-        "ERROR: pointer to bufptr is null\0".as_ptr()
-    }
-    else if pos.is_null() {
-        "ERROR: pointer to pos is null\0".as_ptr()
-    }
-    else {
-        let res = panic::catch_unwind(|| {
-            unsafe {
-                let texturegen = Box::from_raw(texturegen);
-                // info!("{:?}", texturegen);
-                let pix_cnt = texturegen.width * texturegen.height;
-                let tx_buffer: &mut [Color32] = std::slice::from_raw_parts_mut(bufptr, pix_cnt);
-                let pos = *pos;
-                // info!("{:?}", pos);
-                texturegen.fill_texture_2d(tx_buffer, pos);
-                Box::leak(texturegen);
-            }
-        });
-        match res {
-            Ok(_) => "OK\0".as_ptr(),
-            Err(err) => format!("{:?}\0", err).as_ptr()
+    }
+
+    /// Checks for settings that would generate NaN pixels or no texture at all.
+    pub fn validate(&self) -> Result<()> {
+        if self.width < 1 || self.height < 1 {
+            return Err(Error::invalid_parameter(format!("texture must be at least 1x1, got {}x{}", self.width, self.height)));
         }
-    }    
+        self.biome_map.validate()
+    }
+
+    /// Biome blend of every pixel `fill_texture` writes for the texture centered on `pos`, row major.
+    pub fn fill_texture_biomes(&self, biome_buffer: &mut [BiomeBlend], pos: Position2D64) -> Result<()> {
+        let pix_cnt = self.width * self.height;
+        Error::check_len("biome_buffer", biome_buffer, pix_cnt)?;
+        let origin = Position2D64{x: -(self.width as f64 / 2.0), y: -(self.height as f64 / 2.0)} + pos;
+        let grid = HeightMap::build(self.width, self.height, origin, 1.0);
+        self.biome_map.fill_texture_blends(&grid, &mut biome_buffer[..pix_cnt]);
+        Ok(())
+    }
+}
+
+impl Default for BiomeTextureGen {
+    fn default() -> Self {
+        BiomeTextureGen::build(100, 100, BiomeMap::default())
+    }
+}
+
+impl TextureGen2D for BiomeTextureGen {
+    fn get(&self, pos: Position2D64) -> f64 {
+        self.biome_map.get(pos)
+    }
+    fn get_width(&self) -> usize {
+        self.width
+    }
+    fn get_height(&self) -> usize {
+        self.height
+    }
+
+    /// gradient of the first biome, `fill_texture_2d` blends the gradients of all biomes
+    fn get_color_gradient(&self) -> ColorKeyGradient {
+        self.biome_map.biomes[0].color_gradient.clone()
+    }
+
+    fn fill_texture_2d(&self, tex_buffer: &mut [Color32], pos: Position2D64) {
+        let heights = self.sample_height_map(pos);
+        self.biome_map.fill_color_texture(&heights, tex_buffer);
+    }
+}
+
+/// Encodes a row major RGBA texture, as filled by `fill_texture_2d`, as an 8 bit PNG.
+/// Fails with `InvalidInput` if `pixels` holds less than `width * height` colors.
+pub fn write_png<W: Write>(out: W, width: usize, height: usize, pixels: &[Color32]) -> io::Result<()> {
+    let pixel_count = width.checked_mul(height).filter(|count| *count <= pixels.len())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{}x{} texture needs more than the {} pixels given", width, height, pixels.len())))?;
+    let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    let data: Vec<u8> = pixels[..pixel_count].iter().flat_map(|c| vec![c.r, c.g, c.b, c.a]).collect();
+    writer.write_image_data(&data).map_err(io::Error::other)
 }
 
 #[cfg(test)]
 mod texture_tests {
+    use crate::{error::Error, heightmap::HeightMap, unity::{Color32, Position2D64}};
+    use super::{BiomeTextureGen, MountainousTerrainTextureGen, TextureGen2D, write_png};
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn test_builder_and_checked_fill() {
+        assert!(matches!(MountainousTerrainTextureGen::builder().size(0, 4).build(), Err(Error::InvalidParameter(_))));
+        let texturegen = MountainousTerrainTextureGen::builder().size(8, 4).build().unwrap();
+        let pos = Position2D64{x: 3.0, y: -2.0};
+        let owned = texturegen.generate_texture(pos);
+        assert_eq!(owned.len(), 32);
+
+        let mut short = vec![Color32::default(); 31];
+        assert!(matches!(texturegen.fill_texture(&mut short, pos), Err(Error::BufferTooSmall { required: 32, .. })));
+        // longer buffers are fine, the extra pixels are left alone
+        let mut long = vec![Color32::default(); 40];
+        texturegen.fill_texture(&mut long, pos).unwrap();
+        assert_eq!(&long[..32], &owned[..]);
+        assert_eq!(long[32], Color32::default());
+    }
+
+    #[test]
+    fn test_biome_texture() {
+        use crate::biome::BiomeBlend;
+        let texturegen = BiomeTextureGen { width: 16, height: 8, ..BiomeTextureGen::default() };
+        texturegen.validate().unwrap();
+        let pos = Position2D64{x: 1200.0, y: -300.0};
+        let pixels = texturegen.generate_texture(pos);
+        let mut biome_buffer = vec![BiomeBlend::default(); 16 * 8];
+        texturegen.fill_texture_biomes(&mut biome_buffer, pos).unwrap();
+        for (i, blend) in biome_buffer.iter().enumerate() {
+            let world = Position2D64{x: (i % 16) as f64 - 8.0, y: (i / 16) as f64 - 4.0} + pos;
+            assert_eq!(*blend, texturegen.biome_map.blend_at(world));
+            let h = (texturegen.get(world) / 2.0 + 0.5) as f32 as f64;
+            assert_eq!(pixels[i], texturegen.biome_map.color(h, blend));
+        }
+        assert!(matches!(texturegen.fill_texture_biomes(&mut biome_buffer[..10], pos), Err(Error::BufferTooSmall { .. })));
+    }
+
+    #[test]
+    fn test_analysis_matches_across_textures() {
+        use crate::analysis::{TerrainAnalysis, TerrainAttribute};
+        let analysis = TerrainAnalysis{ occlusion_radius: 4, ..TerrainAnalysis::build(80.0) };
+        let texturegen = MountainousTerrainTextureGen::builder().size(8, 8).analysis(analysis).build().unwrap();
+        let wide = MountainousTerrainTextureGen::builder().size(16, 8).analysis(analysis).build().unwrap();
+        for attribute in [TerrainAttribute::Slope, TerrainAttribute::Aspect, TerrainAttribute::PlanCurvature, TerrainAttribute::Occlusion].iter() {
+            let mut left = vec![0.0; 64];
+            let mut right = vec![0.0; 64];
+            let mut both = vec![0.0; 128];
+            texturegen.fill_analysis(*attribute, &mut left, Position2D64{x: 96.0, y: 20.0}).unwrap();
+            texturegen.fill_analysis(*attribute, &mut right, Position2D64{x: 104.0, y: 20.0}).unwrap();
+            wide.fill_analysis(*attribute, &mut both, Position2D64{x: 100.0, y: 20.0}).unwrap();
+            for row in 0..8 {
+                assert_eq!(&both[row * 16..row * 16 + 8], &left[row * 8..row * 8 + 8]);
+                assert_eq!(&both[row * 16 + 8..row * 16 + 16], &right[row * 8..row * 8 + 8]);
+            }
+        }
+        let mut pixels = vec![Color32::default(); 64];
+        texturegen.fill_analysis_texture(TerrainAttribute::Occlusion, &mut pixels, Position2D64::default()).unwrap();
+        assert!(pixels.iter().all(|pixel| pixel.r == pixel.g && pixel.a == 255));
+        assert!(matches!(texturegen.fill_analysis_texture(TerrainAttribute::Slope, &mut pixels[..63], Position2D64::default()), Err(Error::BufferTooSmall { .. })));
+    }
+
+    #[test]
+    fn test_normal_maps() {
+        use crate::heightmap::{NormalFilter, NormalMap, NormalSpace};
+        let decode = |c: u8| c as f32 / 255.0 * 2.0 - 1.0;
+        for filter in [NormalFilter::Central, NormalFilter::Sobel].iter() {
+            let normal_map = NormalMap{ filter: *filter, ..NormalMap::build(80.0) };
+            let texturegen = MountainousTerrainTextureGen::builder().size(8, 8).normal_map(normal_map).build().unwrap();
+            let wide = MountainousTerrainTextureGen::builder().size(16, 8).normal_map(normal_map).build().unwrap();
+            let left = texturegen.generate_normal_map(Position2D64{x: 40.0, y: 8.0});
+            let right = texturegen.generate_normal_map(Position2D64{x: 48.0, y: 8.0});
+            let both = wide.generate_normal_map(Position2D64{x: 44.0, y: 8.0});
+            for row in 0..8 {
+                assert_eq!(&both[row * 16..row * 16 + 8], &left[row * 8..row * 8 + 8]);
+                assert_eq!(&both[row * 16 + 8..row * 16 + 16], &right[row * 8..row * 8 + 8]);
+            }
+            for pixel in left.iter() {
+                let (x, y, z) = (decode(pixel.r), decode(pixel.g), decode(pixel.b));
+                assert!(((x * x + y * y + z * z).sqrt() - 1.0).abs() < 0.02);
+                // tangent space normals point out of the surface
+                assert!(z > 0.0);
+            }
+
+            let object = MountainousTerrainTextureGen::builder().size(8, 8).normal_map(NormalMap{ space: NormalSpace::Object, ..normal_map }).build().unwrap();
+            let swizzled = object.generate_normal_map(Position2D64{x: 40.0, y: 8.0});
+            assert!(swizzled.iter().zip(left.iter()).all(|(o, t)| (o.r, o.g, o.b) == (t.r, t.b, t.g)));
+        }
+        let texturegen = MountainousTerrainTextureGen::builder().size(8, 8).build().unwrap();
+        let mut short = vec![Color32::default(); 63];
+        assert!(matches!(texturegen.fill_normal_map(&mut short, Position2D64::default()), Err(Error::BufferTooSmall { .. })));
+        assert!(matches!(MountainousTerrainTextureGen::builder().normal_map(NormalMap::build(f64::NAN)).build(), Err(Error::InvalidParameter(_))));
+    }
+
+    #[test]
+    fn test_height_formats() {
+        use half::f16;
+        let texturegen = MountainousTerrainTextureGen::builder().size(8, 4).build().unwrap();
+        let pos = Position2D64{x: -30.0, y: 12.0};
+        let heights = HeightMap::from_noise(&texturegen.noise, 8, 4, Position2D64{x: -34.0, y: 10.0}, 1.0);
+        let mut r16 = vec![0_u16; 32];
+        let mut floats = vec![0.0_f32; 32];
+        let mut halves = vec![f16::from_f32(0.0); 32];
+        texturegen.fill_heights_r16(&mut r16, pos).unwrap();
+        texturegen.fill_heights_f32(&mut floats, pos).unwrap();
+        texturegen.fill_heights_f16(&mut halves, pos).unwrap();
+        assert_eq!(floats, heights.data);
+        for i in 0..32 {
+            assert_eq!(r16[i], crate::heightmap::to_r16(floats[i]));
+            assert_eq!(halves[i], f16::from_f32(floats[i]));
+        }
+        // far finer than the 256 levels of the color texture
+        let mut levels = r16.clone();
+        levels.sort_unstable();
+        levels.dedup();
+        assert_eq!(levels.len(), 32);
+        assert!(matches!(texturegen.fill_heights_r16(&mut r16[..31], pos), Err(Error::BufferTooSmall { required: 32, .. })));
+        assert!(matches!(texturegen.fill_heights_f16(&mut halves[..31], pos), Err(Error::BufferTooSmall { .. })));
+    }
+
+    #[test]
+    fn test_write_png_checks_pixel_count() {
+        let pixels = vec![Color32{ r: 10, g: 20, b: 30, a: 255 }; 12];
+        let mut png = Vec::new();
+        write_png(&mut png, 4, 3, &pixels).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        let err = write_png(Vec::new(), 4, 4, &pixels).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(write_png(Vec::new(), usize::MAX, 2, &pixels).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    }
 }
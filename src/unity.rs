@@ -4,8 +4,9 @@ use std::ops::{Add, Mul, Sub};
 use euclid::Point2D;
 use half::f16;
 use lyon_geom::CubicBezierSegment;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Default, PartialEq, PartialOrd, Debug)]
+#[derive(Clone, Copy, Default, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
 #[repr(C)]
 pub struct Position2D32 {
     pub x: f32,
@@ -35,9 +36,9 @@ impl Add for Position2D32 {
     }
 }
 
-impl Into<Point2D<f32, euclid::UnknownUnit>> for Position2D32 {
-    fn into(self) -> Point2D<f32, euclid::UnknownUnit> {
-        [self.x, self.y].into()
+impl From<Position2D32> for Point2D<f32, euclid::UnknownUnit> {
+    fn from(pos: Position2D32) -> Self {
+        [pos.x, pos.y].into()
     }
 }
 
@@ -57,8 +58,8 @@ impl Mul<f64> for Position2D32 {
 
     fn mul(self, rhs: f64) -> Self::Output {
         Self {
-            x: (self.x as f64 * rhs as f64).min(f32::MAX.into()).max(f32::MIN.into()) as f32,
-            y: (self.y as f64 * rhs as f64).min(f32::MAX.into()).max(f32::MIN.into()) as f32,
+            x: (self.x as f64 * rhs).min(f32::MAX.into()).max(f32::MIN.into()) as f32,
+            y: (self.y as f64 * rhs).min(f32::MAX.into()).max(f32::MIN.into()) as f32,
         }
     }
 }
@@ -83,6 +84,82 @@ impl Add for Position3D32 {
     }
 }
 
+/// World position on the xz plane in double precision. Noise is sampled with these so terrain
+/// stays smooth far from the origin, where an `f32` can't resolve the spacing of the samples.
+#[derive(Clone, Copy, Default, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Position2D64 {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl From<Position2D32> for Position2D64 {
+    fn from(pos: Position2D32) -> Self {
+        Position2D64 {x: pos.x as f64, y: pos.y as f64}
+    }
+}
+
+impl From<Position3D64> for Position2D64 {
+    fn from(pos3d: Position3D64) -> Self {
+        Position2D64 {x: pos3d.x, y: pos3d.z}
+    }
+}
+
+impl From<[f64;2]> for Position2D64 {
+    fn from(pos: [f64;2]) -> Self {
+        Position2D64 {x: pos[0], y: pos[1]}
+    }
+}
+
+impl Add for Position2D64 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self { x: self.x + other.x, y: self.y + other.y }
+    }
+}
+
+impl Sub for Position2D64 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self { x: self.x - other.x, y: self.y - other.y }
+    }
+}
+
+impl Mul<f64> for Position2D64 {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        Self { x: self.x * rhs, y: self.y * rhs }
+    }
+}
+
+/// World position in double precision, e.g. the center of a chunk. Vertices are written
+/// relative to it in `f32`.
+#[derive(Clone, Copy, Default, PartialEq, PartialOrd, Debug)]
+#[repr(C)]
+pub struct Position3D64 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl From<Position3D32> for Position3D64 {
+    fn from(pos: Position3D32) -> Self {
+        Position3D64 {x: pos.x as f64, y: pos.y as f64, z: pos.z as f64}
+    }
+}
+
+impl Add for Position3D64 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self { x: self.x + other.x, y: self.y + other.y, z: self.z + other.z }
+    }
+}
+
+#[allow(dead_code)]
 #[derive(Clone, Copy, Default, PartialEq, PartialOrd, Debug)]
 #[repr(C)]
 pub struct Normal16 {
@@ -99,6 +176,15 @@ pub struct Normal32 {
     pub z: f32,
 }
 
+/// Weights of the x, y and z projections of a triplanar material, e.g. in a `Vector3` uv channel.
+#[derive(Clone, Copy, Default, PartialEq, PartialOrd, Debug)]
+#[repr(C)]
+pub struct TriplanarWeights32 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
 #[derive(Clone, Copy, Default, PartialEq, PartialOrd, Debug)]
 #[repr(C)]
 pub struct Tangent32 {
@@ -108,6 +194,7 @@ pub struct Tangent32 {
     pub z: f32,
 }
 
+#[allow(dead_code)]
 #[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Debug)]
 #[repr(C)]
 pub struct TangentU8 {
@@ -159,7 +246,74 @@ pub struct Quad {
     pub v6: i32,
 }
 
-#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+/// Read access to the attributes of the vertex records the chunk generators write.
+pub trait VertexAttributes: Copy {
+    fn position(&self) -> Position3D32;
+    fn uv(&self) -> TexCoord32;
+
+    fn normal(&self) -> Option<Normal32> {
+        None
+    }
+
+    fn tangent(&self) -> Option<Tangent32> {
+        None
+    }
+}
+
+impl VertexAttributes for SimpleVertex {
+    fn position(&self) -> Position3D32 {
+        self.pos
+    }
+
+    fn uv(&self) -> TexCoord32 {
+        self.uv
+    }
+}
+
+impl VertexAttributes for Vertex {
+    fn position(&self) -> Position3D32 {
+        self.pos
+    }
+
+    fn uv(&self) -> TexCoord32 {
+        self.uv
+    }
+
+    fn normal(&self) -> Option<Normal32> {
+        Some(self.norm)
+    }
+
+    fn tangent(&self) -> Option<Tangent32> {
+        Some(self.tangent)
+    }
+}
+
+/// Index buffer elements that can be read as a list of triangles, in Unity's clockwise winding.
+pub trait Faces: Copy {
+    const TRIANGLES: usize;
+    fn triangle(&self, k: usize) -> Triangle;
+}
+
+impl Faces for Triangle {
+    const TRIANGLES: usize = 1;
+
+    fn triangle(&self, _k: usize) -> Triangle {
+        *self
+    }
+}
+
+impl Faces for Quad {
+    const TRIANGLES: usize = 2;
+
+    fn triangle(&self, k: usize) -> Triangle {
+        match k {
+            0 => Triangle{v1: self.v1, v2: self.v2, v3: self.v3},
+            _ => Triangle{v1: self.v4, v2: self.v5, v3: self.v6},
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 #[repr(C)]
 pub struct Color32 {
     pub r: u8,
@@ -174,10 +328,10 @@ impl Add for Color32 {
 
     fn add(self, other: Self) -> Self {
         Self {
-            r: (self.r as i16 + other.r as i16).min(255).max(0) as u8,
-            g: (self.g as i16 + other.g as i16).min(255).max(0) as u8,
-            b: (self.b as i16 + other.b as i16).min(255).max(0) as u8,
-            a: (self.a as i16 + other.a as i16).min(255).max(0) as u8,
+            r: (self.r as i16 + other.r as i16).clamp(0, 255) as u8,
+            g: (self.g as i16 + other.g as i16).clamp(0, 255) as u8,
+            b: (self.b as i16 + other.b as i16).clamp(0, 255) as u8,
+            a: (self.a as i16 + other.a as i16).clamp(0, 255) as u8,
         }
     }
 }
@@ -187,10 +341,10 @@ impl Sub for Color32 {
 
     fn sub(self, other: Self) -> Self {
         Self {
-            r: (self.r as i16 - other.r as i16).min(255).max(0) as u8,
-            g: (self.g as i16 - other.g as i16).min(255).max(0) as u8,
-            b: (self.b as i16 - other.b as i16).min(255).max(0) as u8,
-            a: (self.a as i16 - other.a as i16).min(255).max(0) as u8,
+            r: (self.r as i16 - other.r as i16).clamp(0, 255) as u8,
+            g: (self.g as i16 - other.g as i16).clamp(0, 255) as u8,
+            b: (self.b as i16 - other.b as i16).clamp(0, 255) as u8,
+            a: (self.a as i16 - other.a as i16).clamp(0, 255) as u8,
         }
     }
 }
@@ -200,10 +354,10 @@ impl Mul<f64> for Color32 {
 
     fn mul(self, rhs: f64) -> Self::Output {
         Self {
-            r: (self.r as f64 * rhs).min(255.0).max(0.0).round() as u8,
-            g: (self.g as f64 * rhs).min(255.0).max(0.0).round() as u8,
-            b: (self.b as f64 * rhs).min(255.0).max(0.0).round() as u8,
-            a: (self.a as f64 * rhs).min(255.0).max(0.0).round() as u8,
+            r: (self.r as f64 * rhs).clamp(0.0, 255.0).round() as u8,
+            g: (self.g as f64 * rhs).clamp(0.0, 255.0).round() as u8,
+            b: (self.b as f64 * rhs).clamp(0.0, 255.0).round() as u8,
+            a: (self.a as f64 * rhs).clamp(0.0, 255.0).round() as u8,
         }
     }
 }
@@ -245,7 +399,7 @@ impl<T> Lerp for T where T: Add<T, Output = T> + Sub<T, Output = T> + Mul<f64, O
     }
 
     fn lerp_bounded(self, other: Self, t: f64) -> T {
-        let t = t.min(1_f64).max(0_f64);
+        let t = t.clamp(0_f64, 1_f64);
         self.lerp(other,t)
     }
 }
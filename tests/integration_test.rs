@@ -0,0 +1,73 @@
+use std::{fs, path::PathBuf, process::Command};
+
+fn out_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("meshgen_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn meshgen(args: &[&str]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_meshgen")).args(args).status().unwrap();
+    assert!(status.success(), "meshgen {:?} failed", args);
+}
+
+#[test]
+fn test_cli_writes_files() {
+    let dir = out_dir("cli");
+    let texture_preset = dir.join("texture.toml");
+    fs::write(&texture_preset, "[noise]\nseed = 7\nfractal_type = \"ridged\"\n").unwrap();
+    let texture_preset = texture_preset.to_str().unwrap();
+    let chunk_preset = dir.join("chunk.json");
+    fs::write(&chunk_preset, r#"{"side_len": 32, "noise": {"seed": 7, "fractal_type": "ridged"}}"#).unwrap();
+    let chunk_preset = chunk_preset.to_str().unwrap();
+
+    let texture = dir.join("texture.png");
+    meshgen(&["texture", "--preset", texture_preset, "-o", texture.to_str().unwrap(), "--width", "32", "--depth", "16", "--gradient", "0.5:204080,1:ffffff"]);
+    let heightmap = dir.join("heights.png");
+    meshgen(&["heightmap", "--preset", texture_preset, "-o", heightmap.to_str().unwrap(), "--width", "32", "--depth", "16", "--spacing", "0.5"]);
+    let glb = dir.join("world.glb");
+    meshgen(&["mesh", "--preset", chunk_preset, "-o", glb.to_str().unwrap(), "--side-len", "16", "--width", "48", "--depth", "16"]);
+    let obj = dir.join("world.obj");
+    meshgen(&["mesh", "--preset", chunk_preset, "-o", obj.to_str().unwrap(), "--side-len", "16", "--width", "32", "--depth", "16", "--format", "obj"]);
+
+    let png_size = |bytes: &[u8]| (u32::from_be_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]), u32::from_be_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]), bytes[24]);
+    assert_eq!(png_size(&fs::read(&texture).unwrap()), (32, 16, 8));
+    // half unit spacing doubles the samples
+    assert_eq!(png_size(&fs::read(&heightmap).unwrap()), (64, 32, 16));
+    let glb = fs::read(&glb).unwrap();
+    assert_eq!(&glb[0..4], b"glTF");
+    // the command line side length overrides the preset, three chunks along x
+    assert!(String::from_utf8_lossy(&glb).contains("chunk_2"));
+    assert!(dir.join("world_1_0.obj").exists());
+    assert!(dir.join("world_1_0.mtl").exists());
+    assert!(dir.join("world_1_0.png").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "ffi")]
+#[test]
+fn test_cli_heightmap_matches_ffi_heights() {
+    use meshgen::{ffi::{fill_mountainous_terrain_heights_r16_2d_f64, set_mountainous_terrain_texturegen_preset}, MeshgenError, Position2D64};
+    let dir = out_dir("heights");
+    let preset = dir.join("texture.json");
+    fs::write(&preset, r#"{"width": 16, "height": 8, "noise": {"seed": 3, "octaves": 4}}"#).unwrap();
+    let raw = dir.join("heights.r16");
+    meshgen(&["heightmap", "--preset", preset.to_str().unwrap(), "-o", raw.to_str().unwrap(), "--x", "-40", "--z", "12", "--width", "16", "--depth", "8", "--format", "raw"]);
+    let cli: Vec<u16> = fs::read(&raw).unwrap().chunks(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+
+    let mut texturegen = std::ptr::null_mut();
+    assert_eq!(meshgen::get_mountainous_terrain_texturegen(16, 8, &mut texturegen), MeshgenError::Ok);
+    let json = std::ffi::CString::new(fs::read_to_string(&preset).unwrap()).unwrap();
+    assert_eq!(set_mountainous_terrain_texturegen_preset(texturegen, json.as_ptr(), 0), MeshgenError::Ok);
+    // the cli takes the corner of the rectangle, the ffi the center of the texture
+    let mut heights = vec![0_u16; 16 * 8];
+    let mut center = Position2D64{x: -40.0 + 8.0, y: 12.0 + 4.0};
+    assert_eq!(fill_mountainous_terrain_heights_r16_2d_f64(texturegen, heights.as_mut_ptr(), &mut center), MeshgenError::Ok);
+    assert_eq!(meshgen::free_mountainous_terrain_texturegen(texturegen), MeshgenError::Ok);
+
+    assert_eq!(heights, cli);
+    // the full 16 bit range is in use, not just its upper half
+    assert!(heights.iter().any(|h| *h < u16::MAX / 2));
+    fs::remove_dir_all(&dir).unwrap();
+}
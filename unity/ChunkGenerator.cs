@@ -10,35 +10,90 @@ using System.Collections.Generic;
 
 public class ChunkGenerator : MonoBehaviour
 {
+    public enum MeshgenError : int { Ok, NullPointer, InvalidParameter, InvalidPreset, BufferTooLarge, Panic, Logger, ChunkNotReady }
+
+    [UnmanagedFunctionPointer(CallingConvention.Cdecl)]
+    delegate void LogCallback(uint level, IntPtr target, IntPtr message);
+
+    [DllImport("meshgen")]
+    private static extern MeshgenError init_logger_with_callback(LogCallback callback, uint level);
+    [DllImport("meshgen")]
+    private static extern MeshgenError shutdown_logger();
     [DllImport("meshgen")]
-    private static extern void init_logger();
+    private static extern IntPtr meshgen_last_error_message();
+    [DllImport("meshgen")]
+    private static extern void meshgen_free_error_message(IntPtr message);
 
     [DllImport("meshgen")]
-    private static extern IntPtr get_mountainous_terrain_chunkgen(UIntPtr side_len, double height);
+    private static extern MeshgenError get_mountainous_terrain_chunkgen(UIntPtr side_len, double height, out IntPtr chunkgen);
+    [DllImport("meshgen")]
+    private static extern MeshgenError free_mountainous_terrain_chunkgen(IntPtr chunkgen);
+    [DllImport("meshgen")]
+    private static extern MeshgenError fill_mountainous_terrain_chunk_with_layout(IntPtr chunkgen, IntPtr layout, IntPtr streams, IntPtr ibuf, IntPtr pos);
     [DllImport("meshgen")]
-    private static extern void free_mountainous_terrain_chunkgen(IntPtr chunkgen);
+    private static extern MeshgenError get_vertex_layout(VertexAttributeDescriptor[] descs, UIntPtr descCnt, out IntPtr layout);
     [DllImport("meshgen")]
-    private static extern IntPtr fill_mountainous_terrain_chunk(IntPtr chunkgen, IntPtr vbuf, IntPtr ibuf, IntPtr tbuf, IntPtr pos);
+    private static extern MeshgenError get_vertex_layout_stride(IntPtr layout, uint stream, out uint stride);
     [DllImport("meshgen")]
-    private static extern IntPtr get_mountainous_terrain_chunk_geometry_desc(IntPtr chunkgen, out int vCnt, out int eCnt, out int fCnt);
+    private static extern MeshgenError free_vertex_layout(IntPtr layout);
     [DllImport("meshgen")]
-    private static extern void set_mountainous_terrain_chunkgen_dim(IntPtr chunkgen, UIntPtr sideLength, double height);
+    private static extern MeshgenError get_mountainous_terrain_chunk_geometry_desc(IntPtr chunkgen, out int vCnt, out int eCnt, out int fCnt);
     [DllImport("meshgen")]
-    private static extern void set_mountainous_terrain_chunkgen_noise(IntPtr chunkgen, uint seed, uint octaves, double scale, double persistence, double lacunarity, double displacement, double bias_gain_a, IntPtr bezier_from, IntPtr bezier_to, double bezier_bias_control);
+    private static extern MeshgenError set_mountainous_terrain_chunkgen_dim(IntPtr chunkgen, UIntPtr sideLength, double height);
     [DllImport("meshgen")]
-    private static extern void set_mountainous_terrain_chunkgen_color_gradient(IntPtr chunkgen, IntPtr colorKeys, UIntPtr keyCnt, bool isLinearBlend);
+    private static extern MeshgenError set_mountainous_terrain_chunkgen_noise(IntPtr chunkgen, uint seed, uint octaves, double scale, double persistence, double lacunarity, double displacement, double bias_gain_a, IntPtr bezier_from, IntPtr bezier_to, double bezier_bias_control, uint fractalType);
+    [DllImport("meshgen")]
+    private static extern MeshgenError set_mountainous_terrain_chunkgen_color_gradient(IntPtr chunkgen, IntPtr colorKeys, UIntPtr keyCnt, bool isLinearBlend);
 
-    [StructLayout(LayoutKind.Sequential)]
-    struct ExampleVertex
-    {
-        public Vector3 pos;
-        // public Vector3 normal;
-        // public Vector4 tangent;
-        public Vector2 uv;
+    // static so the delegate outlives the native logger
+    static readonly LogCallback logCallback = OnLog;
+
+    [AOT.MonoPInvokeCallback(typeof(LogCallback))]
+    static void OnLog(uint level, IntPtr target, IntPtr message) {
+        var text = Marshal.PtrToStringAnsi(target) + ": " + Marshal.PtrToStringAnsi(message);
+        if (level == 1) {
+            Debug.LogError(text);
+        }
+        else if (level == 2) {
+            Debug.LogWarning(text);
+        }
+        else {
+            Debug.Log(text);
+        }
     }
 
+    // logs the last error message of this thread, returns false for anything but Ok
+    static bool Check(MeshgenError res) {
+        if (res == MeshgenError.Ok) {
+            return true;
+        }
+        var message = meshgen_last_error_message();
+        Debug.LogError(res + ": " + Marshal.PtrToStringAnsi(message));
+        meshgen_free_error_message(message);
+        return false;
+    }
+
+    public enum FractalType : uint { Fbm, Ridged, Billow, HybridMulti }
+
+    // the mesh gets stream 0, meshgen also writes the vertex colors of stream 1 straight into the texture
+    static readonly VertexAttributeDescriptor[] meshLayout = new[]
+    {
+        new VertexAttributeDescriptor(VertexAttribute.Position, VertexAttributeFormat.Float32, 3),
+        // new VertexAttributeDescriptor(VertexAttribute.Normal, VertexAttributeFormat.Float32, 3),
+        // new VertexAttributeDescriptor(VertexAttribute.Tangent, VertexAttributeFormat.Float32, 4),
+        new VertexAttributeDescriptor(VertexAttribute.TexCoord0, VertexAttributeFormat.Float32, 2),
+    };
+    static readonly VertexAttributeDescriptor[] fillLayout = new[]
+    {
+        meshLayout[0],
+        meshLayout[1],
+        new VertexAttributeDescriptor(VertexAttribute.Color, VertexAttributeFormat.UNorm8, 4, 1),
+    };
+
     IntPtr chunkgen;
-    NativeArray<ExampleVertex> verts;
+    IntPtr vertexLayout;
+    uint vertexStride;
+    NativeArray<byte> verts;
     NativeArray<int> tris;
     int vertexCount;
     int edgeCount;
@@ -48,6 +103,9 @@ public class ChunkGenerator : MonoBehaviour
     IntPtr texturegen; 
 
 	public bool autoUpdate;
+    // 0 off, 1 error, 2 warn, 3 info, 4 debug, 5 trace
+    [Range(0,5)]
+    public uint logLevel = 2;
     uint sideLength = 128;
     public uint seed = 0;
     [Range(0,8)]
@@ -63,6 +121,7 @@ public class ChunkGenerator : MonoBehaviour
     public double bias_gain_a = .5;
     [Range(0,1)]
     public double bezier_bias_control = .5;
+    public FractalType fractalType = FractalType.Fbm;
     public Vector2 bezier_bias_from = new Vector2(.4f, .01f);
     public Vector2 bezier_bias_to = new Vector2(.55f, .15f);
     public double height = 50;
@@ -78,7 +137,7 @@ public class ChunkGenerator : MonoBehaviour
 
     void Start()
     {
-        init_logger();
+        Check(init_logger_with_callback(logCallback, logLevel));
 
         offset = new Vector3();
         mesh = new Mesh();
@@ -91,14 +150,17 @@ public class ChunkGenerator : MonoBehaviour
         gameObject.AddComponent<MeshRenderer>();
         GetComponent<MeshRenderer>().sharedMaterial = m_Material;
 
-        chunkgen = get_mountainous_terrain_chunkgen((UIntPtr)sideLength, height);
-        var res = Marshal.PtrToStringAnsi(
-            get_mountainous_terrain_chunk_geometry_desc(chunkgen, out vertexCount, out edgeCount, out faceCount)
-        );
-        if (!res.Equals("OK")) {
+        if (!Check(get_mountainous_terrain_chunkgen((UIntPtr)sideLength, height, out chunkgen))) {
+            return;
+        }
+        if (!Check(get_mountainous_terrain_chunk_geometry_desc(chunkgen, out vertexCount, out edgeCount, out faceCount))) {
             return;
         }
-        verts = new NativeArray<ExampleVertex>(vertexCount, Allocator.Persistent);
+        if (!Check(get_vertex_layout(fillLayout, (UIntPtr)fillLayout.Length, out vertexLayout))) {
+            return;
+        }
+        Check(get_vertex_layout_stride(vertexLayout, 0, out vertexStride));
+        verts = new NativeArray<byte>(vertexCount * (int)vertexStride, Allocator.Persistent);
         tris = new NativeArray<int>(faceCount * 3, Allocator.Persistent);
         Debug.Log(chunkgen);
 
@@ -113,46 +175,38 @@ public class ChunkGenerator : MonoBehaviour
     public void GenerateChunk()
     {
         var t0 = Time.realtimeSinceStartup;
-        set_mountainous_terrain_chunkgen_dim(chunkgen, (UIntPtr)sideLength, height);
+        Check(set_mountainous_terrain_chunkgen_dim(chunkgen, (UIntPtr)sideLength, height));
         unsafe {
             fixed(Vector2* bezier_bias_from_ptr = &bezier_bias_from, bezier_bias_to_ptr = &bezier_bias_to) {
-                set_mountainous_terrain_chunkgen_noise(chunkgen, seed, octaves, scale, persistence, lacunarity, displacement, bias_gain_a, new IntPtr(bezier_bias_from_ptr), new IntPtr(bezier_bias_to_ptr), bezier_bias_control);
+                // invalid noise settings keep the previous noise
+                Check(set_mountainous_terrain_chunkgen_noise(chunkgen, seed, octaves, scale, persistence, lacunarity, displacement, bias_gain_a, new IntPtr(bezier_bias_from_ptr), new IntPtr(bezier_bias_to_ptr), bezier_bias_control, (uint)fractalType));
             }
         }
 
-        var layout = new[]
-        {
-            new VertexAttributeDescriptor(VertexAttribute.Position, VertexAttributeFormat.Float32, 3),
-            // new VertexAttributeDescriptor(VertexAttribute.Normal, VertexAttributeFormat.Float32, 3),
-            // new VertexAttributeDescriptor(VertexAttribute.Tangent, VertexAttributeFormat.Float32, 4),
-            new VertexAttributeDescriptor(VertexAttribute.TexCoord0, VertexAttributeFormat.Float32, 2),
-        };
-
         mesh.Clear();
         var colorKeys = new NativeArray<CustomGradient.ColourKey>(colorGradients.NumKeys, Allocator.Temp);
         var colorKeysArr = colorGradients.ToArray();
         colorKeys.CopyFrom(colorKeysArr);
 
         unsafe {
-            set_mountainous_terrain_chunkgen_color_gradient(
+            Check(set_mountainous_terrain_chunkgen_color_gradient(
                 chunkgen, 
                 new IntPtr(NativeArrayUnsafeUtility.GetUnsafePtr(colorKeys)), 
                 (UIntPtr)colorGradients.NumKeys, 
                 colorGradients.blendMode == CustomGradient.BlendMode.Linear
-            );
+            ));
+            var streams = stackalloc IntPtr[2];
+            streams[0] = new IntPtr(NativeArrayUnsafeUtility.GetUnsafePtr(verts));
+            streams[1] = new IntPtr(NativeArrayUnsafeUtility.GetUnsafePtr(tex_data));
             fixed(Vector3* offset_ptr = &offset) {
-                var res = Marshal.PtrToStringAnsi(
-                    fill_mountainous_terrain_chunk(chunkgen, new IntPtr(NativeArrayUnsafeUtility.GetUnsafePtr(verts)), new IntPtr(NativeArrayUnsafeUtility.GetUnsafePtr(tris)), new IntPtr(NativeArrayUnsafeUtility.GetUnsafePtr(tex_data)), new IntPtr(offset_ptr))
-                );
-                if (!res.Equals("OK")) {
-                    Debug.Log(res);
+                if (!Check(fill_mountainous_terrain_chunk_with_layout(chunkgen, vertexLayout, new IntPtr(streams), new IntPtr(NativeArrayUnsafeUtility.GetUnsafePtr(tris)), new IntPtr(offset_ptr)))) {
                     return;
                 }
             }
-            mesh.SetVertexBufferParams(vertexCount, layout);
+            mesh.SetVertexBufferParams(vertexCount, meshLayout);
             mesh.SetIndexBufferParams(faceCount * 3, UnityEngine.Rendering.IndexFormat.UInt32);
 
-            mesh.SetVertexBufferData(verts, 0, 0, vertexCount, 0, MeshUpdateFlags.DontRecalculateBounds | MeshUpdateFlags.DontValidateIndices);
+            mesh.SetVertexBufferData(verts, 0, 0, verts.Length, 0, MeshUpdateFlags.DontRecalculateBounds | MeshUpdateFlags.DontValidateIndices);
             mesh.SetIndexBufferData(tris, 0, 0, faceCount * 3);
 
             mesh.subMeshCount = 1;
@@ -174,6 +228,8 @@ public class ChunkGenerator : MonoBehaviour
     void OnApplicationQuit()
     {
         free_mountainous_terrain_chunkgen(chunkgen);
+        free_vertex_layout(vertexLayout);
+        shutdown_logger();
         verts.Dispose();
         tris.Dispose();
         Debug.Log("freed chunkgen");
@@ -0,0 +1,153 @@
+using UnityEngine;
+using UnityEngine.Rendering;
+using Unity.Collections.LowLevel.Unsafe;
+using Unity.Collections;
+
+using System;
+using System.Runtime.InteropServices;
+using System.Collections.Generic;
+
+// Streams chunks around `viewer`, the chunks are generated on the rust side off the main thread
+public class ChunkStreamer : MonoBehaviour
+{
+    [StructLayout(LayoutKind.Sequential)]
+    struct ChunkCoord
+    {
+        public long x;
+        public long z;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    struct ExampleVertex
+    {
+        public Vector3 pos;
+        public Vector2 uv;
+    }
+
+    [DllImport("meshgen")]
+    private static extern ChunkGenerator.MeshgenError get_mountainous_terrain_chunkgen(UIntPtr side_len, double height, out IntPtr chunkgen);
+    [DllImport("meshgen")]
+    private static extern ChunkGenerator.MeshgenError free_mountainous_terrain_chunkgen(IntPtr chunkgen);
+    [DllImport("meshgen")]
+    private static extern ChunkGenerator.MeshgenError get_mountainous_terrain_chunk_geometry_desc(IntPtr chunkgen, out int vCnt, out int eCnt, out int fCnt);
+    [DllImport("meshgen")]
+    private static extern ChunkGenerator.MeshgenError get_chunk_streamer(IntPtr chunkgen, uint viewRadius, UIntPtr cacheCapacity, out IntPtr streamer);
+    [DllImport("meshgen")]
+    private static extern ChunkGenerator.MeshgenError free_chunk_streamer(IntPtr streamer);
+    [DllImport("meshgen")]
+    private static extern ChunkGenerator.MeshgenError update_chunk_streamer(IntPtr streamer, ref Vector3 viewerPos);
+    [DllImport("meshgen")]
+    private static extern ChunkGenerator.MeshgenError take_chunk_streamer_ready(IntPtr streamer, [Out] ChunkCoord[] coords, UIntPtr max, out UIntPtr count);
+    [DllImport("meshgen")]
+    private static extern ChunkGenerator.MeshgenError take_chunk_streamer_unload(IntPtr streamer, [Out] ChunkCoord[] coords, UIntPtr max, out UIntPtr count);
+    [DllImport("meshgen")]
+    private static extern ChunkGenerator.MeshgenError fill_chunk_from_streamer(IntPtr streamer, ChunkCoord coord, IntPtr vbuf, IntPtr ibuf, IntPtr tbuf);
+
+    public Transform viewer;
+    public Material material;
+    public uint sideLength = 64;
+    public double height = 50;
+    [Range(1, 16)]
+    public uint viewRadius = 4;
+    public uint cacheCapacity = 128;
+    // chunks turned into meshes per frame, the rest wait for the next frames
+    public int meshesPerFrame = 4;
+
+    IntPtr chunkgen;
+    IntPtr streamer;
+    int vertexCount;
+    int edgeCount;
+    int faceCount;
+    ChunkCoord[] coords = new ChunkCoord[64];
+    Dictionary<(long, long), GameObject> chunks = new Dictionary<(long, long), GameObject>();
+
+    void Start()
+    {
+        if (get_mountainous_terrain_chunkgen((UIntPtr)sideLength, height, out chunkgen) != ChunkGenerator.MeshgenError.Ok) {
+            return;
+        }
+        get_mountainous_terrain_chunk_geometry_desc(chunkgen, out vertexCount, out edgeCount, out faceCount);
+        get_chunk_streamer(chunkgen, viewRadius, (UIntPtr)cacheCapacity, out streamer);
+    }
+
+    void Update()
+    {
+        if (streamer == IntPtr.Zero) {
+            return;
+        }
+        var viewerPos = viewer.position;
+        update_chunk_streamer(streamer, ref viewerPos);
+
+        UIntPtr count;
+        while (take_chunk_streamer_unload(streamer, coords, (UIntPtr)coords.Length, out count) == ChunkGenerator.MeshgenError.Ok && (int)count > 0) {
+            for (int i = 0; i < (int)count; i++) {
+                if (chunks.TryGetValue((coords[i].x, coords[i].z), out var chunk)) {
+                    Destroy(chunk);
+                    chunks.Remove((coords[i].x, coords[i].z));
+                }
+            }
+        }
+
+        if (take_chunk_streamer_ready(streamer, coords, (UIntPtr)meshesPerFrame, out count) != ChunkGenerator.MeshgenError.Ok) {
+            return;
+        }
+        for (int i = 0; i < (int)count; i++) {
+            var chunk = BuildChunk(coords[i]);
+            if (chunk != null) {
+                chunks[(coords[i].x, coords[i].z)] = chunk;
+            }
+        }
+    }
+
+    GameObject BuildChunk(ChunkCoord coord)
+    {
+        var verts = new NativeArray<ExampleVertex>(vertexCount, Allocator.Temp);
+        var tris = new NativeArray<int>(faceCount * 3, Allocator.Temp);
+        var colors = new NativeArray<Color32>(vertexCount, Allocator.Temp);
+        try {
+            unsafe {
+                var res = fill_chunk_from_streamer(streamer, coord,
+                    new IntPtr(NativeArrayUnsafeUtility.GetUnsafePtr(verts)),
+                    new IntPtr(NativeArrayUnsafeUtility.GetUnsafePtr(tris)),
+                    new IntPtr(NativeArrayUnsafeUtility.GetUnsafePtr(colors)));
+                if (res != ChunkGenerator.MeshgenError.Ok) {
+                    return null;
+                }
+            }
+            var layout = new[]
+            {
+                new VertexAttributeDescriptor(VertexAttribute.Position, VertexAttributeFormat.Float32, 3),
+                new VertexAttributeDescriptor(VertexAttribute.TexCoord0, VertexAttributeFormat.Float32, 2),
+            };
+            var mesh = new Mesh();
+            mesh.SetVertexBufferParams(vertexCount, layout);
+            mesh.SetIndexBufferParams(faceCount * 3, IndexFormat.UInt32);
+            mesh.SetVertexBufferData(verts, 0, 0, vertexCount, 0, MeshUpdateFlags.DontRecalculateBounds | MeshUpdateFlags.DontValidateIndices);
+            mesh.SetIndexBufferData(tris, 0, 0, faceCount * 3);
+            mesh.subMeshCount = 1;
+            mesh.SetSubMesh(0, new SubMeshDescriptor(0, faceCount * 3, MeshTopology.Triangles));
+            mesh.bounds = new Bounds(Vector3.zero, new Vector3(sideLength * 2, (float)height * 2, sideLength * 2));
+            mesh.RecalculateNormals();
+
+            var chunk = new GameObject("Chunk " + coord.x + "," + coord.z);
+            chunk.transform.parent = transform;
+            // vertices are relative to the chunk center
+            chunk.transform.localPosition = new Vector3(coord.x * (float)sideLength, 0, coord.z * (float)sideLength);
+            chunk.AddComponent<MeshFilter>().sharedMesh = mesh;
+            chunk.AddComponent<MeshRenderer>().sharedMaterial = material;
+            return chunk;
+        }
+        finally {
+            verts.Dispose();
+            tris.Dispose();
+            colors.Dispose();
+        }
+    }
+
+    void OnDestroy()
+    {
+        free_chunk_streamer(streamer);
+        free_mountainous_terrain_chunkgen(chunkgen);
+        streamer = IntPtr.Zero;
+    }
+}
@@ -22,38 +22,61 @@ public class MapGenerator : MonoBehaviour {
     [Range(0,8)]
     public double lacunarity = .5;
     public double displacement = 1.5;
+    [Range(0,1)]
+    public double bezier_bias_control = .5;
+    public ChunkGenerator.FractalType fractalType = ChunkGenerator.FractalType.Fbm;
+    public Vector2 bezier_bias_from = new Vector2(.4f, .01f);
+    public Vector2 bezier_bias_to = new Vector2(.55f, .15f);
     public Vector2 offset = new Vector2();
     public CustomGradient colorGradients;
 
     IntPtr texturegen; 
 	public bool autoUpdate;
     [DllImport("meshgen")]
-    private static extern void init_logger();
+    private static extern ChunkGenerator.MeshgenError init_logger([MarshalAs(UnmanagedType.LPUTF8Str)] string path, uint level);
+    [DllImport("meshgen")]
+    private static extern IntPtr meshgen_last_error_message();
+    [DllImport("meshgen")]
+    private static extern void meshgen_free_error_message(IntPtr message);
     [DllImport("meshgen")]
-    private static extern IntPtr get_mountainous_terrain_texturegen(UIntPtr width, UIntPtr height);
+    private static extern ChunkGenerator.MeshgenError get_mountainous_terrain_texturegen(UIntPtr width, UIntPtr height, out IntPtr texturegen);
     [DllImport("meshgen")]
-    private static extern void free_mountainous_terrain_texturegen(IntPtr texturegen);
+    private static extern ChunkGenerator.MeshgenError free_mountainous_terrain_texturegen(IntPtr texturegen);
     [DllImport("meshgen")]
-    private static extern void set_mountainous_terrain_texturegen_dim(IntPtr texturegen, UIntPtr width, UIntPtr height);
+    private static extern ChunkGenerator.MeshgenError set_mountainous_terrain_texturegen_dim(IntPtr texturegen, UIntPtr width, UIntPtr height);
     [DllImport("meshgen")]
-    private static extern void set_mountainous_terrain_texturegen_noise(IntPtr texturegen, uint seed, uint octaves, double scale, double persistence, double lacunarity, double displacement, double bias_gain_a);
+    private static extern ChunkGenerator.MeshgenError set_mountainous_terrain_texturegen_noise(IntPtr texturegen, uint seed, uint octaves, double scale, double persistence, double lacunarity, double displacement, double bias_gain_a, Vector2 bezier_from, Vector2 bezier_to, double bezier_bias_control, uint fractalType);
     [DllImport("meshgen")]
-    private static extern void set_mountainous_terrain_texturegen_color_gradient(IntPtr texturegen, IntPtr colorKeys, UIntPtr keyCnt, bool isLinearBlend);
+    private static extern ChunkGenerator.MeshgenError set_mountainous_terrain_texturegen_color_gradient(IntPtr texturegen, IntPtr colorKeys, UIntPtr keyCnt, bool isLinearBlend);
     [DllImport("meshgen")]
-    private static extern IntPtr fill_mountainous_terrain_texture_2d(IntPtr texturegen, IntPtr tbuf, IntPtr pos);
+    private static extern ChunkGenerator.MeshgenError fill_mountainous_terrain_texture_2d(IntPtr texturegen, IntPtr tbuf, IntPtr pos);
+
+    // logs the last error message of this thread, returns false for anything but Ok
+    static bool Check(ChunkGenerator.MeshgenError res) {
+        if (res == ChunkGenerator.MeshgenError.Ok) {
+            return true;
+        }
+        var message = meshgen_last_error_message();
+        Debug.LogError(res + ": " + Marshal.PtrToStringAnsi(message));
+        meshgen_free_error_message(message);
+        return false;
+    }
 
     void Start()
     {
-        init_logger();
-        texturegen = get_mountainous_terrain_texturegen((UIntPtr)mapWidth, (UIntPtr)mapHeight);
+        Check(init_logger(Application.persistentDataPath + "/meshgen.log", 3));
+        if (!Check(get_mountainous_terrain_texturegen((UIntPtr)mapWidth, (UIntPtr)mapHeight, out texturegen))) {
+            return;
+        }
         Debug.Log(texturegen);
         GenerateMap();
     }
 
     void OnApplicationQuit()
     {
-        free_mountainous_terrain_texturegen(texturegen);
-        Debug.Log("freed texturegen");
+        if (Check(free_mountainous_terrain_texturegen(texturegen))) {
+            Debug.Log("freed texturegen");
+        }
     }
 
 	public void GenerateMap() {
@@ -73,25 +96,24 @@ public class MapGenerator : MonoBehaviour {
         }
 
         // update dim
-        set_mountainous_terrain_texturegen_dim(texturegen, (UIntPtr)mapWidth, (UIntPtr)mapHeight);
-        set_mountainous_terrain_texturegen_noise(texturegen, seed, octaves, noiseScale, persistence, lacunarity, displacement, bias_gain_a);
+        if (!Check(set_mountainous_terrain_texturegen_dim(texturegen, (UIntPtr)mapWidth, (UIntPtr)mapHeight))) {
+            return;
+        }
+        // invalid noise settings keep the previous noise
+        Check(set_mountainous_terrain_texturegen_noise(texturegen, seed, octaves, noiseScale, persistence, lacunarity, displacement, bias_gain_a, bezier_bias_from, bezier_bias_to, bezier_bias_control, (uint)fractalType));
 
         unsafe {
-            set_mountainous_terrain_texturegen_color_gradient(
+            Check(set_mountainous_terrain_texturegen_color_gradient(
                 texturegen, 
                 new IntPtr(NativeArrayUnsafeUtility.GetUnsafePtr(colorKeys)), 
                 (UIntPtr)colorGradients.NumKeys, 
                 colorGradients.blendMode == CustomGradient.BlendMode.Linear
-            );
+            ));
 
             fixed(Vector2* offser_ptr = &offset) {
-                var res = Marshal.PtrToStringAnsi(
-                    fill_mountainous_terrain_texture_2d(texturegen, new IntPtr(NativeArrayUnsafeUtility.GetUnsafePtr(data)), new IntPtr(offser_ptr))
-                );
-                if (!res.Equals("OK")) {
-                    Debug.Log(res);
+                if (!Check(fill_mountainous_terrain_texture_2d(texturegen, new IntPtr(NativeArrayUnsafeUtility.GetUnsafePtr(data)), new IntPtr(offser_ptr)))) {
                     return;
-                }            
+                }
             }
         }
 		MapDisplay display = FindObjectOfType<MapDisplay> ();